@@ -195,6 +195,15 @@ pub fn is_active(sid: &str) -> bool {
     }
 }
 
+// `LockedHint` is set by the screen lockers which support it, e.g. the ones of GNOME and KDE.
+pub fn is_locked(sid: &str) -> bool {
+    if let Ok(output) = run_loginctl(Some(vec!["show-session", "-p", "LockedHint", sid])) {
+        String::from_utf8_lossy(&output.stdout).contains("LockedHint=yes")
+    } else {
+        false
+    }
+}
+
 pub fn is_active_and_seat0(sid: &str) -> bool {
     if let Ok(output) = run_loginctl(Some(vec!["show-session", sid])) {
         String::from_utf8_lossy(&output.stdout).contains("State=active")
@@ -43,6 +43,28 @@ impl TraitCapturer for Capturer {
             Capturer::WAYLAND(d) => d.frame(timeout),
         }
     }
+
+    #[cfg(feature = "dmabuf")]
+    fn frame_dmabuf<'a>(&'a mut self, timeout: Duration) -> io::Result<crate::DmaBufOrFrame<'a>> {
+        match self {
+            Capturer::X11(d) => d.frame(timeout).map(crate::DmaBufOrFrame::Frame),
+            Capturer::WAYLAND(d) => d.frame_dmabuf(timeout),
+        }
+    }
+
+    fn set_paused(&mut self, paused: bool) -> io::Result<()> {
+        match self {
+            Capturer::X11(_) => Ok(()),
+            Capturer::WAYLAND(d) => d.set_paused(paused),
+        }
+    }
+
+    fn set_frame_notify(&mut self, notify: Option<crate::FrameNotify>) -> io::Result<bool> {
+        match self {
+            Capturer::X11(_) => Ok(false),
+            Capturer::WAYLAND(d) => d.set_frame_notify(notify),
+        }
+    }
 }
 
 pub enum Display {
@@ -95,6 +117,35 @@ impl Display {
         }
     }
 
+    pub fn scale(&self) -> f64 {
+        match self {
+            Display::X11(_) => 1.0,
+            Display::WAYLAND(d) => d.scale().unwrap_or(1.0),
+        }
+    }
+
+    // Only the wayland displays can be cloned, they share the portal session.
+    pub fn try_clone(&self) -> Option<Display> {
+        match self {
+            Display::X11(_) => None,
+            Display::WAYLAND(d) => Some(Display::WAYLAND(d.clone())),
+        }
+    }
+
+    pub fn is_virtual(&self) -> bool {
+        match self {
+            Display::X11(_) => false,
+            Display::WAYLAND(d) => d.is_virtual(),
+        }
+    }
+
+    pub fn refresh_rate(&self) -> Option<f64> {
+        match self {
+            Display::X11(_) => None,
+            Display::WAYLAND(d) => d.refresh_rate(),
+        }
+    }
+
     pub fn is_online(&self) -> bool {
         match self {
             Display::X11(d) => d.is_online(),
@@ -17,8 +17,10 @@ cfg_if! {
         mod wayland;
         mod x11;
         pub use self::linux::*;
-        pub use self::wayland::set_map_err;
-        pub use self::x11::PixelBuffer;
+        pub use self::wayland::{get_map_err, is_repeated_frame, set_map_err};
+        pub use self::x11::{
+            ColorInfo, ColorRange, ColorSpace, ColorTransfer, DamageRect, HdrMetadata, PixelBuffer,
+        };
             } else {
                 mod x11;
                 pub use self::x11::*;
@@ -122,6 +124,43 @@ pub trait TraitCapturer {
 
     #[cfg(feature = "vram")]
     fn set_output_texture(&mut self, texture: bool);
+
+    // Stop pulling the frames without closing the capturer, only supported by wayland for now.
+    #[cfg(target_os = "linux")]
+    fn set_paused(&mut self, _paused: bool) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    // Call `notify` when a new frame is ready, so the caller can wait for it instead of polling `frame()`.
+    // The frame is still pulled by `frame()`. `None` to stop notifying.
+    // Returns false if the capturer does not push the frames, only supported by wayland for now.
+    #[cfg(target_os = "linux")]
+    fn set_frame_notify(&mut self, _notify: Option<FrameNotify>) -> std::io::Result<bool> {
+        Ok(false)
+    }
+
+    // Falls back to the CPU frame if the capturer or the negotiated buffer doesn't support DMA-BUF.
+    #[cfg(feature = "dmabuf")]
+    fn frame_dmabuf<'a>(
+        &'a mut self,
+        timeout: std::time::Duration,
+    ) -> std::io::Result<DmaBufOrFrame<'a>> {
+        self.frame(timeout).map(DmaBufOrFrame::Frame)
+    }
+}
+
+// Called on the streaming thread of the capturer, it should only wake up the caller.
+#[cfg(target_os = "linux")]
+pub type FrameNotify = Box<dyn Fn() + Send + Sync>;
+
+#[cfg(feature = "dmabuf")]
+pub use crate::wayland::capturable::{DmaBufFrame, DmaBufPlane, DRM_FORMAT_MOD_INVALID};
+
+#[cfg(feature = "dmabuf")]
+pub enum DmaBufOrFrame<'a> {
+    // Valid until the next frame is captured, see `DmaBufFrame`.
+    DmaBuf(DmaBufFrame),
+    Frame(Frame<'a>),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -240,10 +279,76 @@ pub struct EncodeYuvFormat {
     pub v: usize,
 }
 
+// Capture with X11 (XWayland) on a wayland desktop, if the wayland portal is unavailable.
+#[cfg(x11)]
+static X11_FALLBACK: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(x11)]
+#[inline]
+pub fn set_x11_fallback(v: bool) {
+    X11_FALLBACK.store(v, std::sync::atomic::Ordering::SeqCst);
+}
+
+#[cfg(x11)]
+#[inline]
+pub fn is_x11_fallback() -> bool {
+    X11_FALLBACK.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+// "x11" or "wayland" to override the detected session type, e.g. if the detection guesses wrong.
+#[cfg(x11)]
+pub const CAPTURE_BACKEND_ENV: &str = "RUSTDESK_CAPTURE_BACKEND";
+
+#[cfg(x11)]
+lazy_static::lazy_static! {
+    // Read once, `Some(true)` forces X11 and `Some(false)` forces wayland.
+    static ref FORCED_X11: Option<bool> =
+        parse_capture_backend(std::env::var(CAPTURE_BACKEND_ENV).ok().as_deref());
+}
+
+#[cfg(x11)]
+fn parse_capture_backend(value: Option<&str>) -> Option<bool> {
+    let value = value?.trim().to_lowercase();
+    let forced = match value.as_str() {
+        "x11" => Some(true),
+        "wayland" => Some(false),
+        "" | "auto" => None,
+        _ => {
+            log::warn!(
+                "Invalid {}={}, expected x11 or wayland, detect the backend",
+                CAPTURE_BACKEND_ENV,
+                value
+            );
+            None
+        }
+    };
+    if forced.is_some() {
+        log::info!(
+            "The capture backend is forced to {} by {}",
+            value,
+            CAPTURE_BACKEND_ENV
+        );
+    }
+    forced
+}
+
+#[cfg(x11)]
+#[inline]
+pub fn forced_x11() -> Option<bool> {
+    *FORCED_X11
+}
+
+// The detected session type, unless it's overridden by `CAPTURE_BACKEND_ENV`.
+#[cfg(x11)]
+#[inline]
+pub fn detect_x11() -> bool {
+    forced_x11().unwrap_or_else(hbb_common::platform::linux::is_x11_or_headless)
+}
+
 #[cfg(x11)]
 #[inline]
 pub fn is_x11() -> bool {
-    hbb_common::platform::linux::is_x11_or_headless()
+    is_x11_fallback() || detect_x11()
 }
 
 #[cfg(x11)]
@@ -252,7 +357,12 @@ pub fn is_cursor_embedded() -> bool {
     if is_x11() {
         x11::IS_CURSOR_EMBEDDED
     } else {
-        false
+        // The mode applied to the portal session, not the requested one.
+        #[cfg(feature = "wayland")]
+        return crate::wayland::pipewire::get_negotiated_cursor_mode()
+            == crate::wayland::pipewire::CURSOR_MODE_EMBEDDED;
+        #[cfg(not(feature = "wayland"))]
+        return false;
     }
 }
 
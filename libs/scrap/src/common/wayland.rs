@@ -17,6 +17,10 @@ pub fn set_map_err(f: fn(err: String) -> io::Error) {
     *MAP_ERR.write().unwrap() = Some(f);
 }
 
+pub fn get_map_err() -> Option<fn(err: String) -> io::Error> {
+    *MAP_ERR.read().unwrap()
+}
+
 fn map_err<E: ToString>(err: E) -> io::Error {
     if let Some(f) = *MAP_ERR.read().unwrap() {
         f(err.to_string())
@@ -40,27 +44,77 @@ impl Capturer {
     }
 }
 
+// The `WouldBlock` error of a frame identical to the last one, see `is_repeated_frame()`.
+#[derive(Debug)]
+struct RepeatedFrame;
+
+impl std::fmt::Display for RepeatedFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The frame is identical to the last one")
+    }
+}
+
+impl std::error::Error for RepeatedFrame {}
+
+// Both a repeated frame and a timeout are `WouldBlock`, only the former means the stream is alive
+// but the screen is unchanged.
+pub fn is_repeated_frame(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::WouldBlock
+        && err.get_ref().map_or(false, |e| e.is::<RepeatedFrame>())
+}
+
+fn frame_of<'a>(pixels: PixelProvider<'a>, meta: FrameMeta) -> io::Result<Frame<'a>> {
+    let (x, pixfmt, w, h) = match pixels {
+        PixelProvider::BGR0(w, h, x) => (x, crate::Pixfmt::BGRA, w, h),
+        PixelProvider::RGB0(w, h, x) => (x, crate::Pixfmt::RGBA, w, h),
+        PixelProvider::NONE if meta.repeated => {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, RepeatedFrame))
+        }
+        PixelProvider::NONE => return Err(std::io::ErrorKind::WouldBlock.into()),
+        _ => return Err(map_err("Invalid data")),
+    };
+    Ok(Frame::PixelBuffer(
+        PixelBuffer::new(x, pixfmt, w, h)
+            .with_timestamp_ns(meta.timestamp_ns)
+            .with_damage(meta.damage)
+            .with_color(meta.color)
+            .with_stride(meta.stride),
+    ))
+}
+
 impl TraitCapturer for Capturer {
     fn frame<'a>(&'a mut self, timeout: Duration) -> io::Result<Frame<'a>> {
-        match self.1.capture(timeout.as_millis() as _).map_err(map_err)? {
-            PixelProvider::BGR0(w, h, x) => Ok(Frame::PixelBuffer(PixelBuffer::new(
-                x,
-                crate::Pixfmt::BGRA,
-                w,
-                h,
-            ))),
-            PixelProvider::RGB0(w, h, x) => Ok(Frame::PixelBuffer(PixelBuffer::new(
-                x,
-                crate::Pixfmt::RGBA,
-                w,
-                h,
-            ))),
-            PixelProvider::NONE => Err(std::io::ErrorKind::WouldBlock.into()),
-            _ => Err(map_err("Invalid data")),
+        let (pixels, meta) = self
+            .1
+            .capture_with_meta(timeout.as_millis() as _)
+            .map_err(map_err)?;
+        frame_of(pixels, meta)
+    }
+
+    #[cfg(feature = "dmabuf")]
+    fn frame_dmabuf<'a>(&'a mut self, timeout: Duration) -> io::Result<crate::DmaBufOrFrame<'a>> {
+        match self
+            .1
+            .capture_dmabuf(timeout.as_millis() as _)
+            .map_err(map_err)?
+        {
+            DmaBufCapture::DmaBuf(frame) => Ok(crate::DmaBufOrFrame::DmaBuf(frame)),
+            DmaBufCapture::Pixels(pixels) => {
+                frame_of(pixels, FrameMeta::default()).map(crate::DmaBufOrFrame::Frame)
+            }
         }
     }
+
+    fn set_paused(&mut self, paused: bool) -> io::Result<()> {
+        self.1.set_paused(paused).map_err(map_err)
+    }
+
+    fn set_frame_notify(&mut self, notify: Option<crate::FrameNotify>) -> io::Result<bool> {
+        self.1.set_frame_notify(notify).map_err(map_err)
+    }
 }
 
+#[derive(Clone)]
 pub struct Display(pipewire::PipeWireCapturable);
 
 impl Display {
@@ -92,6 +146,34 @@ impl Display {
         self.0.position
     }
 
+    // The ratio of the captured size to the logical size, `None` if the portal does not report the size.
+    pub fn scale(&self) -> Option<f64> {
+        let (logical_width, _) = self.0.logical_size;
+        if logical_width == 0 || self.0.size.0 == 0 {
+            None
+        } else {
+            Some(self.0.size.0 as f64 / logical_width as f64)
+        }
+    }
+
+    // A virtual monitor created by the compositor for screen sharing, e.g. on a headless host.
+    pub fn is_virtual(&self) -> bool {
+        self.0.source_type() == pipewire::SOURCE_TYPE_VIRTUAL as u64
+    }
+
+    // A single window selected in the portal dialog, see `pipewire::SOURCE_TYPE_WINDOW`.
+    pub fn is_window(&self) -> bool {
+        self.0.source_type() == pipewire::SOURCE_TYPE_WINDOW as u64
+    }
+
+    pub fn node_id(&self) -> u64 {
+        self.0.path()
+    }
+
+    pub fn refresh_rate(&self) -> Option<f64> {
+        self.0.refresh_rate
+    }
+
     pub fn is_online(&self) -> bool {
         true
     }
@@ -100,7 +182,8 @@ impl Display {
         false
     }
 
+    // The portal does not expose the connector names, the stream id is used instead.
     pub fn name(&self) -> String {
-        "".to_owned()
+        self.0.name.clone()
     }
 }
@@ -33,12 +33,90 @@ impl TraitCapturer for Capturer {
     }
 }
 
+// A changed region of a frame, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DamageRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+// The YUV matrix of the frames, to be tagged by the encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    BT601,
+    BT709,
+    BT2020,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorRange {
+    // 16-235
+    Limited,
+    // 0-255
+    Full,
+}
+
+// The transfer function of the frames, PQ and HLG are HDR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorTransfer {
+    Sdr,
+    // SMPTE ST 2084
+    Pq,
+    // ARIB STD-B67
+    Hlg,
+}
+
+// The static HDR metadata of the stream, see SMPTE ST 2086 and CTA-861.3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HdrMetadata {
+    // (min, max) luminance of the mastering display in 0.0001 cd/m2.
+    pub mastering_luminance: Option<(u32, u32)>,
+    // (MaxCLL, MaxFALL) in cd/m2.
+    pub content_light_level: Option<(u16, u16)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorInfo {
+    pub space: ColorSpace,
+    pub range: ColorRange,
+    pub transfer: ColorTransfer,
+    // Only for the HDR transfers, `None` if the stream does not carry it.
+    pub hdr: Option<HdrMetadata>,
+}
+
+impl Default for ColorInfo {
+    // Assumed if the capturer does not report it.
+    fn default() -> Self {
+        Self {
+            space: ColorSpace::BT709,
+            range: ColorRange::Limited,
+            transfer: ColorTransfer::Sdr,
+            hdr: None,
+        }
+    }
+}
+
+impl ColorInfo {
+    #[inline]
+    pub fn is_hdr(&self) -> bool {
+        self.transfer != ColorTransfer::Sdr
+    }
+}
+
 pub struct PixelBuffer<'a> {
     data: &'a [u8],
     pixfmt: Pixfmt,
     width: usize,
     height: usize,
     stride: Vec<usize>,
+    // The presentation timestamp in nanoseconds, only provided by the wayland capturer.
+    timestamp_ns: Option<u64>,
+    // `None` if the whole frame is dirty, only provided by the wayland capturer.
+    damage: Option<Vec<DamageRect>>,
+    // The negotiated color space and range, only provided by the wayland capturer.
+    color: Option<ColorInfo>,
 }
 
 impl<'a> PixelBuffer<'a> {
@@ -52,6 +130,54 @@ impl<'a> PixelBuffer<'a> {
             width,
             height,
             stride,
+            timestamp_ns: None,
+            damage: None,
+            color: None,
+        }
+    }
+
+    pub fn with_timestamp_ns(mut self, timestamp_ns: Option<u64>) -> Self {
+        self.timestamp_ns = timestamp_ns;
+        self
+    }
+
+    pub fn timestamp_ns(&self) -> Option<u64> {
+        self.timestamp_ns
+    }
+
+    pub fn with_damage(mut self, damage: Option<Vec<DamageRect>>) -> Self {
+        self.damage = damage;
+        self
+    }
+
+    pub fn with_color(mut self, color: Option<ColorInfo>) -> Self {
+        self.color = color;
+        self
+    }
+
+    // `None` keeps the stride computed from the data size, only the wayland capturer knows the padding.
+    pub fn with_stride(mut self, stride: Option<usize>) -> Self {
+        if let Some(stride) = stride {
+            self.stride = vec![stride];
+        }
+        self
+    }
+
+    // `None` if the capturer does not know it, `ColorInfo::default()` is assumed then.
+    pub fn color(&self) -> Option<ColorInfo> {
+        self.color
+    }
+
+    // The changed regions since the last frame, the whole frame if the damage is not tracked.
+    pub fn damage(&self) -> Vec<DamageRect> {
+        match &self.damage {
+            Some(damage) => damage.clone(),
+            None => vec![DamageRect {
+                x: 0,
+                y: 0,
+                width: self.width,
+                height: self.height,
+            }],
         }
     }
 }
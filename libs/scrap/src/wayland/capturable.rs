@@ -1,6 +1,8 @@
 use std::boxed::Box;
 use std::error::Error;
 
+use crate::{ColorInfo, DamageRect, FrameNotify};
+
 pub enum PixelProvider<'a> {
     // 8 bits per color
     RGB(usize, usize, &'a [u8]),
@@ -23,8 +25,82 @@ impl<'a> PixelProvider<'a> {
     }
 }
 
+/// A plane of a DMA-BUF frame.
+#[cfg(feature = "dmabuf")]
+#[derive(Debug, Clone, Copy)]
+pub struct DmaBufPlane {
+    pub offset: usize,
+    pub stride: usize,
+}
+
+/// A frame still in the GPU memory, see `Recorder::capture_dmabuf()`.
+///
+/// The fd is owned by the buffer held by the recorder, it is valid until the next capture or until
+/// the recorder is dropped. Duplicate it with `dup()` if it's needed longer, never close it.
+#[cfg(feature = "dmabuf")]
+#[derive(Debug, Clone)]
+pub struct DmaBufFrame {
+    pub fd: std::os::unix::io::RawFd,
+    /// The DRM fourcc code, e.g. "AR24".
+    pub fourcc: String,
+    /// The DRM format modifier, `DRM_FORMAT_MOD_INVALID` if it's not reported by pipewire.
+    pub modifier: u64,
+    pub width: usize,
+    pub height: usize,
+    pub planes: Vec<DmaBufPlane>,
+    /// The presentation timestamp in nanoseconds.
+    pub timestamp_ns: Option<u64>,
+}
+
+#[cfg(feature = "dmabuf")]
+pub const DRM_FORMAT_MOD_INVALID: u64 = 0x00ff_ffff_ffff_ffff;
+
+#[cfg(feature = "dmabuf")]
+pub enum DmaBufCapture<'a> {
+    DmaBuf(DmaBufFrame),
+    /// The negotiated buffer is not a DMA-BUF, the frame is copied to the CPU memory.
+    Pixels(PixelProvider<'a>),
+}
+
+/// The metadata of a captured frame.
+#[derive(Debug, Clone, Default)]
+pub struct FrameMeta {
+    /// The presentation timestamp in nanoseconds, monotonic for a recorder.
+    pub timestamp_ns: Option<u64>,
+    /// The changed regions since the last frame, `None` if the whole frame is dirty.
+    pub damage: Option<Vec<DamageRect>>,
+    /// The negotiated color space and range, `None` if the caps do not carry the colorimetry.
+    pub color: Option<ColorInfo>,
+    /// The bytes of a row if the rows are padded, `None` if they are packed.
+    pub stride: Option<usize>,
+    /// The frame is identical to the last one, it's dropped and `PixelProvider::NONE` is returned.
+    /// Not set if no frame arrived before the timeout.
+    pub repeated: bool,
+}
+
 pub trait Recorder {
     fn capture(&mut self, timeout_ms: u64) -> Result<PixelProvider, Box<dyn Error>>;
+    /// Also return the metadata of the frame.
+    fn capture_with_meta(
+        &mut self,
+        timeout_ms: u64,
+    ) -> Result<(PixelProvider, FrameMeta), Box<dyn Error>> {
+        self.capture(timeout_ms).map(|p| (p, FrameMeta::default()))
+    }
+    /// Pause or resume the stream without closing it, so no new session has to be requested.
+    fn set_paused(&mut self, _paused: bool) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+    /// Call `notify` when a new frame is ready to be captured, `None` to stop.
+    /// Returns false if the recorder can't push the frames, the caller has to poll `capture()` then.
+    fn set_frame_notify(&mut self, _notify: Option<FrameNotify>) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+    /// Capture the frame as a DMA-BUF if the negotiated buffer type supports it.
+    #[cfg(feature = "dmabuf")]
+    fn capture_dmabuf(&mut self, timeout_ms: u64) -> Result<DmaBufCapture, Box<dyn Error>> {
+        self.capture(timeout_ms).map(DmaBufCapture::Pixels)
+    }
 }
 
 pub trait BoxCloneCapturable {
@@ -2,9 +2,12 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::os::unix::io::AsRawFd;
 use std::process::Command;
-use std::sync::{atomic::AtomicBool, Arc, Mutex};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32},
+    Arc, Mutex,
+};
 use std::time::Duration;
-use tracing::{debug, trace, warn};
+use tracing::{debug, info, trace, warn};
 
 use dbus::{
     arg::{OwnedFd, PropMap, RefArg, Variant},
@@ -15,19 +18,100 @@ use dbus::{
 
 use gstreamer as gst;
 use gstreamer::prelude::*;
-use gstreamer_app::AppSink;
+use gstreamer_app::{AppSink, AppSinkCallbacks};
 
 use hbb_common::config;
 
-use super::capturable::PixelProvider;
 use super::capturable::{Capturable, Recorder};
+#[cfg(feature = "dmabuf")]
+use super::capturable::{DmaBufCapture, DmaBufFrame, DmaBufPlane, DRM_FORMAT_MOD_INVALID};
+use super::capturable::{FrameMeta, PixelProvider};
 use super::remote_desktop_portal::OrgFreedesktopPortalRemoteDesktop as remote_desktop_portal;
 use super::request_portal::OrgFreedesktopPortalRequestResponse;
 use super::screencast_portal::OrgFreedesktopPortalScreenCast as screencast_portal;
+use crate::FrameNotify;
+use crate::{ColorInfo, ColorRange, ColorSpace, ColorTransfer, DamageRect, HdrMetadata, Pixfmt};
 use lazy_static::lazy_static;
 
 lazy_static! {
     pub static ref RDP_SESSION_INFO: Mutex<Option<RdpSessionInfo>> = Mutex::new(None);
+    // Overrides `DBUS_SESSION_BUS_ADDRESS`, see `set_session_bus_address()`.
+    static ref SESSION_BUS_ADDRESS: Mutex<Option<String>> = Mutex::new(None);
+    // The maximum size of the frames, see `set_capture_max_size()`.
+    static ref CAPTURE_MAX_SIZE: Mutex<Option<(usize, usize)>> = Mutex::new(None);
+    // The pixel formats negotiated first, see `set_format_preference()`.
+    static ref FORMAT_PREFERENCE: Mutex<Vec<Pixfmt>> = Mutex::new(vec![Pixfmt::BGRA, Pixfmt::RGBA]);
+    // See `set_on_access_revoked()`.
+    static ref ON_ACCESS_REVOKED: Mutex<Option<Arc<dyn Fn() + Send + Sync>>> = Mutex::new(None);
+}
+
+// See `set_stop_stream_on_pause()`.
+static STOP_STREAM_ON_PAUSE: AtomicBool = AtomicBool::new(false);
+
+// Set when the portal closes the session, reset when a new session is created.
+static ACCESS_REVOKED: AtomicBool = AtomicBool::new(false);
+
+// The interval of processing the session connection, to receive the "Closed" signal.
+const SESSION_WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+// `callback` is called from the dbus thread after the session closed by the portal is dropped,
+// e.g. the user clicked "Stop Sharing" in the system indicator.
+// Stop the pipewire streams on the next pause instead of only stop pulling the frames,
+// so the compositor's recording indicator is off. The portal session is kept, resuming does not prompt.
+pub fn set_stop_stream_on_pause(stop: bool) {
+    STOP_STREAM_ON_PAUSE.store(stop, std::sync::atomic::Ordering::SeqCst);
+}
+
+// The formats preferred by the encoder are negotiated first, to avoid the color conversions.
+// Only BGRA and RGBA are received from pipewire, the others are ignored,
+// and the missing ones are appended as the fallbacks. Returns the effective order.
+// It's used by the capturers created later.
+pub fn set_format_preference(formats: &[Pixfmt]) -> Vec<Pixfmt> {
+    let mut order = Vec::new();
+    for f in formats.iter().chain(&[Pixfmt::BGRA, Pixfmt::RGBA]) {
+        if caps_format(*f).is_some() && !order.contains(f) {
+            order.push(*f);
+        }
+    }
+    *FORMAT_PREFERENCE.lock().unwrap() = order.clone();
+    order
+}
+
+fn caps_format(format: Pixfmt) -> Option<&'static str> {
+    match format {
+        Pixfmt::BGRA => Some("BGRx"),
+        Pixfmt::RGBA => Some("RGBx"),
+        _ => None,
+    }
+}
+
+pub fn set_on_access_revoked(callback: Option<Arc<dyn Fn() + Send + Sync>>) {
+    *ON_ACCESS_REVOKED.lock().unwrap() = callback;
+}
+
+pub fn is_access_revoked() -> bool {
+    ACCESS_REVOKED.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+// Connect to another session bus than the one of the environment, e.g. the bus of the logged-in user
+// for a system service. `None` to use `DBUS_SESSION_BUS_ADDRESS`.
+pub fn set_session_bus_address(address: Option<String>) {
+    *SESSION_BUS_ADDRESS.lock().unwrap() = address;
+}
+
+pub fn get_session_bus_address() -> Option<String> {
+    SESSION_BUS_ADDRESS.lock().unwrap().clone()
+}
+
+fn new_session_connection() -> Result<SyncConnection, dbus::Error> {
+    match get_session_bus_address() {
+        Some(address) => {
+            let mut channel = dbus::channel::Channel::open_private(&address)?;
+            channel.register()?;
+            Ok(SyncConnection::from(channel))
+        }
+        None => SyncConnection::new_session(),
+    }
 }
 
 #[inline]
@@ -40,6 +124,16 @@ pub fn is_rdp_session_hold() -> bool {
     RDP_SESSION_INFO.lock().unwrap().is_some()
 }
 
+// Whether a new session restores the sources of the current one without prompting the user.
+pub fn is_session_restorable() -> bool {
+    RDP_SESSION_INFO
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(false, |info| info.is_support_restore_token)
+        && has_restore_token()
+}
+
 pub fn try_close_session() {
     let mut rdp_info = RDP_SESSION_INFO.lock().unwrap();
     let mut close = false;
@@ -62,12 +156,14 @@ pub struct RdpSessionInfo {
     pub is_support_restore_token: bool,
     pub resolution: Arc<Mutex<Option<(usize, usize)>>>,
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct PwStreamInfo {
     pub path: u64,
     source_type: u64,
     position: (i32, i32),
     size: (usize, usize),
+    // The "id" property of the stream, stable across the restored sessions. Empty if not reported.
+    id: String,
 }
 
 impl PwStreamInfo {
@@ -109,9 +205,25 @@ pub struct PipeWireCapturable {
     source_type: u64,
     pub position: (i32, i32),
     pub size: (usize, usize),
+    // The size reported by the portal, in logical pixels.
+    pub logical_size: (usize, usize),
+    pub name: String,
+    // The max framerate negotiated with pipewire, `None` if unknown.
+    pub refresh_rate: Option<f64>,
 }
 
 impl PipeWireCapturable {
+    #[inline]
+    pub fn source_type(&self) -> u64 {
+        self.source_type
+    }
+
+    // The pipewire node id of the stream.
+    #[inline]
+    pub fn path(&self) -> u64 {
+        self.path
+    }
+
     fn new(
         conn: Arc<SyncConnection>,
         fd: OwnedFd,
@@ -120,15 +232,18 @@ impl PipeWireCapturable {
     ) -> Self {
         // alternative to get screen resolution as stream.size is not always correct ex: on fractional scaling
         // https://github.com/rustdesk/rustdesk/issues/6116#issuecomment-1817724244
-        let size = get_res(Self {
+        let (size, refresh_rate) = get_res(Self {
             dbus_conn: conn.clone(),
             fd: fd.clone(),
             path: stream.path,
             source_type: stream.source_type,
             position: stream.position,
             size: stream.size,
+            logical_size: stream.size,
+            name: stream.id.clone(),
+            refresh_rate: None,
         })
-        .unwrap_or(stream.size);
+        .unwrap_or((stream.size, None));
         *resolution.lock().unwrap() = Some(size);
         Self {
             dbus_conn: conn,
@@ -137,6 +252,9 @@ impl PipeWireCapturable {
             source_type: stream.source_type,
             position: stream.position,
             size,
+            logical_size: stream.size,
+            name: stream.id,
+            refresh_rate,
         }
     }
 }
@@ -177,8 +295,11 @@ impl Capturable for PipeWireCapturable {
     }
 }
 
-fn get_res(capturable: PipeWireCapturable) -> Result<(usize, usize), Box<dyn Error>> {
-    let rec = PipeWireRecorder::new(capturable)?;
+fn get_res(
+    capturable: PipeWireCapturable,
+) -> Result<((usize, usize), Option<f64>), Box<dyn Error>> {
+    // The native size, `CAPTURE_MAX_SIZE` scales only the frames.
+    let rec = PipeWireRecorder::with_max_size(capturable, None)?;
     if let Some(sample) = rec
         .appsink
         .try_pull_sample(gst::ClockTime::from_mseconds(300))
@@ -192,7 +313,17 @@ fn get_res(capturable: PipeWireCapturable) -> Result<(usize, usize), Box<dyn Err
         let h: i32 = cap.get_value("height")?.get_some()?;
         let w = w as usize;
         let h = h as usize;
-        Ok((w, h))
+        // "framerate" is 0/1 if the framerate is variable, the max framerate is the refresh rate.
+        let refresh_rate = ["max-framerate", "framerate"].iter().find_map(|k| {
+            let f: gst::Fraction = cap.get_value(k).ok()?.get_some().ok()?;
+            let (n, d) = (*f.numer(), *f.denom());
+            if n > 0 && d > 0 {
+                Some(n as f64 / d as f64)
+            } else {
+                None
+            }
+        });
+        Ok(((w, h), refresh_rate))
     } else {
         Err(Box::new(GStreamerError(
             "Error getting screen resolution".into(),
@@ -205,15 +336,113 @@ pub struct PipeWireRecorder {
     buffer_cropped: Vec<u8>,
     pix_fmt: String,
     is_cropped: bool,
+    // The bytes of a row of the last frame, pipewire may pad the rows.
+    stride: usize,
     pipeline: gst::Pipeline,
     appsink: AppSink,
     width: usize,
     height: usize,
     saved_raw_data: Vec<u8>, // for faster compare and copy
+    // The presentation timestamp of the last frame in nanoseconds, in the running time of the pipeline.
+    pts: Option<u64>,
+    // The damage of the last frame, see `set_damage_tracking()`.
+    damage: Option<Vec<DamageRect>>,
+    // The colorimetry of the caps, see `parse_colorimetry()`.
+    color: Option<ColorInfo>,
+    // Keeps the fd of the last `DmaBufFrame` valid.
+    #[cfg(feature = "dmabuf")]
+    dmabuf_sample: Option<gst::Sample>,
+}
+
+#[cfg(feature = "dmabuf")]
+#[link(name = "gstallocators-1.0")]
+extern "C" {
+    fn gst_is_dmabuf_memory(mem: *mut gst::ffi::GstMemory) -> gst::glib::ffi::gboolean;
+    fn gst_dmabuf_memory_get_fd(mem: *mut gst::ffi::GstMemory) -> std::os::raw::c_int;
+}
+
+// The memory of the buffers negotiated with pipewire, see `get_buffer_mode()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferMode {
+    DmaBuf,
+    // Copied to the CPU memory by pipewire, e.g. from a MemFd.
+    Cpu,
 }
 
+// The mode of the last recorder created, 0 if none.
+static BUFFER_MODE: AtomicU32 = AtomicU32::new(0);
+
+pub fn get_buffer_mode() -> Option<BufferMode> {
+    match BUFFER_MODE.load(std::sync::atomic::Ordering::Relaxed) {
+        1 => Some(BufferMode::DmaBuf),
+        2 => Some(BufferMode::Cpu),
+        _ => None,
+    }
+}
+
+fn set_buffer_mode(mode: BufferMode) {
+    let v = match mode {
+        BufferMode::DmaBuf => 1,
+        BufferMode::Cpu => 2,
+    };
+    BUFFER_MODE.store(v, std::sync::atomic::Ordering::Relaxed);
+}
+
+// How long to wait for a negotiation error of the DMA-BUF pipeline.
+#[cfg(feature = "dmabuf")]
+const NEGOTIATION_TIMEOUT_MS: u64 = 500;
+
 impl PipeWireRecorder {
+    // Prefers the DMA-BUFs, and falls back to the CPU buffers if they can't be negotiated.
     pub fn new(capturable: PipeWireCapturable) -> Result<Self, Box<dyn Error>> {
+        Self::with_max_size(capturable, get_capture_max_size())
+    }
+
+    fn with_max_size(
+        capturable: PipeWireCapturable,
+        max_size: Option<(usize, usize)>,
+    ) -> Result<Self, Box<dyn Error>> {
+        #[cfg(feature = "dmabuf")]
+        match Self::new_with_mode(capturable.clone(), BufferMode::DmaBuf, max_size)
+            .and_then(|r| r.check_negotiated().map(|_| r))
+        {
+            Ok(r) => {
+                set_buffer_mode(BufferMode::DmaBuf);
+                return Ok(r);
+            }
+            Err(e) => warn!(
+                "Failed to negotiate the DMA-BUFs with pipewire, fall back to the CPU buffers: {}",
+                e
+            ),
+        }
+        let r = Self::new_with_mode(capturable, BufferMode::Cpu, max_size)?;
+        set_buffer_mode(BufferMode::Cpu);
+        Ok(r)
+    }
+
+    // The errors of the negotiation, e.g. "not-negotiated", are posted on the bus after the pipeline starts.
+    #[cfg(feature = "dmabuf")]
+    fn check_negotiated(&self) -> Result<(), Box<dyn Error>> {
+        let bus = self
+            .pipeline
+            .get_bus()
+            .ok_or("Failed to get the pipeline bus")?;
+        if let Some(msg) = bus.timed_pop_filtered(
+            gst::ClockTime::from_mseconds(NEGOTIATION_TIMEOUT_MS),
+            &[gst::MessageType::Error],
+        ) {
+            if let gst::MessageView::Error(err) = msg.view() {
+                return Err(Box::new(GStreamerError(err.get_error().to_string())));
+            }
+        }
+        Ok(())
+    }
+
+    fn new_with_mode(
+        capturable: PipeWireCapturable,
+        mode: BufferMode,
+        max_size: Option<(usize, usize)>,
+    ) -> Result<Self, Box<dyn Error>> {
         let pipeline = gst::Pipeline::new(None);
 
         let src = gst::ElementFactory::make("pipewiresrc", None)?;
@@ -223,27 +452,60 @@ impl PipeWireRecorder {
 
         // For some reason pipewire blocks on destruction of AppSink if this is not set to true,
         // see: https://gitlab.freedesktop.org/pipewire/pipewire/-/issues/982
-        src.set_property("always-copy", &true)?;
+        // But the copies are always in the CPU memory, so it's off to receive the DMA-BUFs.
+        src.set_property("always-copy", &(mode == BufferMode::Cpu))?;
+        set_buffer_count_of(&src, get_buffer_count());
 
         let sink = gst::ElementFactory::make("appsink", None)?;
         sink.set_property("drop", &true)?;
         sink.set_property("max-buffers", &1u32)?;
 
-        pipeline.add_many(&[&src, &sink])?;
-        src.link(&sink)?;
+        let size = fit_capture_size(capturable.size, max_size);
+        if size != capturable.size {
+            // The portal doesn't offer scaled streams, the frames are scaled on the CPU.
+            info!(
+                "Scale the pipewire stream {:?} to {:?} with videoscale",
+                capturable.size, size
+            );
+            let scale = gst::ElementFactory::make("videoscale", None)?;
+            pipeline.add_many(&[&src, &scale, &sink])?;
+            gst::Element::link_many(&[&src, &scale, &sink])?;
+        } else {
+            pipeline.add_many(&[&src, &sink])?;
+            src.link(&sink)?;
+        }
 
         let appsink = sink
             .dynamic_cast::<AppSink>()
             .map_err(|_| GStreamerError("Sink element is expected to be an appsink!".into()))?;
+        // The structures of the caps are in the order of preference.
+        let formats: Vec<&str> = FORMAT_PREFERENCE
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|f| caps_format(*f))
+            .collect();
         let mut caps = gst::Caps::new_empty();
-        caps.merge_structure(gst::structure::Structure::new(
-            "video/x-raw",
-            &[("format", &"BGRx")],
-        ));
-        caps.merge_structure(gst::structure::Structure::new(
-            "video/x-raw",
-            &[("format", &"RGBx")],
-        ));
+        for format in formats.iter().cloned() {
+            let mut structure =
+                gst::structure::Structure::new("video/x-raw", &[("format", &format)]);
+            if size != capturable.size {
+                structure.set("width", &(size.0 as i32));
+                structure.set("height", &(size.1 as i32));
+            }
+            caps.merge_structure(structure);
+        }
+        #[cfg(feature = "dmabuf")]
+        if mode == BufferMode::DmaBuf {
+            for format in formats.iter().cloned() {
+                caps.get_mut()
+                    .ok_or("Failed to get caps")?
+                    .merge_structure_full(
+                        gst::structure::Structure::new("video/x-raw", &[("format", &format)]),
+                        Some(gst::CapsFeatures::new(&["memory:DMABuf"])),
+                    );
+            }
+        }
         appsink.set_caps(Some(&caps));
 
         pipeline.set_state(gst::State::Playing)?;
@@ -256,87 +518,201 @@ impl PipeWireRecorder {
             height: 0,
             buffer_cropped: vec![],
             is_cropped: false,
+            stride: 0,
             saved_raw_data: Vec::new(),
+            pts: None,
+            color: None,
+            damage: None,
+            #[cfg(feature = "dmabuf")]
+            dmabuf_sample: None,
         })
     }
-}
 
-impl Recorder for PipeWireRecorder {
-    fn capture(&mut self, timeout_ms: u64) -> Result<PixelProvider, Box<dyn Error>> {
-        if let Some(sample) = self
-            .appsink
-            .try_pull_sample(gst::ClockTime::from_mseconds(timeout_ms))
-        {
-            let cap = sample
-                .get_caps()
-                .ok_or("Failed get caps")?
-                .get_structure(0)
-                .ok_or("Failed to get structure")?;
-            let w: i32 = cap.get_value("width")?.get_some()?;
-            let h: i32 = cap.get_value("height")?.get_some()?;
-            let w = w as usize;
-            let h = h as usize;
-            self.pix_fmt = cap
-                .get::<&str>("format")?
-                .ok_or("Failed to get pixel format")?
-                .to_string();
-
-            let buf = sample
-                .get_buffer_owned()
-                .ok_or_else(|| GStreamerError("Failed to get owned buffer.".into()))?;
-            let mut crop = buf
-                .get_meta::<gstreamer_video::VideoCropMeta>()
-                .map(|m| m.get_rect());
-            // only crop if necessary
-            if Some((0, 0, w as u32, h as u32)) == crop {
-                crop = None;
-            }
-            let buf = buf
-                .into_mapped_buffer_readable()
-                .map_err(|_| GStreamerError("Failed to map buffer.".into()))?;
-            if let Err(..) = crate::would_block_if_equal(&mut self.saved_raw_data, buf.as_slice()) {
-                return Ok(PixelProvider::NONE);
-            }
-            let buf_size = buf.get_size();
+    // `None` if the memory of the sample is not a DMA-BUF.
+    #[cfg(feature = "dmabuf")]
+    fn dmabuf_of(sample: &gst::Sample) -> Result<Option<DmaBufFrame>, Box<dyn Error>> {
+        let buf = sample
+            .get_buffer()
+            .ok_or_else(|| GStreamerError("Failed to get buffer.".into()))?;
+        if buf.n_memory() == 0 {
+            return Ok(None);
+        }
+        let mem = buf.peek_memory(0).as_mut_ptr();
+        if unsafe { gst_is_dmabuf_memory(mem) } == 0 {
+            return Ok(None);
+        }
+        let fd = unsafe { gst_dmabuf_memory_get_fd(mem) };
+        if fd < 0 {
+            return Ok(None);
+        }
+        let cap = sample
+            .get_caps()
+            .ok_or("Failed get caps")?
+            .get_structure(0)
+            .ok_or("Failed to get structure")?;
+        let w: i32 = cap.get_value("width")?.get_some()?;
+        let h: i32 = cap.get_value("height")?.get_some()?;
+        let (w, h) = (w as usize, h as usize);
+        // "drm-format" is "<fourcc>:<modifier>" since GStreamer 1.24.
+        let drm_format = cap.get::<&str>("drm-format").ok().flatten();
+        let fourcc = match drm_format {
+            Some(f) => f.split(':').next().unwrap_or_default().to_owned(),
+            None => match cap.get::<&str>("format")?.unwrap_or_default() {
+                "BGRx" => "XR24".to_owned(),
+                "RGBx" => "XB24".to_owned(),
+                f => return Err(Box::new(GStreamerError(format!("Unknown pix_fmt, {}", f)))),
+            },
+        };
+        let modifier = drm_format
+            .and_then(|f| f.split(':').nth(1))
+            .and_then(|m| u64::from_str_radix(m.trim_start_matches("0x"), 16).ok())
+            .unwrap_or(DRM_FORMAT_MOD_INVALID);
+        let planes = match buf.get_meta::<gstreamer_video::VideoMeta>() {
+            Some(meta) => meta
+                .get_offset()
+                .iter()
+                .zip(meta.get_stride().iter())
+                .map(|(offset, stride)| DmaBufPlane {
+                    offset: *offset,
+                    stride: *stride as usize,
+                })
+                .collect(),
             // BGRx is 4 bytes per pixel
-            if buf_size != (w * h * 4) {
-                // for some reason the width and height of the caps do not guarantee correct buffer
-                // size, so ignore those buffers, see:
-                // https://gitlab.freedesktop.org/pipewire/pipewire/-/issues/985
-                trace!(
-                    "Size of mapped buffer: {} does NOT match size of capturable {}x{}@BGRx, \
-                    dropping it!",
-                    buf_size,
-                    w,
-                    h
-                );
-            } else {
-                // Copy region specified by crop into self.buffer_cropped
-                // TODO: Figure out if ffmpeg provides a zero copy alternative
-                if let Some((x_off, y_off, w_crop, h_crop)) = crop {
-                    let x_off = x_off as usize;
-                    let y_off = y_off as usize;
-                    let w_crop = w_crop as usize;
-                    let h_crop = h_crop as usize;
-                    self.buffer_cropped.clear();
-                    let data = buf.as_slice();
-                    // BGRx is 4 bytes per pixel
-                    self.buffer_cropped.reserve(w_crop * h_crop * 4);
-                    for y in y_off..(y_off + h_crop) {
-                        let i = 4 * (w * y + x_off);
-                        self.buffer_cropped.extend(&data[i..i + 4 * w_crop]);
-                    }
-                    self.width = w_crop;
-                    self.height = h_crop;
-                } else {
-                    self.width = w;
-                    self.height = h;
+            None => vec![DmaBufPlane {
+                offset: 0,
+                stride: w * 4,
+            }],
+        };
+        Ok(Some(DmaBufFrame {
+            fd,
+            fourcc,
+            modifier,
+            width: w,
+            height: h,
+            planes,
+            timestamp_ns: buf.get_pts().nanoseconds(),
+        }))
+    }
+
+    fn handle_sample(
+        &mut self,
+        sample: gst::Sample,
+    ) -> Result<(PixelProvider, FrameMeta), Box<dyn Error>> {
+        let cap = sample
+            .get_caps()
+            .ok_or("Failed get caps")?
+            .get_structure(0)
+            .ok_or("Failed to get structure")?;
+        let w: i32 = cap.get_value("width")?.get_some()?;
+        let h: i32 = cap.get_value("height")?.get_some()?;
+        let w = w as usize;
+        let h = h as usize;
+        self.pix_fmt = cap
+            .get::<&str>("format")?
+            .ok_or("Failed to get pixel format")?
+            .to_string();
+        let color = cap
+            .get::<&str>("colorimetry")
+            .ok()
+            .flatten()
+            .and_then(parse_colorimetry)
+            .map(|mut color| {
+                if color.is_hdr() {
+                    let field = |name| cap.get::<&str>(name).ok().flatten();
+                    color.hdr = parse_hdr_metadata(
+                        field("mastering-display-info"),
+                        field("content-light-level"),
+                    );
                 }
-                self.is_cropped = crop.is_some();
-                self.buffer = Some(buf);
-            }
+                color
+            });
+        if color != self.color {
+            info!("The colorimetry of the pipewire stream: {:?}", color);
+            self.color = color;
+        }
+
+        let buf = sample
+            .get_buffer_owned()
+            .ok_or_else(|| GStreamerError("Failed to get owned buffer.".into()))?;
+        let pts = buf.get_pts().nanoseconds();
+        let mut crop = buf
+            .get_meta::<gstreamer_video::VideoCropMeta>()
+            .map(|m| m.get_rect());
+        // only crop if necessary
+        if Some((0, 0, w as u32, h as u32)) == crop {
+            crop = None;
+        }
+        let meta_stride = buf
+            .get_meta::<gstreamer_video::VideoMeta>()
+            .and_then(|m| m.get_stride().first().cloned())
+            .map(|s| s as usize);
+        let buf = buf
+            .into_mapped_buffer_readable()
+            .map_err(|_| GStreamerError("Failed to map buffer.".into()))?;
+        // Compared with the last frame before it's overwritten by `would_block_if_equal()`.
+        let damage = if DAMAGE_TRACKING.load(std::sync::atomic::Ordering::Relaxed)
+            && buf.get_size() == w * h * 4
+        {
+            damage_rects(&self.saved_raw_data, buf.as_slice(), w, h)
+        } else {
+            None
+        };
+        if let Err(..) = crate::would_block_if_equal(&mut self.saved_raw_data, buf.as_slice()) {
+            let meta = FrameMeta {
+                repeated: true,
+                ..Default::default()
+            };
+            return Ok((PixelProvider::NONE, meta));
+        }
+        let buf_size = buf.get_size();
+        let stride = row_stride(meta_stride, buf_size, w, h);
+        if let Some(stride) = stride {
+            self.stride = stride;
         } else {
-            return Ok(PixelProvider::NONE);
+            // for some reason the width and height of the caps do not guarantee correct buffer
+            // size, so ignore those buffers, see:
+            // https://gitlab.freedesktop.org/pipewire/pipewire/-/issues/985
+            trace!(
+                "Size of mapped buffer: {} does NOT match size of capturable {}x{}@BGRx, \
+                dropping it!",
+                buf_size,
+                w,
+                h
+            );
+        }
+        if let Some(stride) = stride {
+            // Copy region specified by crop into self.buffer_cropped
+            // TODO: Figure out if ffmpeg provides a zero copy alternative
+            if let Some((x_off, y_off, w_crop, h_crop)) = crop {
+                let x_off = x_off as usize;
+                let y_off = y_off as usize;
+                let w_crop = w_crop as usize;
+                let h_crop = h_crop as usize;
+                self.buffer_cropped.clear();
+                let data = buf.as_slice();
+                // BGRx is 4 bytes per pixel
+                self.buffer_cropped.reserve(w_crop * h_crop * 4);
+                for y in y_off..(y_off + h_crop) {
+                    let i = stride * y + 4 * x_off;
+                    self.buffer_cropped.extend(&data[i..i + 4 * w_crop]);
+                }
+                self.width = w_crop;
+                self.height = h_crop;
+                self.stride = 4 * w_crop;
+            } else {
+                self.width = w;
+                self.height = h;
+            }
+            // The damage is in the coordinates of the uncropped frame.
+            self.damage = match crop {
+                Some((x_off, y_off, w_crop, h_crop)) => damage.map(|rects| {
+                    crop_damage_rects(rects, (x_off as _, y_off as _, w_crop as _, h_crop as _))
+                }),
+                None => damage,
+            };
+            self.is_cropped = crop.is_some();
+            self.buffer = Some(buf);
+            self.pts = pts;
         }
         if self.buffer.is_none() {
             return Err(Box::new(GStreamerError("No buffer available!".into())));
@@ -349,9 +725,16 @@ impl Recorder for PipeWireRecorder {
                 .ok_or("Failed to get buffer as ref")?
                 .as_slice()
         };
+        let meta = FrameMeta {
+            timestamp_ns: self.pts,
+            stride: Some(self.stride).filter(|s| *s != self.width * 4),
+            damage: self.damage.clone(),
+            color: self.color,
+            repeated: false,
+        };
         match self.pix_fmt.as_str() {
-            "BGRx" => Ok(PixelProvider::BGR0(self.width, self.height, buf)),
-            "RGBx" => Ok(PixelProvider::RGB0(self.width, self.height, buf)),
+            "BGRx" => Ok((PixelProvider::BGR0(self.width, self.height, buf), meta)),
+            "RGBx" => Ok((PixelProvider::RGB0(self.width, self.height, buf), meta)),
             _ => Err(Box::new(GStreamerError(format!(
                 "Unreachable! Unknown pix_fmt, {}",
                 &self.pix_fmt
@@ -360,6 +743,303 @@ impl Recorder for PipeWireRecorder {
     }
 }
 
+// The bytes of a row of a BGRx/RGBx buffer, from the video meta or the buffer size.
+// `None` if the buffer is smaller than the caps, the frame is dropped then.
+fn row_stride(meta_stride: Option<usize>, buf_size: usize, w: usize, h: usize) -> Option<usize> {
+    let stride = match meta_stride {
+        Some(stride) => stride,
+        None if h > 0 && buf_size % h == 0 => buf_size / h,
+        None => return None,
+    };
+    if stride >= w * 4 && buf_size >= stride * h {
+        Some(stride)
+    } else {
+        None
+    }
+}
+
+impl Recorder for PipeWireRecorder {
+    fn capture(&mut self, timeout_ms: u64) -> Result<PixelProvider, Box<dyn Error>> {
+        self.capture_with_meta(timeout_ms).map(|(p, _)| p)
+    }
+
+    fn capture_with_meta(
+        &mut self,
+        timeout_ms: u64,
+    ) -> Result<(PixelProvider, FrameMeta), Box<dyn Error>> {
+        match self
+            .appsink
+            .try_pull_sample(gst::ClockTime::from_mseconds(timeout_ms))
+        {
+            Some(sample) => self.handle_sample(sample),
+            None => Ok((PixelProvider::NONE, FrameMeta::default())),
+        }
+    }
+
+    #[cfg(feature = "dmabuf")]
+    fn capture_dmabuf(&mut self, timeout_ms: u64) -> Result<DmaBufCapture, Box<dyn Error>> {
+        let Some(sample) = self
+            .appsink
+            .try_pull_sample(gst::ClockTime::from_mseconds(timeout_ms))
+        else {
+            return Ok(DmaBufCapture::Pixels(PixelProvider::NONE));
+        };
+        if let Some(frame) = Self::dmabuf_of(&sample)? {
+            self.dmabuf_sample = Some(sample);
+            return Ok(DmaBufCapture::DmaBuf(frame));
+        }
+        self.handle_sample(sample)
+            .map(|(p, _)| DmaBufCapture::Pixels(p))
+    }
+
+    fn set_paused(&mut self, paused: bool) -> Result<(), Box<dyn Error>> {
+        // The pipewiresrc disconnects its stream in the READY state, the fd of the portal is kept.
+        let state = if paused && STOP_STREAM_ON_PAUSE.load(std::sync::atomic::Ordering::SeqCst) {
+            gst::State::Ready
+        } else if paused {
+            gst::State::Paused
+        } else {
+            gst::State::Playing
+        };
+        self.pipeline.set_state(state)?;
+        Ok(())
+    }
+
+    // The sample is left in the appsink, it's pulled by the next `capture()`.
+    fn set_frame_notify(&mut self, notify: Option<FrameNotify>) -> Result<bool, Box<dyn Error>> {
+        let callbacks = match notify {
+            Some(notify) => AppSinkCallbacks::builder()
+                .new_sample(move |_| {
+                    notify();
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+            None => AppSinkCallbacks::builder().build(),
+        };
+        self.appsink.set_callbacks(callbacks);
+        Ok(true)
+    }
+}
+
+// Limit the size of the frames of the recorders created afterwards, e.g. 1920x1080 for a slow network.
+// `None` to capture in the native size.
+pub fn set_capture_max_size(size: Option<(usize, usize)>) {
+    *CAPTURE_MAX_SIZE.lock().unwrap() = size;
+}
+
+pub fn get_capture_max_size() -> Option<(usize, usize)> {
+    *CAPTURE_MAX_SIZE.lock().unwrap()
+}
+
+// The size of the frames of a stream, fit in `max_size` with the aspect ratio kept, never upscaled.
+// The width and height are even, which the encoders require.
+pub fn fit_capture_size(size: (usize, usize), max_size: Option<(usize, usize)>) -> (usize, usize) {
+    let Some((max_w, max_h)) = max_size else {
+        return size;
+    };
+    let (w, h) = size;
+    if w == 0 || h == 0 || max_w == 0 || max_h == 0 || (w <= max_w && h <= max_h) {
+        return size;
+    }
+    let ratio = (max_w as f64 / w as f64).min(max_h as f64 / h as f64);
+    let even = |v: f64| ((v as usize) & !1).max(2);
+    (even(w as f64 * ratio), even(h as f64 * ratio))
+}
+
+// Parse the colorimetry of the gstreamer caps, e.g. "bt709" or "range:matrix:transfer:primaries" like "2:3:5:1".
+// The RGB formats are usually "sRGB", the full range with the primaries of BT.709.
+pub fn parse_colorimetry(colorimetry: &str) -> Option<ColorInfo> {
+    let limited = |space| ColorInfo {
+        space,
+        range: ColorRange::Limited,
+        ..Default::default()
+    };
+    match colorimetry {
+        "bt601" | "smpte240m" => return Some(limited(ColorSpace::BT601)),
+        "bt709" => return Some(limited(ColorSpace::BT709)),
+        "bt2020" | "bt2020-10" => return Some(limited(ColorSpace::BT2020)),
+        "bt2100-pq" => {
+            return Some(ColorInfo {
+                transfer: ColorTransfer::Pq,
+                ..limited(ColorSpace::BT2020)
+            })
+        }
+        "bt2100-hlg" => {
+            return Some(ColorInfo {
+                transfer: ColorTransfer::Hlg,
+                ..limited(ColorSpace::BT2020)
+            })
+        }
+        "sRGB" => {
+            return Some(ColorInfo {
+                space: ColorSpace::BT709,
+                range: ColorRange::Full,
+                ..Default::default()
+            })
+        }
+        _ => {}
+    }
+    // See `GstVideoColorRange` and `GstVideoColorMatrix`.
+    let mut parts = colorimetry.split(':');
+    let range = match parts.next()?.parse::<u32>().ok()? {
+        1 => ColorRange::Full,
+        2 => ColorRange::Limited,
+        _ => return None,
+    };
+    let space = match parts.next()?.parse::<u32>().ok()? {
+        // RGB, the primaries are not reliable for the matrix, BT.709 is the common one.
+        1 | 3 => ColorSpace::BT709,
+        2 | 4 | 5 => ColorSpace::BT601,
+        6 => ColorSpace::BT2020,
+        _ => return None,
+    };
+    // See `GstVideoTransferFunction`, the others are SDR.
+    let transfer = match parts.next().and_then(|t| t.parse::<u32>().ok()) {
+        Some(14) => ColorTransfer::Pq,
+        Some(15) => ColorTransfer::Hlg,
+        _ => ColorTransfer::Sdr,
+    };
+    Some(ColorInfo {
+        space,
+        range,
+        transfer,
+        hdr: None,
+    })
+}
+
+// Parse the "mastering-display-info" and "content-light-level" of the gstreamer caps,
+// e.g. "35400:14600:8500:39850:6550:2300:15635:16450:10000000:1" and "1000:400".
+// The mastering display info ends with the max and min luminance.
+pub fn parse_hdr_metadata(
+    mastering_display_info: Option<&str>,
+    content_light_level: Option<&str>,
+) -> Option<HdrMetadata> {
+    let mastering_luminance = mastering_display_info.and_then(|s| {
+        let values = s
+            .split(':')
+            .map(|v| v.parse::<u32>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        match values.as_slice() {
+            [_, _, _, _, _, _, _, _, max, min] => Some((*min, *max)),
+            _ => None,
+        }
+    });
+    let content_light_level = content_light_level.and_then(|s| {
+        let (max_cll, max_fall) = s.split_once(':')?;
+        Some((max_cll.parse().ok()?, max_fall.parse().ok()?))
+    });
+    if mastering_luminance.is_none() && content_light_level.is_none() {
+        return None;
+    }
+    Some(HdrMetadata {
+        mastering_luminance,
+        content_light_level,
+    })
+}
+
+// The buffers negotiated with pipewire, 0 to keep the default of pipewiresrc.
+static BUFFER_COUNT: AtomicU32 = AtomicU32::new(0);
+
+// Takes effect on the recorders created afterwards.
+pub fn set_buffer_count(count: u32) {
+    BUFFER_COUNT.store(count, std::sync::atomic::Ordering::Relaxed);
+}
+
+pub fn get_buffer_count() -> u32 {
+    BUFFER_COUNT.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+// More buffers drop fewer frames on a busy host, fewer buffers lower the latency.
+// The properties are missing in the old versions of pipewiresrc, the default is kept then.
+fn set_buffer_count_of(src: &gst::Element, count: u32) {
+    if count == 0 {
+        return;
+    }
+    let mut applied = false;
+    for name in ["min-buffers", "max-buffers"] {
+        if src.find_property(name).is_some() {
+            match src.set_property(name, &(count as i32)) {
+                Ok(_) => applied = true,
+                Err(e) => warn!("Failed to set {} of pipewiresrc to {}: {}", name, count, e),
+            }
+        }
+    }
+    if applied {
+        info!("Set the pipewire buffer count to {}", count);
+    } else {
+        warn!("The pipewiresrc does not support the buffer count, keep the default");
+    }
+}
+
+// Off by default, comparing the frames costs CPU time.
+// The portal streams do not carry the damage of the compositor to gstreamer, so it's computed.
+static DAMAGE_TRACKING: AtomicBool = AtomicBool::new(false);
+
+const DAMAGE_TILE_SIZE: usize = 64;
+
+pub fn set_damage_tracking(enabled: bool) {
+    DAMAGE_TRACKING.store(enabled, std::sync::atomic::Ordering::Relaxed);
+}
+
+// The changed tiles of the BGRx frames, the adjacent tiles in a tile row are merged.
+// `None` if the frames can't be compared, e.g. the first frame or the size is changed.
+fn damage_rects(old: &[u8], new: &[u8], w: usize, h: usize) -> Option<Vec<DamageRect>> {
+    if old.len() != new.len() || new.len() != w * h * 4 {
+        return None;
+    }
+    let mut rects = Vec::new();
+    for ty in (0..h).step_by(DAMAGE_TILE_SIZE) {
+        let th = DAMAGE_TILE_SIZE.min(h - ty);
+        let mut run: Option<DamageRect> = None;
+        for tx in (0..w).step_by(DAMAGE_TILE_SIZE) {
+            let tw = DAMAGE_TILE_SIZE.min(w - tx);
+            let dirty = (ty..ty + th).any(|y| {
+                let i = 4 * (w * y + tx);
+                old[i..i + 4 * tw] != new[i..i + 4 * tw]
+            });
+            match (dirty, run.as_mut()) {
+                (true, Some(r)) => r.width += tw,
+                (true, None) => {
+                    run = Some(DamageRect {
+                        x: tx,
+                        y: ty,
+                        width: tw,
+                        height: th,
+                    })
+                }
+                (false, _) => rects.extend(run.take()),
+            }
+        }
+        rects.extend(run.take());
+    }
+    Some(rects)
+}
+
+// Clips the rects to the crop region, and makes them relative to it.
+fn crop_damage_rects(
+    rects: Vec<DamageRect>,
+    (x_off, y_off, w_crop, h_crop): (usize, usize, usize, usize),
+) -> Vec<DamageRect> {
+    rects
+        .into_iter()
+        .filter_map(|r| {
+            let x0 = r.x.max(x_off);
+            let y0 = r.y.max(y_off);
+            let x1 = (r.x + r.width).min(x_off + w_crop);
+            let y1 = (r.y + r.height).min(y_off + h_crop);
+            if x0 >= x1 || y0 >= y1 {
+                return None;
+            }
+            Some(DamageRect {
+                x: x0 - x_off,
+                y: y0 - y_off,
+                width: x1 - x0,
+                height: y1 - y0,
+            })
+        })
+        .collect()
+}
+
 impl Drop for PipeWireRecorder {
     fn drop(&mut self) {
         if let Err(err) = self.pipeline.set_state(gst::State::Null) {
@@ -395,6 +1075,7 @@ where
             0 => {}
             1 => {
                 warn!("DBus response: User cancelled interaction.");
+                PORTAL_CANCELLED.store(true, std::sync::atomic::Ordering::Relaxed);
                 failure_out.store(true, std::sync::atomic::Ordering::Relaxed);
                 return true;
             }
@@ -420,6 +1101,23 @@ pub fn get_portal(conn: &SyncConnection) -> Proxy<&SyncConnection> {
     )
 }
 
+// The portal backends on the session bus, e.g. "gnome", "kde", "wlr".
+// The backends are started on demand, so the list may be empty before the first screencast.
+pub fn get_portal_backends() -> Result<Vec<String>, dbus::Error> {
+    let conn = new_session_connection()?;
+    let proxy = conn.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_millis(1000),
+    );
+    let (names,): (Vec<String>,) = proxy.method_call("org.freedesktop.DBus", "ListNames", ())?;
+    Ok(names
+        .iter()
+        .filter_map(|name| name.strip_prefix("org.freedesktop.impl.portal.desktop."))
+        .map(|backend| backend.to_owned())
+        .collect())
+}
+
 fn streams_from_response(response: OrgFreedesktopPortalRequestResponse) -> Vec<PwStreamInfo> {
     (move || {
         Some(
@@ -454,6 +1152,11 @@ fn streams_from_response(response: OrgFreedesktopPortalRequestResponse) -> Vec<P
                             .map_or(Some(0), |v| v.as_u64())?,
                         position: (0, 0),
                         size: (0, 0),
+                        id: attributes
+                            .get("id")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default()
+                            .to_owned(),
                     };
                     let v = attributes
                         .get("size")?
@@ -499,15 +1202,177 @@ fn streams_from_response(response: OrgFreedesktopPortalRequestResponse) -> Vec<P
 }
 
 static mut INIT: bool = false;
+// Set if the user cancelled the portal dialog, reset on every request.
+static PORTAL_CANCELLED: AtomicBool = AtomicBool::new(false);
 const RESTORE_TOKEN: &str = "restore_token";
 const RESTORE_TOKEN_CONF_KEY: &str = "wayland-restore-token";
 
+#[inline]
+fn has_restore_token() -> bool {
+    !config::LocalConfig::get_option(RESTORE_TOKEN_CONF_KEY).is_empty()
+}
+
+// Remove the saved restore token, the next screencast session will prompt the user again.
+pub fn clear_restore_token() {
+    config::LocalConfig::set_option(RESTORE_TOKEN_CONF_KEY.to_owned(), "".to_owned());
+}
+
+// Whether the portal is running or can be activated on the session bus, it's not activated.
+pub fn is_portal_present() -> Result<bool, dbus::Error> {
+    let conn = new_session_connection()?;
+    let proxy = conn.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_millis(500),
+    );
+    for method in ["ListNames", "ListActivatableNames"] {
+        let (names,): (Vec<String>,) = proxy.method_call("org.freedesktop.DBus", method, ())?;
+        if names.iter().any(|n| n == "org.freedesktop.portal.Desktop") {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Whether the pipewire socket of the session exists.
+pub fn is_pipewire_present() -> bool {
+    let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") else {
+        return false;
+    };
+    let remote = std::env::var("PIPEWIRE_REMOTE").unwrap_or("pipewire-0".to_owned());
+    std::path::Path::new(&runtime_dir).join(remote).exists()
+}
+
 pub fn get_available_cursor_modes() -> Result<u32, dbus::Error> {
-    let conn = SyncConnection::new_session()?;
+    let conn = new_session_connection()?;
     let portal = get_portal(&conn);
     portal.available_cursor_modes()
 }
 
+// https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.ScreenCast.html
+pub const CURSOR_MODE_HIDDEN: u32 = 1;
+pub const CURSOR_MODE_EMBEDDED: u32 = 2;
+pub const CURSOR_MODE_METADATA: u32 = 4;
+
+// 0 means the cursor mode is not requested, the portal uses the hidden mode by default.
+static CURSOR_MODE: AtomicU32 = AtomicU32::new(0);
+
+// Takes effect on the next portal session.
+#[inline]
+pub fn set_cursor_mode(mode: u32) {
+    CURSOR_MODE.store(mode, std::sync::atomic::Ordering::Relaxed);
+}
+
+#[inline]
+pub fn get_cursor_mode() -> u32 {
+    CURSOR_MODE.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+// The cursor mode of the last portal session, 0 before any session is started.
+// It may differ from `get_cursor_mode()` if the portal does not support the requested mode.
+static NEGOTIATED_CURSOR_MODE: AtomicU32 = AtomicU32::new(0);
+
+#[inline]
+pub fn get_negotiated_cursor_mode() -> u32 {
+    NEGOTIATED_CURSOR_MODE.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+// https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.ScreenCast.html
+pub const SOURCE_TYPE_MONITOR: u32 = 1;
+pub const SOURCE_TYPE_WINDOW: u32 = 2;
+pub const SOURCE_TYPE_VIRTUAL: u32 = 4;
+
+static SOURCE_TYPES: AtomicU32 = AtomicU32::new(SOURCE_TYPE_MONITOR);
+
+// Takes effect on the next portal session.
+#[inline]
+pub fn set_source_types(types: u32) {
+    SOURCE_TYPES.store(types, std::sync::atomic::Ordering::Relaxed);
+}
+
+#[inline]
+pub fn get_source_types() -> u32 {
+    SOURCE_TYPES.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+static MULTIPLE_SOURCES: AtomicBool = AtomicBool::new(false);
+
+// Let the user select more than one source in the portal dialog, e.g. all the monitors.
+// Takes effect on the next portal session.
+#[inline]
+pub fn set_multiple_sources(multiple: bool) {
+    MULTIPLE_SOURCES.store(multiple, std::sync::atomic::Ordering::Relaxed);
+}
+
+fn insert_multiple(args: &mut PropMap) {
+    if MULTIPLE_SOURCES.load(std::sync::atomic::Ordering::Relaxed) {
+        args.insert("multiple".into(), Variant(Box::new(true)));
+    }
+}
+
+pub fn get_available_source_types() -> Result<u32, dbus::Error> {
+    let conn = new_session_connection()?;
+    let portal = get_portal(&conn);
+    portal.available_source_types()
+}
+
+// The requested source types which are supported by the portal, monitors at least.
+fn get_source_types_arg(portal: &Proxy<&SyncConnection>) -> u32 {
+    let types = get_source_types();
+    match portal.available_source_types() {
+        Ok(available) if available & types != 0 => available & types,
+        Ok(available) => {
+            warn!(
+                "Source types {} are not supported, available source types: {}",
+                types, available
+            );
+            SOURCE_TYPE_MONITOR
+        }
+        Err(err) => {
+            warn!("Failed to get available source types: {}", err);
+            SOURCE_TYPE_MONITOR
+        }
+    }
+}
+
+fn insert_cursor_mode(portal: &Proxy<&SyncConnection>, args: &mut PropMap) {
+    // The portal hides the cursor if the mode is not requested.
+    let mut negotiated = CURSOR_MODE_HIDDEN;
+    let mode = get_cursor_mode();
+    if mode != 0 {
+        negotiated = request_cursor_mode(portal, args, mode).unwrap_or(negotiated);
+    }
+    let old = NEGOTIATED_CURSOR_MODE.swap(negotiated, std::sync::atomic::Ordering::Relaxed);
+    if old != negotiated {
+        info!("Negotiated cursor mode: {} -> {}", old, negotiated);
+    }
+}
+
+// Returns the requested mode if it's supported.
+fn request_cursor_mode(
+    portal: &Proxy<&SyncConnection>,
+    args: &mut PropMap,
+    mode: u32,
+) -> Option<u32> {
+    match portal.available_cursor_modes() {
+        Ok(available) if available & mode != 0 => {
+            args.insert("cursor_mode".to_string(), Variant(Box::new(mode)));
+            Some(mode)
+        }
+        Ok(available) => {
+            warn!(
+                "Cursor mode {} is not supported, available cursor modes: {}",
+                mode, available
+            );
+            None
+        }
+        Err(err) => {
+            warn!("Failed to get available cursor modes: {}", err);
+            None
+        }
+    }
+}
+
 // mostly inspired by https://gitlab.gnome.org/-/snippets/39
 pub fn request_remote_desktop() -> Result<
     (
@@ -525,7 +1390,8 @@ pub fn request_remote_desktop() -> Result<
             INIT = true;
         }
     }
-    let conn = SyncConnection::new_session()?;
+    PORTAL_CANCELLED.store(false, std::sync::atomic::Ordering::Relaxed);
+    let conn = new_session_connection()?;
     let portal = get_portal(&conn);
     let mut args: PropMap = HashMap::new();
     let fd: Arc<Mutex<Option<OwnedFd>>> = Arc::new(Mutex::new(None));
@@ -606,6 +1472,11 @@ pub fn request_remote_desktop() -> Result<
             }
         }
     }
+    if PORTAL_CANCELLED.load(std::sync::atomic::Ordering::Relaxed) {
+        return Err(Box::new(DBusError(
+            "User cancelled the screen sharing request.".into(),
+        )));
+    }
     Err(Box::new(DBusError(
 "Failed to obtain screen capture. You may need to upgrade the PipeWire library for better compatibility. Please check https://github.com/rustdesk/rustdesk/issues/8600#issuecomment-2254720954 for more details.".into()
     )))
@@ -660,8 +1531,12 @@ fn on_create_session_response(
                 Variant(Box::new("u3".to_string())),
             );
             // https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.ScreenCast.html
-            // args.insert("multiple".into(), Variant(Box::new(true)));
-            args.insert("types".into(), Variant(Box::new(1u32))); //| 2u32)));
+            insert_multiple(&mut args);
+            args.insert(
+                "types".into(),
+                Variant(Box::new(get_source_types_arg(&portal))),
+            );
+            insert_cursor_mode(&portal, &mut args);
 
             let path = portal.select_sources(ses.clone(), args)?;
             handle_response(
@@ -724,8 +1599,12 @@ fn on_select_devices_response(
             Variant(Box::new("u3".to_string())),
         );
         // https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.ScreenCast.html
-        // args.insert("multiple".into(), Variant(Box::new(true)));
-        args.insert("types".into(), Variant(Box::new(1u32))); //| 2u32)));
+        insert_multiple(&mut args);
+        args.insert(
+            "types".into(),
+            Variant(Box::new(get_source_types_arg(&portal))),
+        );
+        insert_cursor_mode(&portal, &mut args);
 
         let session = session.clone();
         let path = portal.select_sources(session.clone(), args)?;
@@ -833,8 +1712,20 @@ pub fn get_capturables() -> Result<Vec<PipeWireCapturable>, Box<dyn Error>> {
     };
 
     if rdp_connection.is_none() {
-        let (conn, fd, streams, session, is_support_restore_token) = request_remote_desktop()?;
+        let (conn, fd, streams, session, is_support_restore_token) = match request_remote_desktop()
+        {
+            Ok(res) => res,
+            // The restore token may be rejected or expired, try again with the prompt.
+            Err(e) if is_server_running() && has_restore_token() => {
+                warn!("Failed to request screencast with restore token, {}", e);
+                clear_restore_token();
+                request_remote_desktop()?
+            }
+            Err(e) => return Err(e),
+        };
         let conn = Arc::new(conn);
+        ACCESS_REVOKED.store(false, std::sync::atomic::Ordering::SeqCst);
+        watch_session_closed(conn.clone(), session.clone());
 
         let rdp_info = RdpSessionInfo {
             conn,
@@ -869,6 +1760,55 @@ pub fn get_capturables() -> Result<Vec<PipeWireCapturable>, Box<dyn Error>> {
         .collect())
 }
 
+// The portal emits "Closed" on the session when it's closed by the compositor or the user.
+// Nobody processes the connection after the session is created, so a thread does it while the session is kept.
+fn watch_session_closed(conn: Arc<SyncConnection>, session: dbus::Path<'static>) {
+    use dbus::channel::MatchingReceiver;
+
+    let mut m = MatchRule::new();
+    m.path = Some(session);
+    m.msg_type = Some(MessageType::Signal);
+    m.sender = Some("org.freedesktop.portal.Desktop".into());
+    m.interface = Some("org.freedesktop.portal.Session".into());
+    m.member = Some("Closed".into());
+    if let Err(e) = conn.add_match_no_cb(&m.match_str()) {
+        warn!("Failed to watch the closing of the portal session: {}", e);
+        return;
+    }
+    conn.start_receive(
+        m,
+        Box::new(|_, _| {
+            on_session_closed();
+            false
+        }),
+    );
+    std::thread::spawn(move || loop {
+        let is_current = RDP_SESSION_INFO
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(false, |info| Arc::ptr_eq(&info.conn, &conn));
+        if !is_current {
+            break;
+        }
+        if let Err(e) = conn.process(SESSION_WATCH_INTERVAL) {
+            warn!("Failed to process the portal session connection: {}", e);
+            break;
+        }
+    });
+}
+
+fn on_session_closed() {
+    warn!("The portal session is closed, the screen sharing is stopped");
+    ACCESS_REVOKED.store(true, std::sync::atomic::Ordering::SeqCst);
+    // The closed session can't be used anymore, the next capture requests a new one.
+    close_session();
+    let callback = ON_ACCESS_REVOKED.lock().unwrap().clone();
+    if let Some(callback) = callback {
+        callback();
+    }
+}
+
 // If `is_server_running()` is true, then `screencast_portal::start` is called.
 // Otherwise, `remote_desktop_portal::start` is called.
 //
@@ -105,11 +105,40 @@ pub const REQUIRE_2FA: &'static str = "2FA Required";
 pub const LOGIN_MSG_NO_PASSWORD_ACCESS: &str = "No Password Access";
 pub const LOGIN_MSG_OFFLINE: &str = "Offline";
 pub const LOGIN_SCREEN_WAYLAND: &str = "Wayland login screen is not supported";
+// The SCRAP_* messages are translation keys, the english texts are in `src/lang/en.rs`.
+// They are translated by the controlling side, the older ones get the english texts instead.
+pub const SCRAP_KEYS_MIN_VERSION: &str = "1.3.3";
 #[cfg(target_os = "linux")]
-pub const SCRAP_UBUNTU_HIGHER_REQUIRED: &str = "Wayland requires Ubuntu 21.04 or higher version.";
+pub const SCRAP_UBUNTU_HIGHER_REQUIRED: &str = "scrap_ubuntu_higher_required";
 #[cfg(target_os = "linux")]
-pub const SCRAP_OTHER_VERSION_OR_X11_REQUIRED: &str =
-    "Wayland requires higher version of linux distro. Please try X11 desktop or change your OS.";
+pub const SCRAP_FEDORA_HIGHER_REQUIRED: &str = "scrap_fedora_higher_required";
+#[cfg(target_os = "linux")]
+pub const SCRAP_DEBIAN_HIGHER_REQUIRED: &str = "scrap_debian_higher_required";
+#[cfg(target_os = "linux")]
+pub const SCRAP_OPENSUSE_HIGHER_REQUIRED: &str = "scrap_opensuse_higher_required";
+#[cfg(target_os = "linux")]
+pub const SCRAP_OTHER_VERSION_OR_X11_REQUIRED: &str = "scrap_other_version_or_x11_required";
+// Not linux only, the controlling side handles it in `handle_login_error()`.
+pub const SCRAP_USER_CANCELLED: &str = "scrap_user_cancelled";
+#[cfg(target_os = "linux")]
+pub const SCRAP_PORTAL_NO_RESPONSE: &str = "scrap_portal_no_response";
+#[cfg(target_os = "linux")]
+pub const SCRAP_PIPEWIRE_REQUIRED: &str = "scrap_pipewire_required";
+#[cfg(target_os = "linux")]
+pub const SCRAP_PORTAL_MISSING: &str = "scrap_portal_missing";
+#[cfg(target_os = "linux")]
+pub const SCRAP_NO_DISPLAY_ATTACHED: &str = "scrap_no_display_attached";
+#[cfg(target_os = "linux")]
+pub const SCRAP_DBUS_UNREACHABLE: &str = "scrap_dbus_unreachable";
+#[cfg(target_os = "linux")]
+pub const SCRAP_NO_USER_SESSION: &str = "scrap_no_user_session";
+#[cfg(target_os = "linux")]
+pub const SCRAP_ACCESS_REVOKED: &str = "scrap_access_revoked";
+#[cfg(target_os = "linux")]
+pub const SCRAP_WAYLAND_UNAVAILABLE: &str = "scrap_wayland_unavailable";
+#[cfg(target_os = "linux")]
+pub const SCRAP_SCREEN_LOCKED: &str = "scrap_screen_locked";
+// Kept as is, it's already translated and checked by the older clients.
 pub const SCRAP_X11_REQUIRED: &str = "x11 expected";
 pub const SCRAP_X11_REF_URL: &str = "https://rustdesk.com/docs/en/manual/linux/#x11-required";
 
@@ -2760,6 +2789,12 @@ lazy_static::lazy_static! {
             text: "Login screen using Wayland is not supported",
             link: "https://rustdesk.com/docs/en/manual/linux/#login-screen",
             try_again: true,
+        }), (SCRAP_USER_CANCELLED, LoginErrorMsgBox{
+            msgtype: "error",
+            title: "Login Error",
+            text: SCRAP_USER_CANCELLED,
+            link: "",
+            try_again: true,
         }), (LOGIN_MSG_DESKTOP_SESSION_NOT_READY, LoginErrorMsgBox{
             msgtype: "session-login",
             title: "",
@@ -103,7 +103,17 @@ impl Drop for SimpleCallOnReturn {
 pub fn global_init() -> bool {
     #[cfg(target_os = "linux")]
     {
-        if !crate::platform::linux::is_x11() {
+        let is_x11 = crate::platform::linux::is_x11();
+        log::info!(
+            "Capture backend: {}{}",
+            if is_x11 { "x11" } else { "wayland" },
+            if scrap::forced_x11().is_some() {
+                " (forced)"
+            } else {
+                ""
+            }
+        );
+        if !is_x11 {
             crate::server::wayland::init();
         }
     }
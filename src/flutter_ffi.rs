@@ -233,6 +233,12 @@ pub fn session_refresh(session_id: SessionID, display: usize) {
     }
 }
 
+pub fn session_set_capture_paused(session_id: SessionID, paused: bool) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.set_capture_paused(paused);
+    }
+}
+
 pub fn session_is_multi_ui_session(session_id: SessionID) -> SyncReturn<bool> {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
         SyncReturn(session.is_multi_ui_session())
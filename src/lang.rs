@@ -194,6 +194,18 @@ pub fn translate_locale(name: String, locale: &str) -> String {
     replace(&name.as_str())
 }
 
+// The english text of a translation key, for the peers which do not know the key.
+pub fn english_text(name: &str) -> String {
+    let s = match en::T.get(name) {
+        Some(v) if !v.is_empty() => v.to_string(),
+        _ => name.to_owned(),
+    };
+    if !crate::is_rustdesk() && s.contains("RustDesk") {
+        return s.replace("RustDesk", &crate::get_app_name());
+    }
+    s
+}
+
 // Matching pattern is {}
 // Write {value} in the UI and {} in the translation file
 //
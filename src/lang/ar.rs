@@ -380,8 +380,6 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Keyboard Settings", "اعدادات لوحة المفاتيح"),
         ("Full Access", "وصول كامل"),
         ("Screen Share", "مشاركة الشاشة"),
-        ("Wayland requires Ubuntu 21.04 or higher version.", "Wayland يتطلب نسخة ابونتو 21.04 او اعلى."),
-        ("Wayland requires higher version of linux distro. Please try X11 desktop or change your OS.", "Wayland يتطلب نسخة اعلى من توزيعة لينكس. الرجاء تجربة سطح مكتب X11 او غير نظام تشغيلك."),
         ("JumpLink", "رابط القفز"),
         ("Please Select the screen to be shared(Operate on the peer side).", "الرجاء اختيار شاشة لمشاركتها (تعمل على جانب القرين)."),
         ("Show RustDesk", "عرض RustDesk"),
@@ -648,5 +646,20 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Authentication Required", ""),
         ("Authenticate", ""),
         ("web_id_input_tip", ""),
+        ("scrap_ubuntu_higher_required", "Wayland يتطلب نسخة ابونتو 21.04 او اعلى."),
+        ("scrap_fedora_higher_required", ""),
+        ("scrap_debian_higher_required", ""),
+        ("scrap_opensuse_higher_required", ""),
+        ("scrap_other_version_or_x11_required", "Wayland يتطلب نسخة اعلى من توزيعة لينكس. الرجاء تجربة سطح مكتب X11 او غير نظام تشغيلك."),
+        ("scrap_user_cancelled", ""),
+        ("scrap_portal_no_response", ""),
+        ("scrap_pipewire_required", ""),
+        ("scrap_portal_missing", ""),
+        ("scrap_no_display_attached", ""),
+        ("scrap_dbus_unreachable", ""),
+        ("scrap_no_user_session", ""),
+        ("scrap_access_revoked", ""),
+        ("scrap_wayland_unavailable", ""),
+        ("scrap_screen_locked", ""),
     ].iter().cloned().collect();
 }
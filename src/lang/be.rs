@@ -380,8 +380,6 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Keyboard Settings", "Налады клавіятуры"),
         ("Full Access", "Поўны доступ"),
         ("Screen Share", "Дэманстрацыя экрана"),
-        ("Wayland requires Ubuntu 21.04 or higher version.", "Wayland патрабуе Ubuntu версіі 21.04 або навейшай."),
-        ("Wayland requires higher version of linux distro. Please try X11 desktop or change your OS.", "Для Wayland патрабуецца вышэйшая версія дыстрыбутыву Linux. Карыстайцеся працоўным сталом X11 або зменіце сваю АС."),
         ("JumpLink", "Перайсці па спасылцы"),
         ("Please Select the screen to be shared(Operate on the peer side).", "Выберыце экран для дэманстрацыі (кіруецца аддаленай стараной)."),
         ("Show RustDesk", "Паказаць RustDesk"),
@@ -648,5 +646,20 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Authentication Required", ""),
         ("Authenticate", ""),
         ("web_id_input_tip", ""),
+        ("scrap_ubuntu_higher_required", "Wayland патрабуе Ubuntu версіі 21.04 або навейшай."),
+        ("scrap_fedora_higher_required", ""),
+        ("scrap_debian_higher_required", ""),
+        ("scrap_opensuse_higher_required", ""),
+        ("scrap_other_version_or_x11_required", "Для Wayland патрабуецца вышэйшая версія дыстрыбутыву Linux. Карыстайцеся працоўным сталом X11 або зменіце сваю АС."),
+        ("scrap_user_cancelled", ""),
+        ("scrap_portal_no_response", ""),
+        ("scrap_pipewire_required", ""),
+        ("scrap_portal_missing", ""),
+        ("scrap_no_display_attached", ""),
+        ("scrap_dbus_unreachable", ""),
+        ("scrap_no_user_session", ""),
+        ("scrap_access_revoked", ""),
+        ("scrap_wayland_unavailable", ""),
+        ("scrap_screen_locked", ""),
     ].iter().cloned().collect();
 }
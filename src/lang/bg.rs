@@ -380,8 +380,6 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Keyboard Settings", "Настройки на клавиатурата"),
         ("Full Access", "Пълен достъп"),
         ("Screen Share", "Споделяне на екрана"),
-        ("Wayland requires Ubuntu 21.04 or higher version.", "Wayland изисква Ubuntu 21.04 или по-нов"),
-        ("Wayland requires higher version of linux distro. Please try X11 desktop or change your OS.", "Wayland изисква по-нов Linux. Моля, опитайте с X11 или сменете операционната система."),
         ("JumpLink", "Препратка"),
         ("Please Select the screen to be shared(Operate on the peer side).", "Моля, изберете екрана, който да бъде споделен (спрямо отдалечената страна)."),
         ("Show RustDesk", "Покажи RustDesk"),
@@ -648,5 +646,20 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Authentication Required", ""),
         ("Authenticate", ""),
         ("web_id_input_tip", ""),
+        ("scrap_ubuntu_higher_required", "Wayland изисква Ubuntu 21.04 или по-нов"),
+        ("scrap_fedora_higher_required", ""),
+        ("scrap_debian_higher_required", ""),
+        ("scrap_opensuse_higher_required", ""),
+        ("scrap_other_version_or_x11_required", "Wayland изисква по-нов Linux. Моля, опитайте с X11 или сменете операционната система."),
+        ("scrap_user_cancelled", ""),
+        ("scrap_portal_no_response", ""),
+        ("scrap_pipewire_required", ""),
+        ("scrap_portal_missing", ""),
+        ("scrap_no_display_attached", ""),
+        ("scrap_dbus_unreachable", ""),
+        ("scrap_no_user_session", ""),
+        ("scrap_access_revoked", ""),
+        ("scrap_wayland_unavailable", ""),
+        ("scrap_screen_locked", ""),
     ].iter().cloned().collect();
 }
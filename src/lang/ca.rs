@@ -380,8 +380,6 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Keyboard Settings", "Configuració del teclat"),
         ("Full Access", "Accés complet"),
         ("Screen Share", "Compartició de pantalla"),
-        ("Wayland requires Ubuntu 21.04 or higher version.", "Wayland requereix Ubuntu 21.04 o superior"),
-        ("Wayland requires higher version of linux distro. Please try X11 desktop or change your OS.", "Wayland requereix una versió superior de sistema Linux per a funcionar. Proveu iniciant un entorn d'escriptori amb x11 o actualitzeu el vostre sistema operatiu."),
         ("JumpLink", "Marcador"),
         ("Please Select the screen to be shared(Operate on the peer side).", "Seleccioneu la pantalla que compartireu (quina serà visible al client)"),
         ("Show RustDesk", "Mostra el RustDesk"),
@@ -648,5 +646,20 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Authentication Required", "Autenticació requerida"),
         ("Authenticate", "Autentica"),
         ("web_id_input_tip", "Podeu inserir el número ID al propi servidor; l'accés directe per IP no és compatible amb el client web.\nSi voleu accedir a un dispositiu d'un altre servidor, afegiu l'adreça del servidor, com ara <id>@<adreça_del_servidor>?key=<valor_de_la_clau> (p. ex.\n9123456234@192.168.16.1:21117?key=5Qbwsde3unUcJBtrx9ZkvUmwFNoExHzpryHuPUdqlWM=.\nSi voleu accedir a un dispositiu en un servidor públic, no cal que inseriu la clau pública «<id>@» per al servidor públic."),
+        ("scrap_ubuntu_higher_required", "Wayland requereix Ubuntu 21.04 o superior"),
+        ("scrap_fedora_higher_required", ""),
+        ("scrap_debian_higher_required", ""),
+        ("scrap_opensuse_higher_required", ""),
+        ("scrap_other_version_or_x11_required", "Wayland requereix una versió superior de sistema Linux per a funcionar. Proveu iniciant un entorn d'escriptori amb x11 o actualitzeu el vostre sistema operatiu."),
+        ("scrap_user_cancelled", ""),
+        ("scrap_portal_no_response", ""),
+        ("scrap_pipewire_required", ""),
+        ("scrap_portal_missing", ""),
+        ("scrap_no_display_attached", ""),
+        ("scrap_dbus_unreachable", ""),
+        ("scrap_no_user_session", ""),
+        ("scrap_access_revoked", ""),
+        ("scrap_wayland_unavailable", ""),
+        ("scrap_screen_locked", ""),
     ].iter().cloned().collect();
 }
@@ -380,8 +380,6 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Keyboard Settings", "键盘设置"),
         ("Full Access", "完全访问"),
         ("Screen Share", "仅共享屏幕"),
-        ("Wayland requires Ubuntu 21.04 or higher version.", "Wayland 需要 Ubuntu 21.04 或更高版本。"),
-        ("Wayland requires higher version of linux distro. Please try X11 desktop or change your OS.", "Wayland 需要更高版本的 linux 发行版。 请尝试 X11 桌面或更改您的操作系统。"),
         ("JumpLink", "查看"),
         ("Please Select the screen to be shared(Operate on the peer side).", "请选择要分享的画面（对端操作）。"),
         ("Show RustDesk", "显示 RustDesk"),
@@ -648,5 +646,20 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Authentication Required", "需要身份验证"),
         ("Authenticate", "认证"),
         ("web_id_input_tip", "可以输入同一个服务器内的 ID，web 客户端不支持直接 IP 访问。\n要访问另一台服务器上的设备，请附加服务器地址（<ID>@<服务器地址>?key=<密钥>）。比如，\n9123456234@192.168.16.1:21117?key=5Qbwsde3unUcJBtrx9ZkvUmwFNoExHzpryHuPUdqlWM=。\n要访问公共服务器上的设备，请输入 \"<ID>@public\"，无需密钥。"),
+        ("scrap_ubuntu_higher_required", "Wayland 需要 Ubuntu 21.04 或更高版本。"),
+        ("scrap_fedora_higher_required", ""),
+        ("scrap_debian_higher_required", ""),
+        ("scrap_opensuse_higher_required", ""),
+        ("scrap_other_version_or_x11_required", "Wayland 需要更高版本的 linux 发行版。 请尝试 X11 桌面或更改您的操作系统。"),
+        ("scrap_user_cancelled", ""),
+        ("scrap_portal_no_response", ""),
+        ("scrap_pipewire_required", ""),
+        ("scrap_portal_missing", ""),
+        ("scrap_no_display_attached", ""),
+        ("scrap_dbus_unreachable", ""),
+        ("scrap_no_user_session", ""),
+        ("scrap_access_revoked", ""),
+        ("scrap_wayland_unavailable", ""),
+        ("scrap_screen_locked", ""),
     ].iter().cloned().collect();
 }
@@ -236,5 +236,20 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("enable-trusted-devices-tip", "Skip 2FA verification on trusted devices"),
         ("one-way-file-transfer-tip", "One-way file transfer is enabled on the controlled side."),
         ("web_id_input_tip", "You can input an ID in the same server, direct IP access is not supported in web client.\nIf you want to access a device on another server, please append the server address (<id>@<server_address>?key=<key_value>), for example,\n9123456234@192.168.16.1:21117?key=5Qbwsde3unUcJBtrx9ZkvUmwFNoExHzpryHuPUdqlWM=.\nIf you want to access a device on a public server, please input \"<id>@public\", the key is not needed for public server."),
+        ("scrap_ubuntu_higher_required", "Wayland requires Ubuntu 21.04 or higher version."),
+        ("scrap_fedora_higher_required", "Wayland requires Fedora 34 or higher version."),
+        ("scrap_debian_higher_required", "Wayland requires Debian 11 or higher version."),
+        ("scrap_opensuse_higher_required", "Wayland requires openSUSE Leap 15.4 or higher version."),
+        ("scrap_other_version_or_x11_required", "Wayland requires higher version of linux distro. Please try X11 desktop or change your OS."),
+        ("scrap_user_cancelled", "Screen sharing was cancelled on the remote side. Please try again, and select the screen to be shared in the portal dialog."),
+        ("scrap_portal_no_response", "Failed to obtain screen capture from the portal. Please try again."),
+        ("scrap_pipewire_required", "Wayland requires PipeWire. Please install and start PipeWire, or try X11 desktop."),
+        ("scrap_portal_missing", "Wayland requires xdg-desktop-portal with ScreenCast support. Please install it, or try X11 desktop."),
+        ("scrap_no_display_attached", "No display is attached. Please attach a display, or create a virtual display."),
+        ("scrap_dbus_unreachable", "Failed to connect to the D-Bus session bus. Please make sure a desktop session is running."),
+        ("scrap_no_user_session", "Wayland screen capture must run in the desktop session of the user. Please run RustDesk in the user session, or log in to the desktop."),
+        ("scrap_access_revoked", "The host stopped sharing the screen."),
+        ("scrap_wayland_unavailable", "Wayland screen capture is unavailable on this system. Please see the documentation of Linux, or try X11 desktop."),
+        ("scrap_screen_locked", "The remote screen is locked, it's shown after it's unlocked."),
     ].iter().cloned().collect();
 }
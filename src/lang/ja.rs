@@ -380,8 +380,6 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Keyboard Settings", "キーボード設定"),
         ("Full Access", "フルアクセス"),
         ("Screen Share", "画面共有"),
-        ("Wayland requires Ubuntu 21.04 or higher version.", "Waylandを使用するには、Ubuntu 21.04 以降のバージョンが必要です。"),
-        ("Wayland requires higher version of linux distro. Please try X11 desktop or change your OS.", "Waylandを使用するには、より新しいLinuxディストリビューションが必要です。 X11デスクトップを試すか、OSを変更してください。"),
         ("JumpLink", "View"),
         ("Please Select the screen to be shared(Operate on the peer side).", "共有する画面を選択してください(リモートコンピューターが操作します)"),
         ("Show RustDesk", "RustDeskを表示"),
@@ -648,5 +646,20 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Authentication Required", ""),
         ("Authenticate", ""),
         ("web_id_input_tip", ""),
+        ("scrap_ubuntu_higher_required", "Waylandを使用するには、Ubuntu 21.04 以降のバージョンが必要です。"),
+        ("scrap_fedora_higher_required", ""),
+        ("scrap_debian_higher_required", ""),
+        ("scrap_opensuse_higher_required", ""),
+        ("scrap_other_version_or_x11_required", "Waylandを使用するには、より新しいLinuxディストリビューションが必要です。 X11デスクトップを試すか、OSを変更してください。"),
+        ("scrap_user_cancelled", ""),
+        ("scrap_portal_no_response", ""),
+        ("scrap_pipewire_required", ""),
+        ("scrap_portal_missing", ""),
+        ("scrap_no_display_attached", ""),
+        ("scrap_dbus_unreachable", ""),
+        ("scrap_no_user_session", ""),
+        ("scrap_access_revoked", ""),
+        ("scrap_wayland_unavailable", ""),
+        ("scrap_screen_locked", ""),
     ].iter().cloned().collect();
 }
@@ -33,7 +33,8 @@ pub const PA_SAMPLE_RATE: u32 = 48000;
 static mut UNMODIFIED: bool = true;
 
 lazy_static::lazy_static! {
-    pub static ref IS_X11: bool = hbb_common::platform::linux::is_x11_or_headless();
+    // Updated by `refresh_is_x11()` if the user switches the session type.
+    static ref IS_X11: AtomicBool = AtomicBool::new(scrap::detect_x11());
 }
 
 thread_local! {
@@ -1469,7 +1470,13 @@ impl Drop for WallPaperRemover {
 
 #[inline]
 pub fn is_x11() -> bool {
-    *IS_X11
+    IS_X11.load(Ordering::SeqCst) || scrap::is_x11_fallback()
+}
+
+// Re-evaluates the session type, returns true if it's changed.
+pub fn refresh_is_x11() -> bool {
+    let is_x11 = scrap::detect_x11();
+    IS_X11.swap(is_x11, Ordering::SeqCst) != is_x11
 }
 
 #[inline]
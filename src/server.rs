@@ -35,7 +35,9 @@ cfg_if::cfg_if! {
 if #[cfg(not(any(target_os = "android", target_os = "ios")))] {
 mod clipboard_service;
 #[cfg(target_os = "linux")]
-pub(crate) mod wayland;
+pub mod wayland;
+#[cfg(all(target_os = "linux", feature = "wayland-shm"))]
+pub mod wayland_shm;
 #[cfg(target_os = "linux")]
 pub mod uinput;
 #[cfg(target_os = "linux")]
@@ -489,10 +491,8 @@ pub async fn start_server(is_server: bool, no_server: bool) {
             }
         });
         input_service::fix_key_down_timeout_loop();
-        #[cfg(target_os = "linux")]
-        if input_service::wayland_use_uinput() {
-            allow_err!(input_service::setup_uinput(0, 1920, 0, 1080).await);
-        }
+        // The wayland uinput is set up by the first connection which controls the keyboard and mouse,
+        // see `wayland::set_conn_input_enabled()`.
         #[cfg(any(target_os = "macos", target_os = "linux"))]
         tokio::spawn(async { sync_and_watch_config_dir().await });
         #[cfg(target_os = "windows")]
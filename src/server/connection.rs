@@ -486,6 +486,8 @@ impl Connection {
                             if &name == "keyboard" {
                                 conn.keyboard = enabled;
                                 conn.send_permission(Permission::Keyboard, enabled).await;
+                                #[cfg(target_os = "linux")]
+                                conn.update_wayland_input().await;
                                 if let Some(s) = conn.server.upgrade() {
                                     s.write().unwrap().subscribe(
                                         NAME_CURSOR,
@@ -1213,6 +1215,8 @@ impl Connection {
             return;
         }
         #[cfg(target_os = "linux")]
+        self.update_wayland_input().await;
+        #[cfg(target_os = "linux")]
         if !self.file_transfer.is_some() && !self.port_forward_socket.is_some() {
             let mut msg = "".to_string();
             if crate::platform::linux::is_login_screen_wayland() {
@@ -1283,7 +1287,7 @@ impl Connection {
 
             match super::display_service::update_get_sync_displays_on_login().await {
                 Err(err) => {
-                    res.set_error(format!("{}", err));
+                    res.set_error(self.peer_error_text(format!("{}", err)));
                 }
                 Ok(displays) => {
                     // For compatibility with old versions, we need to send the displays to the peer.
@@ -1293,6 +1297,17 @@ impl Connection {
                         self.retina.set_displays(&displays);
                     }
                     pi.displays = displays;
+                    // Resume on the display selected in the last session, see `wayland::set_current_display()`.
+                    #[cfg(target_os = "linux")]
+                    if !is_x11() {
+                        if let (Some(idx), Some(server)) =
+                            (super::wayland::get_current_display(), self.server.upgrade())
+                        {
+                            if idx != self.display_idx && idx < pi.displays.len() {
+                                self.switch_display_to(idx, server);
+                            }
+                        }
+                    }
                     pi.current_display = self.display_idx as _;
                     #[cfg(not(any(target_os = "android", target_os = "ios")))]
                     {
@@ -1430,6 +1445,17 @@ impl Connection {
         self.keyboard && !self.disable_keyboard
     }
 
+    // The wayland uinput is set up only while a peer can control the keyboard and mouse.
+    #[cfg(target_os = "linux")]
+    async fn update_wayland_input(&self) {
+        let enabled = self.authorized
+            && !self.closed
+            && self.file_transfer.is_none()
+            && self.port_forward_socket.is_none()
+            && self.peer_keyboard_enabled();
+        super::wayland::set_conn_input_enabled(self.inner.id(), enabled).await;
+    }
+
     fn clipboard_enabled(&self) -> bool {
         self.clipboard && !self.disable_clipboard
     }
@@ -1473,6 +1499,17 @@ impl Connection {
         self.send_to_cm(ipc::Data::FS(data));
     }
 
+    // The capture errors are translation keys, see `crate::client::SCRAP_KEYS_MIN_VERSION`.
+    fn peer_error_text(&self, err: String) -> String {
+        if err.starts_with("scrap_")
+            && get_version_number(&self.lr.version)
+                < get_version_number(crate::client::SCRAP_KEYS_MIN_VERSION)
+        {
+            return crate::lang::english_text(&err);
+        }
+        err
+    }
+
     async fn send_login_error<T: std::string::ToString>(&mut self, err: T) {
         let mut msg_out = Message::new();
         let mut res = LoginResponse::new();
@@ -2619,6 +2656,12 @@ impl Connection {
         if self.display_idx != display_idx {
             if let Some(server) = self.server.upgrade() {
                 self.switch_display_to(display_idx, server.clone());
+                #[cfg(target_os = "linux")]
+                if !is_x11() {
+                    if let Err(e) = super::wayland::set_current_display(display_idx) {
+                        log::warn!("Failed to set the current wayland display, {}", e);
+                    }
+                }
 
                 #[cfg(not(any(target_os = "android", target_os = "ios")))]
                 if s.width != 0 && s.height != 0 {
@@ -2906,6 +2949,17 @@ impl Connection {
                 }
             }
         }
+        // The capture is shared by the connections, so only the single remote peer can pause it.
+        #[cfg(target_os = "linux")]
+        if let Ok(q) = o.pause_capture.enum_value() {
+            if q == BoolOption::Yes {
+                if raii::AuthedConnID::remote_and_file_conn_count() <= 1 {
+                    super::wayland::pause_capture();
+                }
+            } else if q == BoolOption::No {
+                super::wayland::resume_capture();
+            }
+        }
         if let Ok(q) = o.disable_audio.enum_value() {
             if q != BoolOption::NotSet {
                 self.disable_audio = q == BoolOption::Yes;
@@ -2942,6 +2996,8 @@ impl Connection {
         if let Ok(q) = o.disable_keyboard.enum_value() {
             if q != BoolOption::NotSet {
                 self.disable_keyboard = q == BoolOption::Yes;
+                #[cfg(target_os = "linux")]
+                self.update_wayland_input().await;
                 if let Some(s) = self.server.upgrade() {
                     s.write().unwrap().subscribe(
                         super::clipboard_service::NAME,
@@ -3132,6 +3188,8 @@ impl Connection {
         // But it's not necessary now and we have to consider two audio services(client, server).
         crate::audio_service::set_voice_call_input_device(None, true);
         log::info!("#{} Connection closed: {}", self.inner.id(), reason);
+        #[cfg(target_os = "linux")]
+        self.update_wayland_input().await;
         if lock && self.lock_after_session_end && self.keyboard {
             #[cfg(not(any(target_os = "android", target_os = "ios")))]
             lock_screen().await;
@@ -3812,6 +3870,11 @@ mod raii {
     impl AuthedConnID {
         pub fn new(id: i32, conn_type: AuthConnType) -> Self {
             AUTHED_CONNS.lock().unwrap().push((id, conn_type));
+            // The new peer needs the frames, even if the capture is paused by the other peer.
+            #[cfg(target_os = "linux")]
+            if conn_type == AuthConnType::Remote {
+                super::wayland::resume_capture();
+            }
             Self::check_wake_lock();
             use std::sync::Once;
             static _ONCE: Once = Once::new();
@@ -3867,7 +3930,12 @@ mod raii {
                 #[cfg(windows)]
                 let _ = virtual_display_manager::reset_all();
                 #[cfg(target_os = "linux")]
-                scrap::wayland::pipewire::try_close_session();
+                if super::wayland::is_keep_armed() {
+                    super::wayland::arm_capture();
+                } else {
+                    super::wayland::clear();
+                    scrap::wayland::pipewire::try_close_session();
+                }
             }
             Self::check_wake_lock();
         }
@@ -17,6 +17,9 @@ pub const NAME: &'static str = "display";
 #[cfg(windows)]
 const DUMMY_DISPLAY_SIDE_MAX_SIZE: usize = 1024;
 
+// Sent to the peer if the refresh rate of the display is unknown.
+const DEFAULT_REFRESH_RATE: f64 = 60.0;
+
 struct ChangedResolution {
     original: (i32, i32),
     changed: (i32, i32),
@@ -303,17 +306,30 @@ pub(super) fn get_display_info(idx: usize) -> Option<DisplayInfo> {
 // Display to DisplayInfo
 // The DisplayInfo is be sent to the peer.
 pub(super) fn check_update_displays(all: &Vec<Display>) {
-    let displays = all
-        .iter()
-        .map(|d| {
+    let displays = get_displays_2(all);
+    SYNC_DISPLAYS.lock().unwrap().check_changed(displays);
+}
+
+// Display to DisplayInfo, without updating the synced displays.
+pub(super) fn get_displays_2(all: &Vec<Display>) -> Vec<DisplayInfo> {
+    let primary = get_primary_2(all);
+    all.iter()
+        .enumerate()
+        .map(|(idx, d)| {
             let display_name = d.name();
             #[allow(unused_assignments)]
             #[allow(unused_mut)]
             let mut scale = 1.0;
-            #[cfg(target_os = "macos")]
+            #[cfg(any(target_os = "macos", target_os = "linux"))]
             {
                 scale = d.scale();
             }
+            #[allow(unused_mut)]
+            let mut refresh_rate = DEFAULT_REFRESH_RATE;
+            #[cfg(target_os = "linux")]
+            if let Some(rate) = d.refresh_rate() {
+                refresh_rate = rate;
+            }
             let original_resolution = get_original_resolution(
                 &display_name,
                 ((d.width() as f64) / scale).round() as usize,
@@ -329,11 +345,12 @@ pub(super) fn check_update_displays(all: &Vec<Display>) {
                 cursor_embedded: false,
                 original_resolution,
                 scale,
+                refresh_rate,
+                is_primary: idx == primary,
                 ..Default::default()
             }
         })
-        .collect::<Vec<DisplayInfo>>();
-    SYNC_DISPLAYS.lock().unwrap().check_changed(displays);
+        .collect::<Vec<DisplayInfo>>()
 }
 
 pub fn is_inited_msg() -> Option<Message> {
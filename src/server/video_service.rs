@@ -67,6 +67,9 @@ use std::{
 
 pub const NAME: &'static str = "video";
 pub const OPTION_REFRESH: &'static str = "refresh";
+// Recreate the wayland capturer on the consecutive frame errors, then restart the capture.
+#[cfg(target_os = "linux")]
+const WAYLAND_MAX_FRAME_ERRORS: u32 = 3;
 
 lazy_static::lazy_static! {
     static ref FRAME_FETCHED_NOTIFIER: (UnboundedSender<(i32, Option<Instant>)>, Arc<TokioMutex<UnboundedReceiver<(i32, Option<Instant>)>>>) = {
@@ -267,12 +270,14 @@ fn check_uac_switch(privacy_mode_id: i32, capturer_privacy_mode_id: i32) -> Resu
     Ok(())
 }
 
-pub(super) struct CapturerInfo {
+pub struct CapturerInfo {
     pub origin: (i32, i32),
     pub width: usize,
     pub height: usize,
     pub ndisplay: usize,
     pub current: usize,
+    // The ratio of the captured size to the logical size.
+    pub scale: f64,
     pub privacy_mode_id: i32,
     pub _capturer_privacy_mode_id: i32,
     pub capturer: Box<dyn TraitCapturer>,
@@ -296,7 +301,7 @@ fn get_capturer(current: usize, portable_service_running: bool) -> ResultType<Ca
     #[cfg(target_os = "linux")]
     {
         if !is_x11() {
-            return super::wayland::get_capturer();
+            return super::wayland::get_capturer_for(current);
         }
     }
 
@@ -322,6 +327,12 @@ fn get_capturer(current: usize, portable_service_running: bool) -> ResultType<Ca
     }
 
     let (origin, width, height) = (display.origin(), display.width(), display.height());
+    #[allow(unused_mut)]
+    let mut scale = 1.0;
+    #[cfg(target_os = "macos")]
+    {
+        scale = display.scale();
+    }
     let name = display.name();
     log::debug!(
         "#displays={}, current={}, origin: {:?}, width={}, height={}, cpus={}/{}, name:{}",
@@ -376,26 +387,110 @@ fn get_capturer(current: usize, portable_service_running: bool) -> ResultType<Ca
         height,
         ndisplay,
         current,
+        scale,
         privacy_mode_id,
         _capturer_privacy_mode_id: capturer_privacy_mode_id,
         capturer,
     })
 }
 
+// The result of `benchmark_capture()`. The latencies are the durations of the `frame()` calls which return a frame.
+#[derive(Debug, Clone, Default, serde_derive::Serialize)]
+pub struct CaptureBenchmark {
+    pub display: usize,
+    pub width: usize,
+    pub height: usize,
+    pub duration_ms: u64,
+    pub frames: u64,
+    pub fps: f64,
+    pub avg_latency_ms: f64,
+    pub p99_latency_ms: f64,
+    // `WouldBlock`, no new frame in time or the screen is not changed.
+    pub timeouts: u64,
+    pub errors: u64,
+}
+
+const BENCHMARK_FRAME_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Captures the primary display for `duration` without encoding or sending the frames,
+// to tell whether the lag is caused by the capture. The capture state initialized by it is cleared afterwards.
+pub async fn benchmark_capture(duration: Duration) -> ResultType<CaptureBenchmark> {
+    #[cfg(target_os = "linux")]
+    let was_initialized = super::wayland::is_initialized();
+    #[cfg(target_os = "linux")]
+    if !is_x11() {
+        super::wayland::check_init().await?;
+    }
+    let display = display_service::get_primary();
+    let res =
+        hbb_common::tokio::task::spawn_blocking(move || benchmark_capture_(display, duration))
+            .await;
+    #[cfg(target_os = "linux")]
+    if !was_initialized {
+        super::wayland::clear();
+    }
+    let res = res?;
+    if let Ok(benchmark) = &res {
+        log::info!("capture benchmark: {:?}", benchmark);
+    }
+    res
+}
+
+fn benchmark_capture_(display: usize, duration: Duration) -> ResultType<CaptureBenchmark> {
+    let mut c = get_capturer(display, false)?;
+    let mut benchmark = CaptureBenchmark {
+        display,
+        width: c.width,
+        height: c.height,
+        ..Default::default()
+    };
+    let mut latencies = Vec::new();
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        let t = Instant::now();
+        match c.frame(BENCHMARK_FRAME_TIMEOUT) {
+            Ok(_) => latencies.push(t.elapsed()),
+            Err(e) if e.kind() == WouldBlock => {
+                benchmark.timeouts += 1;
+                // X11 returns at once if the screen is not changed.
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            Err(_) => {
+                benchmark.errors += 1;
+                std::thread::sleep(BENCHMARK_FRAME_TIMEOUT);
+            }
+        }
+    }
+    let elapsed = start.elapsed();
+    benchmark.duration_ms = elapsed.as_millis() as u64;
+    benchmark.frames = latencies.len() as u64;
+    benchmark.fps = benchmark.frames as f64 / elapsed.as_secs_f64().max(0.001);
+    if !latencies.is_empty() {
+        latencies.sort();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        benchmark.avg_latency_ms = ms(latencies.iter().sum::<Duration>()) / latencies.len() as f64;
+        let p99 = (latencies.len() * 99).div_ceil(100).max(1) - 1;
+        benchmark.p99_latency_ms = ms(latencies[p99]);
+    }
+    Ok(benchmark)
+}
+
 fn run(vs: VideoService) -> ResultType<()> {
     let _raii = Raii::new(vs.idx);
-    // Wayland only support one video capturer for now. It is ok to call ensure_inited() here.
+    // Wayland creates the capturers of all displays in one portal session.
+    // It is ok to call ensure_inited() here.
     //
     // ensure_inited() is needed because clear() may be called.
-    // to-do: wayland ensure_inited should pass current display index.
-    // But for now, we do not support multi-screen capture on wayland.
     #[cfg(target_os = "linux")]
     super::wayland::ensure_inited()?;
     #[cfg(target_os = "linux")]
     let _wayland_call_on_ret = SimpleCallOnReturn {
         b: true,
         f: Box::new(|| {
-            super::wayland::clear();
+            // Keep the cached capturers on switching, to avoid prompting again.
+            if super::wayland::displays_changed() {
+                super::wayland::clear();
+            }
         }),
     };
 
@@ -406,7 +501,28 @@ fn run(vs: VideoService) -> ResultType<()> {
 
     let display_idx = vs.idx;
     let sp = vs.sp;
+    #[cfg(target_os = "linux")]
+    let capture_size_generation = super::wayland::capture_size_generation();
     let mut c = get_capturer(display_idx, last_portable_service_running)?;
+    #[cfg(target_os = "linux")]
+    if !is_x11() {
+        log::info!(
+            "wayland frame timeout: {:?}",
+            super::wayland::get_frame_timeout()
+        );
+    }
+    // Do not capture faster than the refresh rate of the display.
+    #[allow(unused_mut)]
+    let mut min_spf = Duration::ZERO;
+    #[cfg(target_os = "linux")]
+    if !is_x11() {
+        if let Some(rate) = get_display_info(display_idx)
+            .map(|d| d.refresh_rate)
+            .filter(|rate| *rate > 0.0)
+        {
+            min_spf = Duration::from_secs_f64(1.0 / rate);
+        }
+    }
     #[cfg(windows)]
     if !scrap::codec::enable_directx_capture() && !c.is_gdi() {
         log::info!("disable dxgi with option, fall back to gdi");
@@ -481,6 +597,11 @@ fn run(vs: VideoService) -> ResultType<()> {
 
     #[cfg(target_os = "linux")]
     let mut would_block_count = 0u32;
+    #[cfg(target_os = "linux")]
+    let mut wayland_frame_errors = 0u32;
+    // The `frame_seq()` of the last frame captured by this loop, to detect the frames taken by others.
+    #[cfg(target_os = "linux")]
+    let mut last_frame_seq: Option<u64> = None;
     let mut yuv = Vec::new();
     let mut mid_data = Vec::new();
     let mut repeat_encode_counter = 0;
@@ -493,7 +614,7 @@ fn run(vs: VideoService) -> ResultType<()> {
         check_uac_switch(c.privacy_mode_id, c._capturer_privacy_mode_id)?;
 
         let mut video_qos = VIDEO_QOS.lock().unwrap();
-        spf = video_qos.spf();
+        spf = video_qos.spf().max(min_spf);
         if quality != video_qos.quality() {
             log::debug!("quality: {:?} -> {:?}", quality, video_qos.quality());
             quality = video_qos.quality();
@@ -542,6 +663,16 @@ fn run(vs: VideoService) -> ResultType<()> {
             bail!("SWITCH");
         }
         check_privacy_mode_changed(&sp, display_idx, &c)?;
+        #[cfg(target_os = "linux")]
+        if !is_x11() && super::wayland::displays_changed() {
+            log::info!("switch due to wayland displays changed");
+            bail!("SWITCH");
+        }
+        #[cfg(target_os = "linux")]
+        if !is_x11() && super::wayland::capture_size_generation() != capture_size_generation {
+            log::info!("switch due to wayland capture resolution changed");
+            bail!("SWITCH");
+        }
         #[cfg(windows)]
         {
             if crate::platform::windows::desktop_changed()
@@ -565,6 +696,20 @@ fn run(vs: VideoService) -> ResultType<()> {
         let res = match c.frame(spf) {
             Ok(frame) => {
                 repeat_encode_counter = 0;
+                #[cfg(target_os = "linux")]
+                if !is_x11() {
+                    let seq = super::wayland::frame_seq(display_idx);
+                    if let (Some(last), Some(seq)) = (last_frame_seq, seq) {
+                        if seq > last + 1 {
+                            log::debug!(
+                                "{} wayland frames of display {} are not encoded",
+                                seq - last - 1,
+                                display_idx
+                            );
+                        }
+                    }
+                    last_frame_seq = seq;
+                }
                 if frame.valid() {
                     let frame = frame.to(encoder.yuvfmt(), &mut yuv, &mut mid_data)?;
                     let send_conn_ids = handle_one_frame(
@@ -593,6 +738,12 @@ fn run(vs: VideoService) -> ResultType<()> {
         };
 
         match res {
+            #[cfg(target_os = "linux")]
+            Err(ref e) if !is_x11() && super::wayland::is_paused_error(e) => {
+                // Nothing is captured or encoded until the capture is resumed.
+                std::thread::sleep(spf);
+                continue;
+            }
             Err(ref e) if e.kind() == WouldBlock => {
                 #[cfg(windows)]
                 if try_gdi > 0 && !c.is_gdi() {
@@ -648,12 +799,25 @@ fn run(vs: VideoService) -> ResultType<()> {
                     log::info!("dxgi error, fall back to gdi: {:?}", err);
                     continue;
                 }
+                #[cfg(target_os = "linux")]
+                if !is_x11() {
+                    if wayland_frame_errors < WAYLAND_MAX_FRAME_ERRORS {
+                        wayland_frame_errors += 1;
+                        log::warn!("wayland capture error: {:?}, recreate the capturer", err);
+                        drop(c);
+                        c = super::wayland::force_reinit(display_idx)?;
+                        last_frame_seq = None;
+                        continue;
+                    }
+                    super::wayland::clear();
+                }
                 return Err(err.into());
             }
             _ => {
                 #[cfg(target_os = "linux")]
                 {
                     would_block_count = 0;
+                    wayland_frame_errors = 0;
                 }
             }
         }
@@ -1,23 +1,481 @@
 use super::*;
-use hbb_common::{allow_err, platform::linux::DISTRO};
-use scrap::{is_cursor_embedded, set_map_err, Capturer, Display, Frame, TraitCapturer};
+use hbb_common::platform::linux::{DISPLAY_SERVER_WAYLAND, DISPLAY_SERVER_X11, DISTRO};
+use scrap::wayland::pipewire::{SOURCE_TYPE_VIRTUAL, SOURCE_TYPE_WINDOW};
+use scrap::{
+    get_map_err, is_cursor_embedded, set_map_err, Capturer, Display, Frame, PixelBuffer,
+    TraitCapturer, TraitPixelBuffer,
+};
+use std::collections::VecDeque;
 use std::io;
 use std::process::{Command, Output};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Instant;
 
-use crate::{
-    client::{
-        SCRAP_OTHER_VERSION_OR_X11_REQUIRED, SCRAP_UBUNTU_HIGHER_REQUIRED, SCRAP_X11_REQUIRED,
-    },
-    platform::linux::is_x11,
+use crate::client::{
+    SCRAP_ACCESS_REVOKED, SCRAP_DBUS_UNREACHABLE, SCRAP_DEBIAN_HIGHER_REQUIRED,
+    SCRAP_FEDORA_HIGHER_REQUIRED, SCRAP_NO_DISPLAY_ATTACHED, SCRAP_NO_USER_SESSION,
+    SCRAP_OPENSUSE_HIGHER_REQUIRED, SCRAP_PIPEWIRE_REQUIRED, SCRAP_PORTAL_MISSING,
+    SCRAP_PORTAL_NO_RESPONSE, SCRAP_SCREEN_LOCKED, SCRAP_UBUNTU_HIGHER_REQUIRED,
+    SCRAP_USER_CANCELLED, SCRAP_WAYLAND_UNAVAILABLE, SCRAP_X11_REQUIRED,
 };
 
+#[cfg(not(test))]
+use crate::platform::linux::is_x11;
+
+// The tests always run the wayland path, with the mock capturers in `CAP_DISPLAY_INFO`.
+#[cfg(test)]
+fn is_x11() -> bool {
+    false
+}
+
+#[cfg(not(test))]
+use crate::input_service as uinput_backend;
+// The tests record the uinput calls instead of opening `/dev/uinput`.
+#[cfg(test)]
+use tests::mock_uinput as uinput_backend;
+
 lazy_static::lazy_static! {
-    static ref CAP_DISPLAY_INFO: RwLock<u64> = RwLock::new(0);
-    static ref LOG_SCRAP_COUNT: Mutex<u32> = Mutex::new(0);
+    static ref CAP_DISPLAY_INFO: RwLock<Option<Arc<CapDisplayInfo>>> = RwLock::new(None);
+    static ref LOG_SCRAP_COUNT: Mutex<ScrapLogState> = Default::default();
+    static ref SCRAP_LOG_POLICY: RwLock<ScrapLogPolicy> = Default::default();
+    // None means the timeout passed by the caller of `frame()` is used.
+    static ref FRAME_TIMEOUT: RwLock<Option<Duration>> = RwLock::new(None);
+    // The last error which stops the capture, reported by `common_get_error()`.
+    static ref LAST_ERROR: Mutex<Option<WaylandCaptureError>> = Default::default();
+    // (retries, base delay) of requesting the displays from the portal in `check_init()`.
+    static ref INIT_RETRY: RwLock<(u32, Duration)> =
+        RwLock::new((DEFAULT_INIT_RETRIES, DEFAULT_INIT_RETRY_DELAY));
+    // The base of the frame timestamps in `CAPTURE_COUNTERS`.
+    static ref CAPTURE_COUNTERS_BASE: Instant = Instant::now();
+    // Serializes the initialization in `check_init()`.
+    // The guard is moved into the blocking initialization task, so it's released only after the task is done.
+    static ref INIT_STATE: Arc<hbb_common::tokio::sync::Mutex<InitState>> = Default::default();
+    static ref DETECTED_BACKEND: DisplayBackend = DisplayBackend::detect();
+    static ref LAST_UINPUT_ERROR: Mutex<Option<String>> = Default::default();
+    // The connections which control the keyboard and mouse, see `set_conn_input_enabled()`.
+    static ref INPUT_CONNS: Mutex<std::collections::HashSet<i32>> = Default::default();
+    // See `register_lifecycle_hook()`, in the registration order.
+    static ref LIFECYCLE_HOOKS: RwLock<Vec<Arc<dyn CaptureLifecycle>>> = Default::default();
+    // The frame formats by display index, see `frame_format()`.
+    static ref FRAME_FORMATS: Mutex<HashMap<usize, FrameFormat>> = Default::default();
+    // (sampled hash of the last frame, identical frames since then) of each display.
+    static ref FROZEN_STATE: Mutex<HashMap<usize, (u64, u64)>> = Default::default();
+    // The timestamp of the last frame of each display in nanoseconds, see `frame_timestamp()`.
+    static ref FRAME_TIMESTAMPS: Mutex<HashMap<usize, u64>> = Default::default();
+    // The CRC-32 of the last frame of each display, see `frame_checksum()`.
+    static ref FRAME_CHECKSUMS: Mutex<HashMap<usize, u32>> = Default::default();
+    static ref CRC32_TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        for (i, v) in table.iter_mut().enumerate() {
+            let mut c = i as u32;
+            for _ in 0..8 {
+                c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+            }
+            *v = c;
+        }
+        table
+    };
+    // The sequence number of the last frame of each display, see `frame_seq()`.
+    static ref FRAME_SEQS: Mutex<HashMap<usize, u64>> = Default::default();
+    // See `set_displays_change_debounce()`.
+    static ref DISPLAYS_CHANGE_DEBOUNCE: RwLock<Duration> = RwLock::new(DEFAULT_DISPLAYS_CHANGE_DEBOUNCE);
+    // See `get_cached_displays()`, with the capture state it's built from.
+    static ref DISPLAYS_CACHE: Mutex<Option<(std::sync::Weak<CapDisplayInfo>, Arc<Vec<DisplayInfo>>)>> = Default::default();
+    // See `set_adaptive_skip()`, `None` never skips.
+    static ref ADAPTIVE_SKIP: RwLock<Option<AdaptiveSkipPolicy>> = Default::default();
+    // See `set_reprompt_cooldown()`.
+    static ref REPROMPT_COOLDOWN: RwLock<Duration> = RwLock::new(DEFAULT_REPROMPT_COOLDOWN);
+    // The last changed rects seen by the displays watcher and when they were first seen.
+    static ref PENDING_DISPLAYS_CHANGE: Mutex<Option<(Vec<((i32, i32), usize, usize)>, Instant)>> = Default::default();
+    // See `set_frame_watchdog_threshold()`.
+    static ref FRAME_WATCHDOG_THRESHOLD: RwLock<Duration> = RwLock::new(DEFAULT_FRAME_WATCHDOG_THRESHOLD);
+    // When the capturer of each display started failing, removed on the next successful frame.
+    static ref FRAME_FAILING_SINCE: Mutex<HashMap<usize, Instant>> = Default::default();
+    // The seat0 session whose lock state is watched, and when the lock state was refreshed.
+    // Both are reset by `clear()`, so a new capture refreshes the lock state of the current session.
+    static ref SEAT0_SESSION: Mutex<Option<String>> = Default::default();
+    static ref SCREEN_LOCK_CHECKED_AT: Mutex<Option<Instant>> = Default::default();
+}
+
+// The index of the display which overrides the detected primary one, see `set_preferred_primary()`.
+const OPTION_PREFERRED_PRIMARY: &str = "wayland-preferred-primary";
+// The last display selected by `set_current_display()`, restored on `check_init()`.
+const OPTION_CURRENT_DISPLAY: &str = "wayland-current-display";
+const OPTION_CURRENT_DISPLAY_NAME: &str = "wayland-current-display-name";
+
+const DEFAULT_INIT_RETRIES: u32 = 2;
+const DEFAULT_INIT_RETRY_DELAY: Duration = Duration::from_millis(1500);
+
+// Max number of cached capturers, the least recently used one is evicted.
+const MAX_CACHED_CAPTURERS: usize = 4;
+// The frames retained by a capturer, the buffers of pipewire and the copy of the recorder.
+// Only used to estimate the memory if the buffer count is not set, see `set_buffer_count()`.
+const DEFAULT_RETAINED_FRAMES: usize = 8;
+
+// Interval of polling `Display::all()` to detect monitor hotplug and resolution changes.
+const DISPLAYS_WATCH_INTERVAL: Duration = Duration::from_secs(3);
+
+// Resolution switching or docking may change the displays several times in a row.
+const DEFAULT_DISPLAYS_CHANGE_DEBOUNCE: Duration = Duration::from_millis(750);
+// The reconnecting peers do not prompt the user again in this time after the user cancelled.
+const DEFAULT_REPROMPT_COOLDOWN: Duration = Duration::from_secs(30);
+
+const DEFAULT_FRAME_WATCHDOG_THRESHOLD: Duration = Duration::from_secs(10);
+const FRAME_WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+// Each refresh of the lock state runs `loginctl`, see `update_screen_locked()`.
+const SCREEN_LOCK_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+// The frames grabbed by `run_capture_selftest()`, they must come in `SELFTEST_TIMEOUT`.
+const SELFTEST_FRAMES: usize = 5;
+const SELFTEST_TIMEOUT: Duration = Duration::from_secs(5);
+// The first frame of a new stream comes soon, the compositor sends the current screen on connecting.
+const SINGLE_FRAME_TIMEOUT: Duration = Duration::from_secs(3);
+// The displays tried by `new_cap_display_info_with_retry()` after the first one.
+const MAX_VANISHED_DISPLAY_RETRIES: usize = 2;
+// How often a subscription polls the capturer which does not push the frames.
+const SUBSCRIBE_POLL_INTERVAL: Duration = Duration::from_millis(33);
+const MIN_BUFFER_COUNT: u32 = 2;
+const MAX_BUFFER_COUNT: u32 = 16;
+// Disabled by default, a static screen also repeats the same frame.
+const DEFAULT_FROZEN_FRAME_THRESHOLD: u64 = 0;
+// The bytes of a frame hashed by `sampled_hash()`, in chunks spread over the frame.
+const FROZEN_HASH_CHUNKS: usize = 64;
+const FROZEN_HASH_CHUNK_SIZE: usize = 16;
+// The bytes of a frame in the checksum, in chunks spread over the frame, see `set_frame_checksum_sampling()`.
+const DEFAULT_FRAME_CHECKSUM_CHUNKS: usize = 64;
+const FRAME_CHECKSUM_CHUNK_SIZE: usize = 256;
+static FRAME_WATCHDOG_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// Set by the displays watcher, reset by `clear()`.
+static DISPLAYS_CHANGED: AtomicBool = AtomicBool::new(false);
+
+// The identical frames in a row to consider the stream frozen, 0 to disable the detection.
+static FROZEN_FRAME_THRESHOLD: AtomicU64 = AtomicU64::new(DEFAULT_FROZEN_FRAME_THRESHOLD);
+
+// See `set_frame_checksum()`.
+static FRAME_CHECKSUM_ENABLED: AtomicBool = AtomicBool::new(false);
+// 0 for the whole frame.
+static FRAME_CHECKSUM_CHUNKS: AtomicUsize = AtomicUsize::new(DEFAULT_FRAME_CHECKSUM_CHUNKS);
+
+// Increased by `set_capture_resolution()`, the video services restart to capture in the new size.
+static CAPTURE_SIZE_GENERATION: AtomicU64 = AtomicU64::new(0);
+// The estimated memory of the cached capturers in bytes, 0 is unlimited.
+static CAPTURER_CACHE_BUDGET: AtomicU64 = AtomicU64::new(0);
+
+// Number of finished initializations, to know if one failed while waiting for `INIT_STATE`.
+static INIT_ATTEMPTS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Default)]
+struct InitState {
+    // The error of the last initialization, returned to the callers waiting for it.
+    last_error: Option<hbb_common::anyhow::Error>,
+    // When the user cancelled the portal dialog, see `set_reprompt_cooldown()`.
+    cancelled_at: Option<Instant>,
+}
+
+// Whether the uinput keyboard and mouse are set up, the screen can be captured even if not.
+static UINPUT_READY: AtomicBool = AtomicBool::new(false);
+// Whether the uinput keyboard and mouse clients are created by `setup_uinput()`.
+static UINPUT_CREATED: AtomicBool = AtomicBool::new(false);
+// Uinput is not needed if no peer controls the keyboard and mouse, see `set_conn_input_enabled()`.
+static INPUT_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Set by `pause_capture()`, e.g. the window of the peer is minimized.
+static CAPTURE_PAUSED: AtomicBool = AtomicBool::new(false);
+// See `set_source_type()`.
+static WHOLE_DESKTOP: AtomicBool = AtomicBool::new(false);
+// Set by `arm_capture()`, reset by `resume_capture()`.
+static CAPTURE_ARMED: AtomicBool = AtomicBool::new(false);
+// See `is_capturing()`.
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+// See `set_keep_armed()`.
+static KEEP_ARMED: AtomicBool = AtomicBool::new(false);
+
+// Off by default, the users who choose wayland may not expect capturing with X11.
+static ALLOW_X11_FALLBACK: AtomicBool = AtomicBool::new(false);
+// See `set_initial_display_follows_cursor()`.
+static INITIAL_DISPLAY_FOLLOWS_CURSOR: AtomicBool = AtomicBool::new(false);
+// Updated by the frame watchdog, see `is_screen_locked()`.
+static SCREEN_LOCKED: AtomicBool = AtomicBool::new(false);
+// Off by default, the users who lock the screen may not expect it to be shared.
+static CAPTURE_WHILE_LOCKED: AtomicBool = AtomicBool::new(false);
+
+// The error kind is wrapped in the `io::Error` returned by scrap,
+// use `io::Error::get_ref()` and `downcast_ref()` to get it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaylandCaptureError {
+    PortalMissing,
+    UserCancelled,
+    PortalNoResponse,
+    PipewireDown,
+    DbusUnreachable,
+    // Running outside of the user's session, e.g. as a system service, so there's no session bus.
+    NoUserSession,
+    // The portal closed the session, e.g. the user clicked "Stop Sharing" in the system indicator.
+    AccessRevoked,
+    // An unknown error on an unknown distro, e.g. `/etc/os-release` is missing in a container.
+    Unavailable,
+    X11Required,
+    NoDisplayAttached,
+    // The message of the unmet `DistroRequirement`.
+    DistroTooOld(&'static str),
+    // Not a failure, returned by `frame()` while the capture is paused, see `pause_capture()`.
+    Paused,
+    // Not a failure, returned by `frame()` while the screen is locked, see `set_capture_while_locked()`.
+    ScreenLocked,
+    Other(String),
+}
+
+impl std::fmt::Display for WaylandCaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PortalMissing => write!(f, "{}", SCRAP_PORTAL_MISSING),
+            Self::UserCancelled => write!(f, "{}", SCRAP_USER_CANCELLED),
+            Self::PortalNoResponse => write!(f, "{}", SCRAP_PORTAL_NO_RESPONSE),
+            Self::PipewireDown => write!(f, "{}", SCRAP_PIPEWIRE_REQUIRED),
+            Self::DbusUnreachable => write!(f, "{}", SCRAP_DBUS_UNREACHABLE),
+            Self::NoUserSession => write!(f, "{}", SCRAP_NO_USER_SESSION),
+            Self::AccessRevoked => write!(f, "{}", SCRAP_ACCESS_REVOKED),
+            Self::Unavailable => write!(f, "{}", SCRAP_WAYLAND_UNAVAILABLE),
+            Self::X11Required => write!(f, "{}", SCRAP_X11_REQUIRED),
+            Self::NoDisplayAttached => write!(f, "{}", SCRAP_NO_DISPLAY_ATTACHED),
+            Self::DistroTooOld(msg) => write!(f, "{}", msg),
+            Self::Paused => write!(f, "Wayland capture is paused"),
+            Self::ScreenLocked => write!(f, "{}", SCRAP_SCREEN_LOCKED),
+            Self::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for WaylandCaptureError {}
+
+impl WaylandCaptureError {
+    fn classify(err: &str) -> Option<Self> {
+        // See `request_remote_desktop()` in scrap.
+        if err.contains("User cancelled") {
+            Some(Self::UserCancelled)
+        } else if err.contains("Failed to obtain screen capture") {
+            Some(Self::PortalNoResponse)
+        } else if err.contains("org.freedesktop.DBus.Error.ServiceUnknown")
+            || err.contains("org.freedesktop.DBus.Error.UnknownMethod")
+            || err.contains("org.freedesktop.portal")
+        {
+            Some(Self::PortalMissing)
+        } else if err.to_lowercase().contains("pipewire") {
+            Some(Self::PipewireDown)
+        } else if err.contains("org.freedesktop.DBus.Error.NoServer")
+            || err.contains("DBUS_SESSION_BUS_ADDRESS")
+            || err.contains("dbus")
+        {
+            Some(Self::DbusUnreachable)
+        } else {
+            None
+        }
+    }
+
+    // The user's decision and the environment do not change by retrying.
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::PortalNoResponse | Self::PipewireDown | Self::DbusUnreachable => true,
+            Self::Other(_) => true,
+            _ => false,
+        }
+    }
+
+    fn from_io_error(err: &io::Error) -> Option<&Self> {
+        err.get_ref()?.downcast_ref::<Self>()
+    }
+
+    fn from_error(err: &hbb_common::anyhow::Error) -> Option<&Self> {
+        err.downcast_ref::<io::Error>()
+            .and_then(Self::from_io_error)
+    }
+
+    #[inline]
+    pub fn is_user_cancelled(&self) -> bool {
+        *self == Self::UserCancelled
+    }
+}
+
+// Whether `frame()` returned because the capture is paused or the screen is locked,
+// the caller should wait instead of recreating the capturer.
+pub fn is_paused_error(err: &io::Error) -> bool {
+    matches!(
+        WaylandCaptureError::from_io_error(err),
+        Some(WaylandCaptureError::Paused | WaylandCaptureError::ScreenLocked)
+    )
+}
+
+// Notified when the capture starts, stops or fails, e.g. by the plugins for the audit logs.
+// The hooks are called synchronously from the capture paths, they should return quickly.
+// Their errors and panics are logged, the capture goes on.
+pub trait CaptureLifecycle: Send + Sync {
+    // The displays and the capturer of the current display are ready.
+    fn on_init(&self) -> ResultType<()> {
+        Ok(())
+    }
+
+    // The capture state is cleared, the capturers in use are dropped when their leases are dropped.
+    fn on_clear(&self) -> ResultType<()> {
+        Ok(())
+    }
+
+    // The capturer of the display is recreated by `force_reinit()`.
+    fn on_reinit(&self, _idx: usize) -> ResultType<()> {
+        Ok(())
+    }
+
+    // `check_init()` or `force_reinit()` failed.
+    fn on_error(&self, _err: &WaylandError) -> ResultType<()> {
+        Ok(())
+    }
+}
+
+// The hooks are run in the registration order, and can't be unregistered.
+pub fn register_lifecycle_hook(hook: impl CaptureLifecycle + 'static) {
+    LIFECYCLE_HOOKS.write().unwrap().push(Arc::new(hook));
+}
+
+// The lock is released before calling the hooks, so they can call the functions of this module.
+fn run_lifecycle_hooks(event: &str, f: impl Fn(&dyn CaptureLifecycle) -> ResultType<()>) {
+    let hooks = LIFECYCLE_HOOKS.read().unwrap().clone();
+    for hook in hooks {
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(hook.as_ref()))) {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::error!("The wayland capture hook of {} failed, {:#}", event, e),
+            Err(_) => log::error!("The wayland capture hook of {} panicked", event),
+        }
+    }
+}
+
+// The errors without a `WaylandError`, e.g. the portal errors, are reported as `NotInitialized`.
+fn run_lifecycle_error_hooks(err: &hbb_common::anyhow::Error) {
+    let err = WaylandError::from_error(err)
+        .cloned()
+        .unwrap_or(WaylandError::NotInitialized);
+    run_lifecycle_hooks("on_error", |hook| hook.on_error(&err));
+}
+
+// The errors of the getters of this module, wrapped in `ResultType`.
+// The capture errors of scrap are kept as the source, see `WaylandCaptureError::from_error()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaylandError {
+    // The capture is not initialized or is cleared, see `check_init()`.
+    NotInitialized,
+    // Called on X11.
+    WrongBackend,
+    CapturerCreateFailed,
+    DisplayEnumFailed,
+    IndexOutOfRange { idx: usize, len: usize },
+}
+
+impl std::fmt::Display for WaylandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotInitialized => write!(f, "Failed to get capturer display info"),
+            Self::WrongBackend => write!(f, "Do not call this function if not wayland"),
+            Self::CapturerCreateFailed => write!(f, "Failed to create capturer"),
+            Self::DisplayEnumFailed => write!(f, "Failed to get the wayland displays"),
+            Self::IndexOutOfRange { idx, len } => {
+                write!(f, "Failed to get display {}, displays len: {}", idx, len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WaylandError {}
+
+impl WaylandError {
+    pub fn from_error(err: &hbb_common::anyhow::Error) -> Option<&Self> {
+        err.downcast_ref::<Self>()
+    }
+}
+
+// `retries` is 0 to disable retrying, e.g. on headless CI.
+pub fn set_init_retry(retries: u32, base_delay: Duration) {
+    *INIT_RETRY.write().unwrap() = (retries, base_delay);
+}
+
+fn get_all_displays_with_retry() -> io::Result<Vec<Display>> {
+    let (retries, mut delay) = *INIT_RETRY.read().unwrap();
+    let mut retried = 0;
+    loop {
+        let start = Instant::now();
+        match Display::all() {
+            Ok(all) => {
+                log::info!(
+                    "Enumerated {} wayland displays in {:?}",
+                    all.len(),
+                    start.elapsed()
+                );
+                return Ok(all);
+            }
+            Err(e) => {
+                let retryable = WaylandCaptureError::from_io_error(&e)
+                    .map(|e| e.is_retryable())
+                    .unwrap_or(true);
+                if !retryable || retried >= retries {
+                    return Err(e);
+                }
+                log::warn!(
+                    "Failed to get wayland displays, retry in {:?}, {}",
+                    delay,
+                    e
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+                retried += 1;
+            }
+        }
+    }
 }
 
+// Installs the default error mapper, unless the embedder has installed its own by `install_error_mapper()`.
 pub fn init() {
-    set_map_err(map_err_scrap);
+    if get_map_err().is_none() {
+        install_error_mapper(None);
+    }
+    scrap::wayland::pipewire::set_on_access_revoked(Some(Arc::new(on_access_revoked)));
+}
+
+// Called by the dbus thread of scrap when the portal closes the session, the portal session is already dropped.
+// The capture is cleared, so the video service fails at once instead of waiting for the frame timeout.
+fn on_access_revoked() {
+    log::info!("The wayland screen sharing is stopped by the host");
+    *LAST_ERROR.lock().unwrap() = Some(WaylandCaptureError::AccessRevoked);
+    clear();
+}
+
+// Whether the portal closed the last session, reset when a new session is created.
+#[inline]
+pub fn is_access_revoked() -> bool {
+    scrap::wayland::pipewire::is_access_revoked()
+}
+
+// Installs the mapper of the scrap errors, `None` for the default one. Calling it again is harmless.
+// `WaylandCaptureError::from_io_error()` only recognizes the errors mapped by the default mapper.
+pub fn install_error_mapper(mapper: Option<fn(String) -> io::Error>) {
+    let mapper = mapper.unwrap_or(map_err_scrap);
+    if get_map_err().map(|f| f as usize) != Some(mapper as usize) {
+        set_map_err(mapper);
+    }
+}
+
+// Restores the previous error mapper when dropped, also on panic.
+struct MapErrGuard(Option<fn(String) -> io::Error>);
+
+impl Drop for MapErrGuard {
+    fn drop(&mut self) {
+        set_map_err(self.0.unwrap_or(map_err_scrap));
+    }
+}
+
+// Runs `f` with `temp` mapping the scrap errors, e.g. to collect the raw errors in diagnostics.
+// The mapper is global, the errors of the other capturers are also mapped by `temp` meanwhile.
+// The calls can be nested, the mappers are restored in order.
+pub fn with_map_err<T, F: FnOnce() -> T>(temp: fn(String) -> io::Error, f: F) -> T {
+    let _guard = MapErrGuard(get_map_err());
+    set_map_err(temp);
+    f()
 }
 
 fn map_err_scrap(err: String) -> io::Error {
@@ -33,272 +491,5709 @@ fn map_err_scrap(err: String) -> io::Error {
         std::process::exit(-1);
     }
 
-    if DISTRO.name.to_uppercase() == "Ubuntu".to_uppercase() {
-        if DISTRO.version_id < "21".to_owned() {
-            io::Error::new(io::ErrorKind::Other, SCRAP_UBUNTU_HIGHER_REQUIRED)
-        } else {
-            try_log(&err);
-            io::Error::new(io::ErrorKind::Other, err)
-        }
+    // The error returned is a translation key, so the raw error is only in the log.
+    try_log(&err);
+    let e = if let Some(req) = unmet_distro_requirement() {
+        WaylandCaptureError::DistroTooOld(req.message)
+    } else if is_dbus_error(&err) && !has_session_bus() {
+        WaylandCaptureError::NoUserSession
     } else {
-        try_log(&err);
-        if err.contains("org.freedesktop.portal")
-            || err.contains("pipewire")
-            || err.contains("dbus")
-        {
-            io::Error::new(io::ErrorKind::Other, SCRAP_OTHER_VERSION_OR_X11_REQUIRED)
-        } else {
-            io::Error::new(io::ErrorKind::Other, SCRAP_X11_REQUIRED)
-        }
+        WaylandCaptureError::classify(&err)
+            .unwrap_or_else(|| unclassified_error(&DISTRO.name, &err))
+    };
+    if e.is_user_cancelled() {
+        // The raw error is not shown to the peer.
+        log::info!("Wayland screen sharing is cancelled, {}", &err);
     }
+    *LAST_ERROR.lock().unwrap() = Some(e.clone());
+    io::Error::new(io::ErrorKind::Other, e)
 }
 
-fn try_log(err: &String) {
-    let mut lock_count = LOG_SCRAP_COUNT.lock().unwrap();
-    if *lock_count >= 1000000 {
-        return;
-    }
-    if *lock_count % 10000 == 0 {
-        log::error!("Failed scrap {}", err);
+// The error of the scrap errors not classified, by the distro.
+// Do not imply the distro is too old if it's unknown.
+fn unclassified_error(distro_name: &str, err: &str) -> WaylandCaptureError {
+    if distro_name.trim().is_empty() {
+        WaylandCaptureError::Unavailable
+    } else if distro_name.to_uppercase() == "Ubuntu".to_uppercase() {
+        WaylandCaptureError::Other(err.to_owned())
+    } else {
+        WaylandCaptureError::X11Required
     }
-    *lock_count += 1;
 }
 
-struct CapturerPtr(*mut Capturer);
+fn is_dbus_error(err: &str) -> bool {
+    WaylandCaptureError::classify(err) == Some(WaylandCaptureError::DbusUnreachable)
+}
 
-impl Clone for CapturerPtr {
-    fn clone(&self) -> Self {
-        Self(self.0)
+// The session bus of the environment or the one set by `use_user_session_bus()`.
+fn has_session_bus() -> bool {
+    if scrap::wayland::pipewire::get_session_bus_address().is_some() {
+        return true;
+    }
+    if std::env::var("DBUS_SESSION_BUS_ADDRESS").map_or(false, |a| !a.is_empty()) {
+        return true;
     }
+    // libdbus falls back to the bus in the runtime directory.
+    std::env::var("XDG_RUNTIME_DIR")
+        .map_or(false, |dir| std::path::Path::new(&dir).join("bus").exists())
 }
 
-impl TraitCapturer for CapturerPtr {
-    fn frame<'a>(&'a mut self, timeout: Duration) -> io::Result<Frame<'a>> {
-        unsafe { (*self.0).frame(timeout) }
+// The optional path for a system service: connect to the session bus of the logged-in user.
+// The portal may still reject a connection from another user, running in the user's session is
+// the reliable way. Returns false if the user has no session bus, `None` to use the environment.
+pub fn use_user_session_bus(uid: Option<u32>) -> bool {
+    let Some(uid) = uid else {
+        scrap::wayland::pipewire::set_session_bus_address(None);
+        return true;
+    };
+    let path = format!("/run/user/{}/bus", uid);
+    if !std::path::Path::new(&path).exists() {
+        log::warn!("No session bus of user {} at {}", uid, path);
+        return false;
     }
+    log::info!("Use the session bus of user {}: {}", uid, path);
+    scrap::wayland::pipewire::set_session_bus_address(Some(format!("unix:path={}", path)));
+    true
 }
 
-struct CapDisplayInfo {
-    rects: Vec<((i32, i32), usize, usize)>,
-    displays: Vec<DisplayInfo>,
-    num: usize,
-    primary: usize,
-    current: usize,
-    capturer: CapturerPtr,
+struct DistroRequirement {
+    // Lowercase prefix of `NAME` in `/etc/os-release`.
+    name: &'static str,
+    min_version: &'static str,
+    message: &'static str,
 }
 
-#[tokio::main(flavor = "current_thread")]
-pub(super) async fn ensure_inited() -> ResultType<()> {
-    check_init().await
+// The rolling releases, e.g. Arch and openSUSE Tumbleweed, are not listed.
+const DISTRO_REQUIREMENTS: [DistroRequirement; 4] = [
+    DistroRequirement {
+        name: "ubuntu",
+        min_version: "21",
+        message: SCRAP_UBUNTU_HIGHER_REQUIRED,
+    },
+    DistroRequirement {
+        name: "fedora",
+        min_version: "34",
+        message: SCRAP_FEDORA_HIGHER_REQUIRED,
+    },
+    DistroRequirement {
+        name: "debian",
+        min_version: "11",
+        message: SCRAP_DEBIAN_HIGHER_REQUIRED,
+    },
+    DistroRequirement {
+        name: "opensuse leap",
+        min_version: "15.4",
+        message: SCRAP_OPENSUSE_HIGHER_REQUIRED,
+    },
+];
+
+fn unmet_distro_requirement() -> Option<&'static DistroRequirement> {
+    find_unmet_distro_requirement(&DISTRO.name, &DISTRO.version_id)
 }
 
-pub(super) fn is_inited() -> Option<Message> {
-    if is_x11() {
-        None
-    } else {
-        if *CAP_DISPLAY_INFO.read().unwrap() == 0 {
-            let mut msg_out = Message::new();
-            let res = MessageBox {
-                msgtype: "nook-nocancel-hasclose".to_owned(),
-                title: "Wayland".to_owned(),
-                text: "Please Select the screen to be shared(Operate on the peer side).".to_owned(),
-                link: "".to_owned(),
-                ..Default::default()
-            };
-            msg_out.set_message_box(res);
-            Some(msg_out)
-        } else {
-            None
+fn find_unmet_distro_requirement(name: &str, version: &str) -> Option<&'static DistroRequirement> {
+    let name = name.to_lowercase();
+    DISTRO_REQUIREMENTS
+        .iter()
+        .find(|r| name.starts_with(r.name))
+        .filter(|r| version_lt(version, r.min_version))
+}
+
+// Compare the versions numerically by the dot separated parts, "9" < "21" and "100" > "21".
+// An unknown version is not less than any version.
+fn version_lt(version: &str, min_version: &str) -> bool {
+    fn parse(v: &str) -> Option<Vec<u64>> {
+        v.split('.').map(|p| p.trim().parse::<u64>().ok()).collect()
+    }
+    match (parse(version), parse(min_version)) {
+        (Some(mut v), Some(mut min)) => {
+            let len = v.len().max(min.len());
+            v.resize(len, 0);
+            min.resize(len, 0);
+            v < min
         }
+        _ => false,
     }
 }
 
-fn get_max_desktop_resolution() -> Option<String> {
-    // works with Xwayland
-    let output: Output = Command::new("sh")
-        .arg("-c")
-        .arg("xrandr | awk '/current/ { print $8,$9,$10 }'")
-        .output()
-        .ok()?;
+#[derive(Debug, Clone, Copy)]
+pub struct ScrapLogPolicy {
+    // Stop logging after `max_total` failures.
+    pub max_total: u32,
+    // Log one of every `every_n` failures.
+    pub every_n: u32,
+}
 
-    if output.status.success() {
-        let result = String::from_utf8_lossy(&output.stdout);
-        Some(result.trim().to_string())
-    } else {
-        None
+impl Default for ScrapLogPolicy {
+    fn default() -> Self {
+        Self {
+            max_total: 1000000,
+            every_n: 10000,
+        }
     }
 }
 
-pub(super) async fn check_init() -> ResultType<()> {
-    if !is_x11() {
-        let mut minx = 0;
-        let mut maxx = 0;
-        let mut miny = 0;
-        let mut maxy = 0;
-        let use_uinput = crate::input_service::wayland_use_uinput();
-
-        if *CAP_DISPLAY_INFO.read().unwrap() == 0 {
-            let mut lock = CAP_DISPLAY_INFO.write().unwrap();
-            if *lock == 0 {
-                let mut all = Display::all()?;
-                let num = all.len();
-                let primary = super::display_service::get_primary_2(&all);
-                let current = primary;
-                super::display_service::check_update_displays(&all);
-                let mut displays = super::display_service::get_sync_displays();
-                for display in displays.iter_mut() {
-                    display.cursor_embedded = is_cursor_embedded();
-                }
+#[derive(Default)]
+struct ScrapLogState {
+    count: u32,
+    first_failure: Option<Instant>,
+}
 
-                let mut rects: Vec<((i32, i32), usize, usize)> = Vec::new();
-                for d in &all {
-                    rects.push((d.origin(), d.width(), d.height()));
-                }
+pub fn set_scrap_log_policy(policy: ScrapLogPolicy) {
+    *SCRAP_LOG_POLICY.write().unwrap() = policy;
+}
 
-                let display = all.remove(current);
-                let (origin, width, height) = (display.origin(), display.width(), display.height());
-                log::debug!(
-                    "#displays={}, current={}, origin: {:?}, width={}, height={}, cpus={}/{}",
-                    num,
-                    current,
-                    &origin,
-                    width,
-                    height,
-                    num_cpus::get_physical(),
-                    num_cpus::get(),
-                );
+// Called on a successful frame, so that the next burst of failures is logged.
+fn reset_scrap_log() {
+    let mut state = LOG_SCRAP_COUNT.lock().unwrap();
+    if state.count > 0 {
+        *state = Default::default();
+    }
+}
 
-                if use_uinput {
-                    let (max_width, max_height) = match get_max_desktop_resolution() {
-                        Some(result) if !result.is_empty() => {
-                            let resolution: Vec<&str> = result.split(" ").collect();
-                            let w: i32 = resolution[0].parse().unwrap_or(origin.0 + width as i32);
-                            let h: i32 = resolution[2]
-                                .trim_end_matches(",")
-                                .parse()
-                                .unwrap_or(origin.1 + height as i32);
-                            if w < origin.0 + width as i32 || h < origin.1 + height as i32 {
-                                (origin.0 + width as i32, origin.1 + height as i32)
-                            } else {
-                                (w, h)
-                            }
-                        }
-                        _ => (origin.0 + width as i32, origin.1 + height as i32),
-                    };
+fn try_log(err: &String) {
+    let policy = *SCRAP_LOG_POLICY.read().unwrap();
+    let mut state = LOG_SCRAP_COUNT.lock().unwrap();
+    if state.count >= policy.max_total {
+        return;
+    }
+    let first_failure = *state.first_failure.get_or_insert_with(Instant::now);
+    if state.count % policy.every_n.max(1) == 0 {
+        log::error!(
+            "Failed scrap {}, failures: {}, elapsed since first failure: {:?}",
+            err,
+            state.count + 1,
+            first_failure.elapsed()
+        );
+    }
+    state.count += 1;
+}
 
-                    minx = 0;
-                    maxx = max_width;
-                    miny = 0;
-                    maxy = max_height;
-                }
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaptureStats {
+    pub frames: u64,
+    pub would_block: u64,
+    pub errors: u64,
+    // The frames not pulled by the decision of the adaptive skip policy, see `set_adaptive_skip()`.
+    pub skipped: u64,
+    // The average interval between two successful frames.
+    pub avg_frame_interval: Option<Duration>,
+    // The time since the last successful frame.
+    pub last_good_frame_age: Option<Duration>,
+    // A display keeps repeating the same frame, see `set_frozen_frame_threshold()`.
+    pub possibly_frozen: bool,
+    // The buffers negotiated by the last capturer created, the CPU ones if the DMA-BUFs failed.
+    pub buffer_mode: Option<scrap::wayland::pipewire::BufferMode>,
+    // The estimated memory of the cached capturers, see `set_capturer_cache_budget_bytes()`.
+    pub capturer_cache_bytes: usize,
+}
 
-                let capturer = Box::into_raw(Box::new(
-                    Capturer::new(display).with_context(|| "Failed to create capturer")?,
-                ));
-                let capturer = CapturerPtr(capturer);
-                let cap_display_info = Box::into_raw(Box::new(CapDisplayInfo {
-                    rects,
-                    displays,
-                    num,
-                    primary,
-                    current,
-                    capturer,
-                }));
-                *lock = cap_display_info as _;
-            }
-        }
+// Counters of all wayland capturers, see `capture_stats()`.
+struct CaptureCounters {
+    frames: AtomicU64,
+    would_block: AtomicU64,
+    errors: AtomicU64,
+    skipped: AtomicU64,
+    // Nanoseconds since `CAPTURE_COUNTERS_BASE`, 0 if there is no frame yet.
+    last_frame_nanos: AtomicU64,
+    interval_nanos_sum: AtomicU64,
+    interval_count: AtomicU64,
+}
 
-        if use_uinput {
-            if minx != maxx && miny != maxy {
-                log::info!(
-                    "update mouse resolution: ({}, {}), ({}, {})",
-                    minx,
-                    maxx,
-                    miny,
-                    maxy
-                );
-                allow_err!(input_service::update_mouse_resolution(minx, maxx, miny, maxy).await);
+static CAPTURE_COUNTERS: CaptureCounters = CaptureCounters {
+    frames: AtomicU64::new(0),
+    would_block: AtomicU64::new(0),
+    errors: AtomicU64::new(0),
+    skipped: AtomicU64::new(0),
+    last_frame_nanos: AtomicU64::new(0),
+    interval_nanos_sum: AtomicU64::new(0),
+    interval_count: AtomicU64::new(0),
+};
+
+impl CaptureCounters {
+    fn on_frame<T>(&self, res: &io::Result<T>) {
+        match res {
+            Ok(_) => {
+                self.frames.fetch_add(1, Ordering::Relaxed);
+                let now = (CAPTURE_COUNTERS_BASE.elapsed().as_nanos() as u64).max(1);
+                let last = self.last_frame_nanos.swap(now, Ordering::Relaxed);
+                if last != 0 && now > last {
+                    self.interval_nanos_sum
+                        .fetch_add(now - last, Ordering::Relaxed);
+                    self.interval_count.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                self.would_block.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
             }
         }
     }
-    Ok(())
 }
 
-pub(super) async fn get_displays() -> ResultType<Vec<DisplayInfo>> {
-    check_init().await?;
-    let addr = *CAP_DISPLAY_INFO.read().unwrap();
-    if addr != 0 {
-        let cap_display_info: *const CapDisplayInfo = addr as _;
-        unsafe {
-            let cap_display_info = &*cap_display_info;
-            Ok(cap_display_info.displays.clone())
-        }
-    } else {
-        bail!("Failed to get capturer display info");
+pub fn capture_stats() -> CaptureStats {
+    let c = &CAPTURE_COUNTERS;
+    let interval_count = c.interval_count.load(Ordering::Relaxed);
+    let last_frame_nanos = c.last_frame_nanos.load(Ordering::Relaxed);
+    CaptureStats {
+        frames: c.frames.load(Ordering::Relaxed),
+        would_block: c.would_block.load(Ordering::Relaxed),
+        errors: c.errors.load(Ordering::Relaxed),
+        skipped: c.skipped.load(Ordering::Relaxed),
+        avg_frame_interval: if interval_count > 0 {
+            Some(Duration::from_nanos(
+                c.interval_nanos_sum.load(Ordering::Relaxed) / interval_count,
+            ))
+        } else {
+            None
+        },
+        last_good_frame_age: last_good_frame_age(last_frame_nanos),
+        possibly_frozen: !frozen_displays(false).is_empty(),
+        buffer_mode: scrap::wayland::pipewire::get_buffer_mode(),
+        capturer_cache_bytes: get_cap_display_info()
+            .map(|info| info.capturers.lock().unwrap().usage())
+            .unwrap_or(0),
     }
 }
 
-pub(super) fn get_primary() -> ResultType<usize> {
-    let addr = *CAP_DISPLAY_INFO.read().unwrap();
-    if addr != 0 {
-        let cap_display_info: *const CapDisplayInfo = addr as _;
-        unsafe {
-            let cap_display_info = &*cap_display_info;
-            Ok(cap_display_info.primary)
-        }
+fn last_good_frame_age(last_frame_nanos: u64) -> Option<Duration> {
+    if last_frame_nanos > 0 {
+        Some(
+            CAPTURE_COUNTERS_BASE
+                .elapsed()
+                .saturating_sub(Duration::from_nanos(last_frame_nanos)),
+        )
     } else {
-        bail!("Failed to get capturer display info");
+        None
     }
 }
 
-pub fn clear() {
-    if is_x11() {
-        return;
+// What the adaptive skip policy knows about the capture, it reads the load signals itself,
+// e.g. the CPU usage or the backlog of the encoder.
+#[derive(Debug, Clone)]
+pub struct SkipContext {
+    pub idx: usize,
+    // The time since the last frame of any display, `None` if there's no frame yet.
+    pub last_frame_age: Option<Duration>,
+    // The frames skipped in a row by this capturer, so the policy can bound the skipping.
+    pub consecutive_skips: u64,
+}
+
+// Returns true to skip pulling the frame.
+pub type AdaptiveSkipPolicy = Arc<dyn Fn(&SkipContext) -> bool + Send + Sync>;
+
+// Called before pulling each frame, a skipped frame returns `WouldBlock` without touching pipewire,
+// the next pull gets the latest frame. `None`, the default, never skips.
+// The policy is called on the capture threads, it should return quickly.
+pub fn set_adaptive_skip(policy: Option<AdaptiveSkipPolicy>) {
+    log::info!("Set the wayland adaptive skip policy: {}", policy.is_some());
+    *ADAPTIVE_SKIP.write().unwrap() = policy;
+}
+
+fn should_skip_frame(idx: usize, consecutive_skips: u64) -> bool {
+    let Some(policy) = ADAPTIVE_SKIP.read().unwrap().clone() else {
+        return false;
+    };
+    policy(&SkipContext {
+        idx,
+        last_frame_age: last_good_frame_age(
+            CAPTURE_COUNTERS.last_frame_nanos.load(Ordering::Relaxed),
+        ),
+        consecutive_skips,
+    })
+}
+
+// Bounds the estimated memory of the cached capturers, 0 is unlimited.
+// The idle capturers are evicted when it's exceeded, the leased ones are never evicted.
+pub fn set_capturer_cache_budget_bytes(n: u64) {
+    CAPTURER_CACHE_BUDGET.store(n, Ordering::Relaxed);
+    log::info!("Set the wayland capturer cache budget to {} bytes", n);
+    if let Some(info) = get_cap_display_info() {
+        info.capturers.lock().unwrap().evict_over_budget(None);
     }
-    let mut write_lock = CAP_DISPLAY_INFO.write().unwrap();
-    if *write_lock != 0 {
-        let cap_display_info: *mut CapDisplayInfo = *write_lock as _;
-        unsafe {
-            let _box_capturer = Box::from_raw((*cap_display_info).capturer.0);
-            let _box_cap_display_info = Box::from_raw(cap_display_info);
-            *write_lock = 0;
-        }
+}
+
+// When the last frame of any display was captured, `None` if no frame is captured yet or since
+// `reset_capture_stats()`. It only reads an atomic, so it never blocks the capture loop.
+pub fn last_frame_instant() -> Option<Instant> {
+    match CAPTURE_COUNTERS.last_frame_nanos.load(Ordering::Relaxed) {
+        0 => None,
+        nanos => Some(*CAPTURE_COUNTERS_BASE + Duration::from_nanos(nanos)),
     }
 }
 
-pub(super) fn get_capturer() -> ResultType<super::video_service::CapturerInfo> {
-    if is_x11() {
-        bail!("Do not call this function if not wayland");
+// Hashes a few chunks spread over the frame, cheap enough for every frame.
+fn sampled_hash(data: &[u8]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    data.len().hash(&mut hasher);
+    if data.len() <= FROZEN_HASH_CHUNKS * FROZEN_HASH_CHUNK_SIZE {
+        data.hash(&mut hasher);
+    } else {
+        let step = (data.len() - FROZEN_HASH_CHUNK_SIZE) / (FROZEN_HASH_CHUNKS - 1);
+        for i in 0..FROZEN_HASH_CHUNKS {
+            let offset = i * step;
+            data[offset..offset + FROZEN_HASH_CHUNK_SIZE].hash(&mut hasher);
+        }
     }
-    let addr = *CAP_DISPLAY_INFO.read().unwrap();
-    if addr != 0 {
-        let cap_display_info: *const CapDisplayInfo = addr as _;
-        unsafe {
-            let cap_display_info = &*cap_display_info;
-            let rect = cap_display_info.rects[cap_display_info.current];
-            Ok(super::video_service::CapturerInfo {
-                origin: rect.0,
-                width: rect.1,
-                height: rect.2,
-                ndisplay: cap_display_info.num,
-                current: cap_display_info.current,
-                privacy_mode_id: 0,
-                _capturer_privacy_mode_id: 0,
-                capturer: Box::new(cap_display_info.capturer.clone()),
-            })
+    hasher.finish()
+}
+
+// `hash` is `None` if pipewire repeated the last frame, which is dropped by scrap as `WouldBlock`.
+fn update_frozen_state(idx: usize, hash: Option<u64>) {
+    let mut state = FROZEN_STATE.lock().unwrap();
+    let (last_hash, count) = state.entry(idx).or_insert((0, 0));
+    match hash {
+        Some(hash) if hash != *last_hash => {
+            *last_hash = hash;
+            *count = 0;
         }
-    } else {
-        bail!("Failed to get capturer display info");
+        _ => *count += 1,
     }
 }
 
-pub fn common_get_error() -> String {
-    if DISTRO.name.to_uppercase() == "Ubuntu".to_uppercase() {
-        if DISTRO.version_id < "21".to_owned() {
-            return "".to_owned();
+// Set how many identical frames in a row make the stream of a display considered frozen,
+// then the frame watchdog recreates its capturer. 0 disables the detection.
+pub fn set_frozen_frame_threshold(threshold: u64) {
+    FROZEN_FRAME_THRESHOLD.store(threshold, Ordering::SeqCst);
+    FROZEN_STATE.lock().unwrap().clear();
+}
+
+// The displays which have repeated the same frame for at least the threshold.
+// Their counts are reset if `take` is true, so they're reported again only after another threshold.
+fn frozen_displays(take: bool) -> Vec<usize> {
+    let threshold = FROZEN_FRAME_THRESHOLD.load(Ordering::SeqCst);
+    if threshold == 0 {
+        return vec![];
+    }
+    let mut state = FROZEN_STATE.lock().unwrap();
+    let mut frozen = Vec::new();
+    for (idx, (_, count)) in state.iter_mut() {
+        if *count >= threshold {
+            frozen.push(*idx);
+            if take {
+                *count = 0;
+            }
         }
+    }
+    frozen
+}
+
+// Log the CRC-32 of each frame, to tell whether a corrupted frame is already corrupted on capture.
+// Off by default, nothing is computed then. The checksum of the last frame is kept by `frame_checksum()`.
+pub fn set_frame_checksum(enabled: bool) {
+    FRAME_CHECKSUM_ENABLED.store(enabled, Ordering::SeqCst);
+    FRAME_CHECKSUMS.lock().unwrap().clear();
+    log::info!("Set the wayland frame checksum: {}", enabled);
+}
+
+// The chunks of `FRAME_CHECKSUM_CHUNK_SIZE` bytes spread over the frame in the checksum,
+// to bound the cost. `None` for the whole frame.
+pub fn set_frame_checksum_sampling(chunks: Option<usize>) {
+    FRAME_CHECKSUM_CHUNKS.store(chunks.map_or(0, |n| n.max(2)), Ordering::SeqCst);
+}
+
+// The checksum of the last frame of the display, `None` if disabled or before the first frame.
+// Pair it with `frame_seq()` to compare it with the frame received by the peer.
+pub fn frame_checksum(idx: usize) -> Option<u32> {
+    FRAME_CHECKSUMS.lock().unwrap().get(&idx).cloned()
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for b in data {
+        crc = CRC32_TABLE[((crc ^ *b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+// CRC-32 (IEEE) of the whole data if `chunks` is 0 or the data is small, otherwise of the sampled chunks.
+fn sampled_crc32(data: &[u8], chunks: usize) -> u32 {
+    let mut crc = !0u32;
+    if chunks == 0 || data.len() <= chunks * FRAME_CHECKSUM_CHUNK_SIZE {
+        crc = crc32_update(crc, data);
     } else {
-        // to-do: check other distros
+        let step = (data.len() - FRAME_CHECKSUM_CHUNK_SIZE) / (chunks - 1);
+        for i in 0..chunks {
+            let offset = i * step;
+            crc = crc32_update(crc, &data[offset..offset + FRAME_CHECKSUM_CHUNK_SIZE]);
+        }
+    }
+    !crc
+}
+
+fn update_frame_checksum(idx: usize, data: &[u8]) {
+    let crc = sampled_crc32(data, FRAME_CHECKSUM_CHUNKS.load(Ordering::Relaxed));
+    log::debug!(
+        "wayland frame {:?} of display {}, crc32: {:08x}",
+        frame_seq(idx),
+        idx,
+        crc
+    );
+    FRAME_CHECKSUMS.lock().unwrap().insert(idx, crc);
+}
+
+// Records when the capturer of the display started failing, for the frame watchdog.
+// `WouldBlock` is not a failure, no frame is sent if the screen is not changed.
+fn update_frame_failing<T>(idx: usize, res: &io::Result<T>) {
+    match res {
+        Ok(_) => {
+            FRAME_FAILING_SINCE.lock().unwrap().remove(&idx);
+        }
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+        Err(_) => {
+            FRAME_FAILING_SINCE
+                .lock()
+                .unwrap()
+                .entry(idx)
+                .or_insert_with(Instant::now);
+        }
+    }
+}
+
+// Takes the displays which have been failing for at least `threshold`.
+fn take_failing_displays(threshold: Duration) -> Vec<usize> {
+    let mut failing = FRAME_FAILING_SINCE.lock().unwrap();
+    let expired: Vec<usize> = failing
+        .iter()
+        .filter(|(_, since)| since.elapsed() >= threshold)
+        .map(|(idx, _)| *idx)
+        .collect();
+    for idx in expired.iter() {
+        failing.remove(idx);
+    }
+    expired
+}
+
+// Set how long the frames of a display can keep failing before its capturer is recreated.
+pub fn set_frame_watchdog_threshold(threshold: Duration) {
+    *FRAME_WATCHDOG_THRESHOLD.write().unwrap() = threshold;
+}
+
+#[inline]
+fn get_frame_watchdog_threshold() -> Duration {
+    *FRAME_WATCHDOG_THRESHOLD.read().unwrap()
+}
+
+// Recreates the capturers which keep failing, e.g. pipewire is restarted in the session.
+// The watchdog stops itself on X11, and is started again by `check_init()`.
+#[cfg_attr(test, allow(dead_code))]
+fn start_frame_watchdog() {
+    if FRAME_WATCHDOG_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    std::thread::spawn(|| loop {
+        std::thread::sleep(FRAME_WATCHDOG_INTERVAL);
+        if is_x11() {
+            log::info!("Stop the wayland frame watchdog, the session is X11");
+            FRAME_WATCHDOG_RUNNING.store(false, Ordering::SeqCst);
+            break;
+        }
+        // Nothing to watch without a capture.
+        if !is_initialized() {
+            continue;
+        }
+        // Some compositors stop the streams while the screen is locked, it's not a frozen stream.
+        if update_screen_locked() {
+            continue;
+        }
+        for idx in frozen_displays(true) {
+            log::warn!(
+                "The stream of wayland display {} may be frozen, recreate the capturer",
+                idx
+            );
+            if let Err(e) = force_reinit(idx) {
+                log::error!(
+                    "Failed to recreate the wayland capturer of display {}, {}",
+                    idx,
+                    e
+                );
+            }
+        }
+        for idx in take_failing_displays(get_frame_watchdog_threshold()) {
+            log::warn!(
+                "The frames of wayland display {} keep failing, recreate the capturer",
+                idx
+            );
+            if let Err(e) = force_reinit(idx) {
+                log::error!(
+                    "Failed to recreate the wayland capturer of display {}, {}",
+                    idx,
+                    e
+                );
+            }
+        }
+    });
+}
+
+// Returns whether the screen is locked, refreshed at most every `SCREEN_LOCK_REFRESH_INTERVAL`.
+fn update_screen_locked() -> bool {
+    {
+        let mut checked_at = SCREEN_LOCK_CHECKED_AT.lock().unwrap();
+        if checked_at.map_or(false, |t| t.elapsed() < SCREEN_LOCK_REFRESH_INTERVAL) {
+            return SCREEN_LOCKED.load(Ordering::SeqCst);
+        }
+        *checked_at = Some(Instant::now());
+    }
+    let sid = {
+        let mut sid = SEAT0_SESSION.lock().unwrap();
+        if sid.as_ref().map_or(true, |s| s.is_empty()) {
+            *sid = Some(hbb_common::platform::linux::get_values_of_seat0(&[0])[0].clone());
+        }
+        sid.clone().unwrap_or_default()
+    };
+    let locked = !sid.is_empty() && hbb_common::platform::linux::is_locked(&sid);
+    if SCREEN_LOCKED.swap(locked, Ordering::SeqCst) != locked {
+        log::info!(
+            "The screen is {}",
+            if locked { "locked" } else { "unlocked" }
+        );
+        // The frames are not expected while locked.
+        FRAME_FAILING_SINCE.lock().unwrap().clear();
+        FROZEN_STATE.lock().unwrap().clear();
+    }
+    locked
+}
+
+// Whether the session is locked, known from logind, updated every `FRAME_WATCHDOG_INTERVAL`.
+// The client may show an overlay instead of the last frame.
+pub fn is_screen_locked() -> bool {
+    SCREEN_LOCKED.load(Ordering::SeqCst)
+}
+
+// Off by default, `frame()` returns `WaylandCaptureError::ScreenLocked` while the screen is locked.
+// If on, the frames are captured as long as the compositor keeps the portal streams running,
+// the restored portal session is kept across the lock. GNOME and KDE show the lock screen in the
+// streams, some compositors stop the streams, the capture resumes after unlocking then.
+pub fn set_capture_while_locked(capture: bool) {
+    CAPTURE_WHILE_LOCKED.store(capture, Ordering::SeqCst);
+}
+
+pub fn reset_capture_stats() {
+    let c = &CAPTURE_COUNTERS;
+    c.frames.store(0, Ordering::Relaxed);
+    c.would_block.store(0, Ordering::Relaxed);
+    c.errors.store(0, Ordering::Relaxed);
+    c.skipped.store(0, Ordering::Relaxed);
+    c.last_frame_nanos.store(0, Ordering::Relaxed);
+    c.interval_nanos_sum.store(0, Ordering::Relaxed);
+    c.interval_count.store(0, Ordering::Relaxed);
+}
+
+// The format negotiated with pipewire, known after the first frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameFormat {
+    pub pixfmt: scrap::Pixfmt,
+    pub width: usize,
+    pub height: usize,
+    pub stride: usize,
+    // `ColorInfo::default()` if the stream does not report it.
+    pub color: scrap::ColorInfo,
+}
+
+impl FrameFormat {
+    // The DRM fourcc code.
+    pub fn fourcc(&self) -> &'static str {
+        match self.pixfmt {
+            scrap::Pixfmt::BGRA => "AR24",
+            scrap::Pixfmt::RGBA => "AB24",
+            scrap::Pixfmt::RGB565LE => "RG16",
+            scrap::Pixfmt::I420 => "YU12",
+            scrap::Pixfmt::NV12 => "NV12",
+            scrap::Pixfmt::I444 => "YU24",
+        }
+    }
+
+    fn of(pixelbuffer: &PixelBuffer) -> Self {
+        Self {
+            pixfmt: pixelbuffer.pixfmt(),
+            width: pixelbuffer.width(),
+            height: pixelbuffer.height(),
+            stride: pixelbuffer.stride().first().cloned().unwrap_or_default(),
+            color: pixelbuffer.color().unwrap_or_else(|| {
+                static LOG_COLOR_ONCE: std::sync::Once = std::sync::Once::new();
+                LOG_COLOR_ONCE.call_once(|| {
+                    log::info!(
+                        "The wayland stream does not report the color space, assume {:?}",
+                        scrap::ColorInfo::default()
+                    );
+                });
+                scrap::ColorInfo::default()
+            }),
+        }
+    }
+}
+
+// The pixels of a frame with its layout. The rows may be padded by pipewire, never assume
+// `stride == width * bytes_per_pixel`, read the rows with `row()`.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameView<'a> {
+    pub data: &'a [u8],
+    // The bytes of a row, of the first plane for the planar formats.
+    pub stride: usize,
+    pub width: usize,
+    pub height: usize,
+    pub pixfmt: scrap::Pixfmt,
+}
+
+impl<'a> FrameView<'a> {
+    // Fails if the data is shorter than the rows, instead of reading the wrong bytes.
+    pub fn of(pixelbuffer: &PixelBuffer<'a>) -> io::Result<Self> {
+        let view = Self {
+            data: pixelbuffer.data(),
+            stride: pixelbuffer.stride().first().cloned().unwrap_or_default(),
+            width: pixelbuffer.width(),
+            height: pixelbuffer.height(),
+            pixfmt: pixelbuffer.pixfmt(),
+        };
+        view.check()?;
+        Ok(view)
+    }
+
+    // The bytes of the pixels in a row, without the padding.
+    pub fn row_bytes(&self) -> usize {
+        match self.pixfmt {
+            scrap::Pixfmt::I420 | scrap::Pixfmt::NV12 | scrap::Pixfmt::I444 => self.width,
+            _ => self.width * self.pixfmt.bytes_per_pixel(),
+        }
+    }
+
+    // The pixels of the row `y`, without the padding.
+    pub fn row(&self, y: usize) -> &'a [u8] {
+        let i = y * self.stride;
+        &self.data[i..i + self.row_bytes()]
+    }
+
+    fn check(&self) -> io::Result<()> {
+        if self.stride < self.row_bytes() || self.data.len() < self.stride * self.height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid frame layout, data: {}, stride: {}, {}x{} {:?}",
+                    self.data.len(),
+                    self.stride,
+                    self.width,
+                    self.height,
+                    self.pixfmt
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Copies the frames into a buffer owned by the caller, which is reused across the frames,
+// instead of copying the borrowed `Frame` into a new buffer each time.
+pub trait FrameInto {
+    // `buf` is resized to the frame data, its capacity is kept if the frame size doesn't change.
+    fn frame_into(&mut self, buf: &mut Vec<u8>, timeout: Duration) -> io::Result<FrameFormat>;
+}
+
+impl<T: TraitCapturer + ?Sized> FrameInto for T {
+    fn frame_into(&mut self, buf: &mut Vec<u8>, timeout: Duration) -> io::Result<FrameFormat> {
+        match self.frame(timeout)? {
+            Frame::PixelBuffer(pixelbuffer) => {
+                buf.clear();
+                buf.extend_from_slice(pixelbuffer.data());
+                Ok(FrameFormat::of(&pixelbuffer))
+            }
+            Frame::Texture(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Texture frames can't be copied",
+            )),
+        }
+    }
+}
+
+fn update_frame_format(idx: usize, format: FrameFormat) {
+    let mut formats = FRAME_FORMATS.lock().unwrap();
+    let old = formats.insert(idx, format);
+    if old != Some(format) {
+        log::info!(
+            "wayland frame format of display {}: {:?} -> {:?}",
+            idx,
+            old,
+            format
+        );
+    }
+}
+
+// `None` before the first frame of the display is captured.
+pub fn frame_format(idx: usize) -> Option<FrameFormat> {
+    FRAME_FORMATS.lock().unwrap().get(&idx).cloned()
+}
+
+// Whether the stream of the display is HDR, known after the first frame, false for the SDR outputs.
+// The frames are still 8-bit RGB, the compositor tone-maps them, the encoder may tag the transfer.
+pub fn is_hdr_source(idx: usize) -> bool {
+    frame_format(idx).map_or(false, |f| f.color.is_hdr())
+}
+
+// The static HDR metadata of the stream of the display, `None` for SDR or if the stream does not carry it.
+pub fn hdr_metadata(idx: usize) -> Option<scrap::HdrMetadata> {
+    frame_format(idx)?.color.hdr
+}
+
+// `pts` is the presentation timestamp of pipewire, the capture time is used if it's not available.
+// The timestamps of a display are strictly increasing until its capturer is recreated.
+fn update_frame_timestamp(idx: usize, pts: Option<u64>) -> u64 {
+    let ts = pts.unwrap_or_else(|| CAPTURE_COUNTERS_BASE.elapsed().as_nanos() as u64);
+    let mut timestamps = FRAME_TIMESTAMPS.lock().unwrap();
+    let ts = match timestamps.get(&idx) {
+        Some(last) if ts <= *last => *last + 1,
+        _ => ts,
+    };
+    timestamps.insert(idx, ts);
+    ts
+}
+
+// The timestamp of the last frame of the display in nanoseconds, `None` before the first frame.
+// Only the differences between the timestamps of a display are meaningful.
+pub fn frame_timestamp(idx: usize) -> Option<u64> {
+    FRAME_TIMESTAMPS.lock().unwrap().get(&idx).cloned()
+}
+
+fn next_frame_seq(idx: usize) -> u64 {
+    let mut seqs = FRAME_SEQS.lock().unwrap();
+    let seq = seqs.entry(idx).or_insert(0);
+    *seq += 1;
+    *seq
+}
+
+// The sequence number of the last frame of the display, starting from 1, `None` before the first frame.
+// It's increased by each successful capture and reset when the capturer is recreated.
+// A gap between the frames seen by a consumer means the frames in between were taken by others.
+pub fn frame_seq(idx: usize) -> Option<u64> {
+    FRAME_SEQS.lock().unwrap().get(&idx).cloned()
+}
+
+// Only wayland capturers are stored in `CAP_DISPLAY_INFO`.
+// The pipewire recorder can be moved to the video service thread.
+// Boxed as a trait object, so the tests can store mock capturers.
+struct SendCapturer(Box<dyn TraitCapturer>);
+
+unsafe impl Send for SendCapturer {}
+
+// The wayland capturers always deliver BGRA/RGBA pixel buffers.
+// The conversion to YUV is done by `Frame::to()` with the format required by the encoder,
+// so there's no yuv switch on the capturers.
+//
+// The capturer is taken out of the slot by `CapturerLease`, and put back when the lease is dropped.
+// So `clear()` never drops a capturer which is still capturing.
+type CapturerSlot = Arc<Mutex<Option<SendCapturer>>>;
+
+struct CapturerLease {
+    slot: CapturerSlot,
+    capturer: Option<SendCapturer>,
+    frame_timeout: Option<Duration>,
+    // The display index, for `frame_format()`.
+    idx: usize,
+    // Whether the capturer is paused by this lease, synced with `CAPTURE_PAUSED` on each frame.
+    paused: bool,
+    // Whether the frame notify is set by this lease, it's removed before the capturer is returned.
+    notified: bool,
+    // The frames skipped in a row, see `set_adaptive_skip()`.
+    skips: u64,
+}
+
+impl CapturerLease {
+    fn take(slot: &CapturerSlot, idx: usize) -> ResultType<Self> {
+        let Some(capturer) = slot.lock().unwrap().take() else {
+            bail!("The capturer is in use");
+        };
+        Ok(Self {
+            slot: slot.clone(),
+            capturer: Some(capturer),
+            frame_timeout: get_frame_timeout(),
+            idx,
+            paused: false,
+            notified: false,
+            skips: 0,
+        })
+    }
+}
+
+impl CapturerLease {
+    // Checks the capturer before capturing, and syncs it with `CAPTURE_PAUSED`.
+    fn ready(&mut self) -> io::Result<&mut SendCapturer> {
+        // The slot is removed from the cache by `clear()` or `force_reinit()`,
+        // discard the frames of the old capturer, the caller should get a new capturer.
+        if Arc::strong_count(&self.slot) == 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "The wayland capturer is stale",
+            ));
+        }
+        let Some(capturer) = self.capturer.as_mut() else {
+            return Err(io::ErrorKind::NotFound.into());
+        };
+        let paused = CAPTURE_PAUSED.load(Ordering::SeqCst);
+        if paused != self.paused {
+            if let Err(e) = capturer.0.set_paused(paused) {
+                log::warn!(
+                    "Failed to set the wayland capturer of display {} paused: {}, {}",
+                    self.idx,
+                    paused,
+                    e
+                );
+            }
+            self.paused = paused;
+        }
+        // The counters and the frame watchdog are not updated, pausing is not a failure.
+        if paused {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                WaylandCaptureError::Paused,
+            ));
+        }
+        if is_screen_locked() && !CAPTURE_WHILE_LOCKED.load(Ordering::SeqCst) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                WaylandCaptureError::ScreenLocked,
+            ));
+        }
+        // Skipping is not a failure either, it's counted as `CaptureStats::skipped`.
+        if should_skip_frame(self.idx, self.skips) {
+            self.skips += 1;
+            CAPTURE_COUNTERS.skipped.fetch_add(1, Ordering::Relaxed);
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        self.skips = 0;
+        Ok(capturer)
+    }
+}
+
+// The encoders read the rows by the stride, a frame shorter than its rows would be read out of bounds.
+fn check_frame_layout(frame: Frame<'_>) -> io::Result<Frame<'_>> {
+    if let Frame::PixelBuffer(f) = &frame {
+        FrameView::of(f)?;
+    }
+    Ok(frame)
+}
+
+impl TraitCapturer for CapturerLease {
+    fn frame<'a>(&'a mut self, timeout: Duration) -> io::Result<Frame<'a>> {
+        let timeout = self.frame_timeout.unwrap_or(timeout);
+        let idx = self.idx;
+        let res = self.ready()?.0.frame(timeout).and_then(check_frame_layout);
+        CAPTURE_COUNTERS.on_frame(&res);
+        update_frame_failing(idx, &res);
+        if res.is_ok() {
+            reset_scrap_log();
+            next_frame_seq(idx);
+            CAPTURING.store(true, Ordering::Relaxed);
+        }
+        if let Ok(Frame::PixelBuffer(f)) = &res {
+            update_frame_format(idx, FrameFormat::of(f));
+            update_frame_timestamp(idx, f.timestamp_ns());
+            if FRAME_CHECKSUM_ENABLED.load(Ordering::Relaxed) {
+                update_frame_checksum(idx, f.data());
+            }
+            #[cfg(feature = "wayland-shm")]
+            if super::wayland_shm::is_enabled() {
+                if let Ok(view) = FrameView::of(f) {
+                    super::wayland_shm::publish(idx, &view, f.timestamp_ns());
+                }
+            }
+        }
+        if FROZEN_FRAME_THRESHOLD.load(Ordering::Relaxed) > 0 {
+            match &res {
+                Ok(Frame::PixelBuffer(f)) => update_frozen_state(idx, Some(sampled_hash(f.data()))),
+                // Not a timeout, the damage-driven stream of a static screen only times out.
+                Err(e) if scrap::is_repeated_frame(e) => update_frozen_state(idx, None),
+                _ => {}
+            }
+        }
+        res
+    }
+
+    // The DMA-BUF frames are not copied, so `frame_format()` is only updated by the CPU frames.
+    #[cfg(feature = "dmabuf")]
+    fn frame_dmabuf<'a>(&'a mut self, timeout: Duration) -> io::Result<scrap::DmaBufOrFrame<'a>> {
+        let timeout = self.frame_timeout.unwrap_or(timeout);
+        let idx = self.idx;
+        let res = self.ready()?.0.frame_dmabuf(timeout).and_then(|f| match f {
+            scrap::DmaBufOrFrame::Frame(f) => {
+                check_frame_layout(f).map(scrap::DmaBufOrFrame::Frame)
+            }
+            f => Ok(f),
+        });
+        CAPTURE_COUNTERS.on_frame(&res);
+        update_frame_failing(idx, &res);
+        if res.is_ok() {
+            reset_scrap_log();
+            next_frame_seq(idx);
+            CAPTURING.store(true, Ordering::Relaxed);
+        }
+        match &res {
+            Ok(scrap::DmaBufOrFrame::Frame(Frame::PixelBuffer(f))) => {
+                update_frame_format(idx, FrameFormat::of(f));
+                update_frame_timestamp(idx, f.timestamp_ns());
+                if FRAME_CHECKSUM_ENABLED.load(Ordering::Relaxed) {
+                    update_frame_checksum(idx, f.data());
+                }
+                #[cfg(feature = "wayland-shm")]
+                if super::wayland_shm::is_enabled() {
+                    if let Ok(view) = FrameView::of(f) {
+                        super::wayland_shm::publish(idx, &view, f.timestamp_ns());
+                    }
+                }
+            }
+            Ok(scrap::DmaBufOrFrame::DmaBuf(f)) => {
+                update_frame_timestamp(idx, f.timestamp_ns);
+            }
+            _ => {}
+        }
+        res
+    }
+
+    fn set_frame_notify(&mut self, notify: Option<scrap::FrameNotify>) -> io::Result<bool> {
+        let Some(capturer) = self.capturer.as_mut() else {
+            return Err(io::ErrorKind::NotFound.into());
+        };
+        self.notified = notify.is_some();
+        capturer.0.set_frame_notify(notify)
+    }
+}
+
+impl Drop for CapturerLease {
+    fn drop(&mut self) {
+        if let Some(mut capturer) = self.capturer.take() {
+            if self.notified {
+                let _ = capturer.0.set_frame_notify(None);
+            }
+            *self.slot.lock().unwrap() = Some(capturer);
+        }
+    }
+}
+
+// Crops the frames of a display to a region.
+struct RegionCapturer {
+    inner: CapturerLease,
+    // x, y relative to the display origin, width, height
+    region: (usize, usize, usize, usize),
+    buffer: Vec<u8>,
+}
+
+impl TraitCapturer for RegionCapturer {
+    fn frame<'a>(&'a mut self, timeout: Duration) -> io::Result<Frame<'a>> {
+        let Self {
+            inner,
+            region,
+            buffer,
+        } = self;
+        let (x, y, w, h) = *region;
+        let pixfmt = match inner.frame(timeout)? {
+            Frame::PixelBuffer(pixelbuffer) => {
+                let view = FrameView::of(&pixelbuffer)?;
+                let bpp = view.pixfmt.bytes_per_pixel();
+                if x + w > view.width || y + h > view.height {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Region {:?} is out of the frame {}x{}",
+                            region, view.width, view.height
+                        ),
+                    ));
+                }
+                buffer.clear();
+                buffer.reserve(w * h * bpp);
+                for row in y..(y + h) {
+                    buffer.extend_from_slice(&view.row(row)[x * bpp..(x + w) * bpp]);
+                }
+                view.pixfmt
+            }
+            Frame::Texture(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Region capture does not support texture frames",
+                ));
+            }
+        };
+        Ok(Frame::PixelBuffer(PixelBuffer::new(buffer, pixfmt, w, h)))
+    }
+}
+
+// Only wayland displays are stored in `CapturerCache`.
+struct SendDisplay(Display);
+
+unsafe impl Send for SendDisplay {}
+
+// Capturers are created lazily by display index, and kept for switching back.
+// `Display::all()` reuses the portal session, so creating a capturer does not prompt again.
+#[derive(Default)]
+struct CapturerCache {
+    slots: HashMap<usize, CapturerSlot>,
+    // Display indices, the most recently used one is at the back.
+    lru: VecDeque<usize>,
+    // The displays enumerated on init, to create the capturers without enumerating again.
+    // Rebuilt on the next init after `clear()`, e.g. when the displays are changed.
+    displays: Vec<SendDisplay>,
+    // The estimated memory of each cached capturer, see `estimated_capturer_bytes()`.
+    retained_bytes: HashMap<usize, usize>,
+    // The capturers of the `CaptureSession`s by (session id, display index).
+    // Each is another stream of the display, so the sessions do not take the capturers of the others.
+    session_slots: HashMap<(u64, usize), CapturerSlot>,
+}
+
+fn estimated_capturer_bytes(width: usize, height: usize) -> usize {
+    let frames = match scrap::wayland::pipewire::get_buffer_count() {
+        0 => DEFAULT_RETAINED_FRAMES,
+        n => n as usize + 1,
+    };
+    width * height * 4 * frames
+}
+
+impl CapturerCache {
+    fn insert(
+        &mut self,
+        idx: usize,
+        capturer: Box<dyn TraitCapturer>,
+        bytes: usize,
+    ) -> CapturerSlot {
+        let slot = Arc::new(Mutex::new(Some(SendCapturer(capturer))));
+        self.slots.insert(idx, slot.clone());
+        self.retained_bytes.insert(idx, bytes);
+        self.touch(idx);
+        self.evict_over_count(idx);
+        self.evict_over_budget(Some(idx));
+        slot
+    }
+
+    // A leased capturer is dropped when its lease is dropped, it's not put back to the cache.
+    fn remove(&mut self, idx: usize) {
+        self.slots.remove(&idx);
+        self.retained_bytes.remove(&idx);
+        self.lru.retain(|i| *i != idx);
+    }
+
+    // Evicts the least recently used idle capturers until at most `MAX_CACHED_CAPTURERS` are cached.
+    // The leased capturers and `keep` are never evicted, the streams in use are not broken.
+    fn evict_over_count(&mut self, keep: usize) {
+        let mut count = self.lru.len();
+        let candidates: Vec<usize> = self.lru.iter().cloned().collect();
+        for idx in candidates {
+            if count <= MAX_CACHED_CAPTURERS {
+                break;
+            }
+            if idx == keep || !self.is_idle(idx) {
+                continue;
+            }
+            log::info!("Evict the wayland capturer of display {}", idx);
+            self.remove(idx);
+            count -= 1;
+        }
+    }
+
+    // The slot is empty while its capturer is leased.
+    fn is_idle(&self, idx: usize) -> bool {
+        self.slots
+            .get(&idx)
+            .map_or(false, |slot| slot.lock().unwrap().is_some())
+    }
+
+    fn usage(&self) -> usize {
+        self.retained_bytes.values().sum()
+    }
+
+    // Evicts the least recently used idle capturers until the usage is within the budget.
+    // The leased capturers, e.g. the streaming ones, and `keep` are never evicted,
+    // so the usage may still exceed the budget.
+    fn evict_over_budget(&mut self, keep: Option<usize>) {
+        let budget = CAPTURER_CACHE_BUDGET.load(Ordering::Relaxed) as usize;
+        if budget == 0 {
+            return;
+        }
+        let mut usage = self.usage();
+        let candidates: Vec<usize> = self.lru.iter().cloned().collect();
+        for idx in candidates {
+            if usage <= budget {
+                break;
+            }
+            if Some(idx) == keep {
+                continue;
+            }
+            if !self.is_idle(idx) {
+                continue;
+            }
+            log::info!(
+                "Evict the idle wayland capturer of display {}, the cache uses {} bytes, budget {}",
+                idx,
+                usage,
+                budget
+            );
+            usage -= self.retained_bytes.get(&idx).cloned().unwrap_or(0);
+            self.remove(idx);
+        }
+    }
+
+    fn get(&mut self, idx: usize) -> Option<CapturerSlot> {
+        let slot = self.slots.get(&idx).cloned()?;
+        self.touch(idx);
+        Some(slot)
+    }
+
+    fn touch(&mut self, idx: usize) {
+        self.lru.retain(|i| *i != idx);
+        self.lru.push_back(idx);
+    }
+
+    fn get_or_create(&mut self, idx: usize) -> ResultType<CapturerSlot> {
+        if let Some(slot) = self.get(idx) {
+            return Ok(slot);
+        }
+        let capturer = self.new_capturer(idx)?;
+        let bytes = estimated_capturer_bytes(capturer.width(), capturer.height());
+        Ok(self.insert(idx, Box::new(capturer), bytes))
+    }
+
+    fn get_or_create_for_session(
+        &mut self,
+        session: u64,
+        idx: usize,
+        rect: &DisplayRect,
+    ) -> ResultType<CapturerSlot> {
+        if let Some(slot) = self.session_slots.get(&(session, idx)) {
+            return Ok(slot.clone());
+        }
+        let capturer = new_session_capturer(self, idx, rect)?;
+        let slot = Arc::new(Mutex::new(Some(SendCapturer(capturer))));
+        self.session_slots.insert((session, idx), slot.clone());
+        Ok(slot)
+    }
+
+    // The capturers in use are dropped when their leases are dropped.
+    fn remove_session(&mut self, session: u64) {
+        self.session_slots.retain(|(s, _), _| *s != session);
+    }
+
+    fn new_capturer(&self, idx: usize) -> ResultType<Capturer> {
+        let display = match self.displays.get(idx).and_then(|d| d.0.try_clone()) {
+            Some(display) => display,
+            None => {
+                let mut all =
+                    get_all_displays_with_retry().context(WaylandError::DisplayEnumFailed)?;
+                if idx >= all.len() {
+                    bail!(WaylandError::IndexOutOfRange {
+                        idx,
+                        len: all.len()
+                    });
+                }
+                all.remove(idx)
+            }
+        };
+        Capturer::new(display).context(WaylandError::CapturerCreateFailed)
+    }
+}
+
+// Another stream of the display for a `CaptureSession`, pipewire lets many consumers connect to a node.
+#[cfg(not(test))]
+fn new_session_capturer(
+    cache: &CapturerCache,
+    idx: usize,
+    _rect: &DisplayRect,
+) -> ResultType<Box<dyn TraitCapturer>> {
+    Ok(Box::new(cache.new_capturer(idx)?))
+}
+
+#[cfg(test)]
+fn new_session_capturer(
+    _cache: &CapturerCache,
+    _idx: usize,
+    rect: &DisplayRect,
+) -> ResultType<Box<dyn TraitCapturer>> {
+    Ok(Box::new(tests::MockCapturer::new(rect.width, rect.height)))
+}
+
+struct CapDisplayInfo {
+    rects: Vec<((i32, i32), usize, usize)>,
+    displays: Vec<DisplayInfo>,
+    num: usize,
+    primary: usize,
+    // Changed by `set_current_display()`.
+    current: AtomicUsize,
+    capturers: Mutex<CapturerCache>,
+    // (minx, maxx, miny, maxy) of the mouse, computed on init.
+    uinput_resolution: (i32, i32, i32, i32),
+    // False if only the virtual monitors are captured.
+    has_physical_display: bool,
+}
+
+// The rect of a display in the compositor's layout, see `CapDisplayInfo::rect_for()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayRect {
+    pub origin: (i32, i32),
+    pub width: usize,
+    pub height: usize,
+}
+
+impl From<((i32, i32), usize, usize)> for DisplayRect {
+    fn from((origin, width, height): ((i32, i32), usize, usize)) -> Self {
+        Self {
+            origin,
+            width,
+            height,
+        }
+    }
+}
+
+impl CapDisplayInfo {
+    // `None` if the index is out of the rects.
+    #[inline]
+    fn rect_for(&self, idx: usize) -> Option<DisplayRect> {
+        self.rects.get(idx).map(|r| DisplayRect::from(*r))
+    }
+
+    // The indices are used to index `rects`, they must be consistent.
+    fn validate(&self) -> ResultType<()> {
+        if self.rects.len() != self.num {
+            bail!(
+                "Inconsistent displays, rects len: {}, displays len: {}",
+                self.rects.len(),
+                self.num
+            );
+        }
+        if self.primary >= self.num || self.current() >= self.num {
+            bail!(
+                "Invalid primary {} or current {} display, displays len: {}",
+                self.primary,
+                self.current(),
+                self.num
+            );
+        }
+        Ok(())
+    }
+
+    // Rebuilds the rects from the displays sent to the peer, so the indices of the peer are kept.
+    // The current and primary displays fall back to the first one if they're not in the displays.
+    fn rebuild_rects(&mut self) {
+        log::warn!(
+            "Rebuild the wayland display rects, rects len: {}, displays len: {}, primary: {}, current: {}",
+            self.rects.len(),
+            self.displays.len(),
+            self.primary,
+            self.current()
+        );
+        self.rects = self
+            .displays
+            .iter()
+            .map(|d| ((d.x, d.y), d.width as usize, d.height as usize))
+            .collect();
+        self.num = self.displays.len();
+        if self.primary >= self.num {
+            self.primary = 0;
+        }
+        if self.current() >= self.num {
+            *self.current.get_mut() = 0;
+        }
+    }
+
+    #[inline]
+    fn current(&self) -> usize {
+        self.current.load(Ordering::SeqCst)
+    }
+}
+
+// Set `is_primary` and `is_current`, so the peer doesn't need to check the indexes.
+#[inline]
+fn set_display_flags(displays: &mut [DisplayInfo], primary: usize, current: usize) {
+    for (idx, display) in displays.iter_mut().enumerate() {
+        display.is_primary = idx == primary;
+        display.is_current = idx == current;
+    }
+}
+
+// The preferred resolution and the physical size in millimeters, parsed from an EDID.
+fn parse_edid_physical_size(edid: &[u8]) -> Option<((usize, usize), (u32, u32))> {
+    const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+    if edid.len() < 128 || edid[..8] != HEADER {
+        return None;
+    }
+    // The first detailed timing descriptor is the preferred mode, its pixel clock is not 0.
+    let dtd = &edid[54..72];
+    if dtd[0] == 0 && dtd[1] == 0 {
+        return None;
+    }
+    let width = dtd[2] as usize | ((dtd[4] as usize & 0xf0) << 4);
+    let height = dtd[5] as usize | ((dtd[7] as usize & 0xf0) << 4);
+    let mut width_mm = dtd[12] as u32 | ((dtd[14] as u32 & 0xf0) << 4);
+    let mut height_mm = dtd[13] as u32 | ((dtd[14] as u32 & 0x0f) << 8);
+    if width_mm == 0 || height_mm == 0 {
+        // The screen size in centimeters.
+        width_mm = edid[21] as u32 * 10;
+        height_mm = edid[22] as u32 * 10;
+    }
+    if width == 0 || height == 0 || width_mm == 0 || height_mm == 0 {
+        return None;
+    }
+    Some(((width, height), (width_mm, height_mm)))
+}
+
+// The portal does not report the connectors, so the EDIDs of the connected outputs are matched by the resolution.
+fn get_drm_physical_sizes() -> Vec<((usize, usize), (u32, u32))> {
+    let Ok(dir) = std::fs::read_dir("/sys/class/drm") else {
+        return vec![];
+    };
+    dir.filter_map(|entry| {
+        let path = entry.ok()?.path();
+        let status = std::fs::read_to_string(path.join("status")).ok()?;
+        if status.trim() != "connected" {
+            return None;
+        }
+        parse_edid_physical_size(&std::fs::read(path.join("edid")).ok()?)
+    })
+    .collect()
+}
+
+// The size is left 0 if no EDID or more than one EDID with different sizes matches the display.
+fn set_physical_sizes(displays: &mut [DisplayInfo], edids: &[((usize, usize), (u32, u32))]) {
+    for display in displays.iter_mut() {
+        let (width, height) = (display.width as usize, display.height as usize);
+        let mut sizes = edids.iter().filter_map(|(res, (w_mm, h_mm))| {
+            if *res == (width, height) {
+                Some((*w_mm, *h_mm))
+            } else if *res == (height, width) {
+                // Rotated
+                Some((*h_mm, *w_mm))
+            } else {
+                None
+            }
+        });
+        let Some(size) = sizes.next() else {
+            continue;
+        };
+        if sizes.all(|s| s == size) {
+            display.physical_width_mm = size.0;
+            display.physical_height_mm = size.1;
+        }
+    }
+}
+
+// The lock is only poisoned by a panic while it's held, e.g. in `check_init()`.
+// The info is replaced as a whole, so it's still consistent, recover it instead of panicking in every caller.
+fn read_cap_display_info() -> std::sync::RwLockReadGuard<'static, Option<Arc<CapDisplayInfo>>> {
+    CAP_DISPLAY_INFO.read().unwrap_or_else(|e| {
+        log_cap_display_info_poisoned();
+        e.into_inner()
+    })
+}
+
+fn write_cap_display_info() -> std::sync::RwLockWriteGuard<'static, Option<Arc<CapDisplayInfo>>> {
+    CAP_DISPLAY_INFO.write().unwrap_or_else(|e| {
+        log_cap_display_info_poisoned();
+        e.into_inner()
+    })
+}
+
+fn log_cap_display_info_poisoned() {
+    static LOG_POISONED_ONCE: std::sync::Once = std::sync::Once::new();
+    LOG_POISONED_ONCE.call_once(|| {
+        log::error!("The wayland capture info lock is poisoned by a panic, recovered");
+    });
+}
+
+fn get_cap_display_info() -> Option<Arc<CapDisplayInfo>> {
+    read_cap_display_info().clone()
+}
+
+fn get_display_scale(cap_display_info: &CapDisplayInfo, idx: usize) -> f64 {
+    match cap_display_info.displays.get(idx) {
+        Some(d) if d.scale > 0.0 => d.scale,
+        _ => 1.0,
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub(super) async fn ensure_inited() -> ResultType<()> {
+    check_init().await
+}
+
+pub(super) fn is_inited() -> Option<Message> {
+    if is_x11() {
+        None
+    } else {
+        if read_cap_display_info().is_none() {
+            let mut msg_out = Message::new();
+            let res = MessageBox {
+                msgtype: "nook-nocancel-hasclose".to_owned(),
+                title: "Wayland".to_owned(),
+                text: "Please Select the screen to be shared(Operate on the peer side).".to_owned(),
+                link: "".to_owned(),
+                ..Default::default()
+            };
+            msg_out.set_message_box(res);
+            Some(msg_out)
+        } else {
+            None
+        }
+    }
+}
+
+fn get_max_desktop_resolution() -> Option<String> {
+    // works with Xwayland
+    let output: Output = Command::new("sh")
+        .arg("-c")
+        .arg("xrandr | awk '/current/ { print $8,$9,$10 }'")
+        .output()
+        .ok()?;
+
+    if output.status.success() {
+        let result = String::from_utf8_lossy(&output.stdout);
+        Some(result.trim().to_string())
+    } else {
+        None
+    }
+}
+
+// The union of all the display rects, so uinput spans the whole desktop.
+// The origins may be negative for the displays on the left of or above the primary one.
+// The desktop size of xrandr only extends the bounds, it starts from the top-left display.
+fn get_uinput_resolution(
+    rects: &[((i32, i32), usize, usize)],
+    max_desktop_resolution: Option<String>,
+) -> (i32, i32, i32, i32) {
+    let (mut minx, mut maxx, mut miny, mut maxy) = (0, 0, 0, 0);
+    for ((x, y), w, h) in rects.iter().cloned() {
+        minx = minx.min(x);
+        miny = miny.min(y);
+        maxx = maxx.max(x + w as i32);
+        maxy = maxy.max(y + h as i32);
+    }
+    if let Some(result) = max_desktop_resolution {
+        let resolution: Vec<&str> = result.split(" ").collect();
+        if let Ok(w) = resolution[0].parse::<i32>() {
+            maxx = maxx.max(minx + w);
+        }
+        if let Some(h) = resolution
+            .get(2)
+            .and_then(|h| h.trim_end_matches(",").parse::<i32>().ok())
+        {
+            maxy = maxy.max(miny + h);
+        }
+    }
+    (minx, maxx, miny, maxy)
+}
+
+fn get_cached_uinput_resolution() -> Option<(i32, i32, i32, i32)> {
+    let (minx, maxx, miny, maxy) = get_cap_display_info()?.uinput_resolution;
+    if minx != maxx && miny != maxy {
+        Some((minx, maxx, miny, maxy))
+    } else {
+        None
+    }
+}
+
+// The watcher only marks the displays as changed.
+// `clear()` is called by the video service when it restarts, to avoid freeing
+// the capturers while they are still being used by `frame()`.
+#[cfg_attr(test, allow(dead_code))]
+fn start_displays_watcher() {
+    static START: std::sync::Once = std::sync::Once::new();
+    START.call_once(|| {
+        std::thread::spawn(|| loop {
+            // Poll again soon if a change is pending, to re-init once it settles.
+            let interval = if PENDING_DISPLAYS_CHANGE.lock().unwrap().is_some() {
+                get_displays_change_debounce().min(DISPLAYS_WATCH_INTERVAL)
+            } else {
+                DISPLAYS_WATCH_INTERVAL
+            };
+            std::thread::sleep(interval);
+            check_displays_changed();
+        });
+    });
+}
+
+fn check_displays_changed() {
+    if DISPLAYS_CHANGED.load(Ordering::SeqCst) {
+        return;
+    }
+    let Some(cap_display_info) = get_cap_display_info() else {
+        return;
+    };
+    let rects = &cap_display_info.rects;
+    let all = match Display::all() {
+        Ok(all) => all,
+        Err(e) => {
+            log::debug!("Failed to get displays in displays watcher, {}", e);
+            return;
+        }
+    };
+    let new_rects: Vec<((i32, i32), usize, usize)> = all
+        .iter()
+        .map(|d| (d.origin(), d.width(), d.height()))
+        .collect();
+    update_displays_changed(rects, new_rects);
+}
+
+// The displays are marked as changed only if they stay the same for the debounce duration,
+// the last change in a burst wins.
+fn update_displays_changed(
+    rects: &Vec<((i32, i32), usize, usize)>,
+    new_rects: Vec<((i32, i32), usize, usize)>,
+) {
+    let mut pending = PENDING_DISPLAYS_CHANGE.lock().unwrap();
+    if &new_rects == rects {
+        // Changed back before settling.
+        *pending = None;
+        return;
+    }
+    if pending
+        .as_ref()
+        .map(|(last, _)| last != &new_rects)
+        .unwrap_or(true)
+    {
+        *pending = Some((new_rects, Instant::now()));
+    }
+    let Some((last, since)) = pending.as_ref() else {
+        return;
+    };
+    if since.elapsed() >= get_displays_change_debounce() {
+        log::info!("Wayland displays changed, {:?} -> {:?}", rects, last);
+        DISPLAYS_CHANGED.store(true, Ordering::SeqCst);
+        invalidate_displays();
+        *pending = None;
+    }
+}
+
+// Set how long the displays must stay unchanged before the capture is re-initialized.
+// Zero marks the displays as changed on the first detected change.
+pub fn set_displays_change_debounce(debounce: Duration) {
+    *DISPLAYS_CHANGE_DEBOUNCE.write().unwrap() = debounce;
+}
+
+#[inline]
+fn get_displays_change_debounce() -> Duration {
+    *DISPLAYS_CHANGE_DEBOUNCE.read().unwrap()
+}
+
+// Set the timeout of waiting for a pipewire frame.
+// It takes effect on the capturers returned by `get_capturer()` afterwards.
+pub fn set_frame_timeout(timeout: Duration) {
+    *FRAME_TIMEOUT.write().unwrap() = Some(timeout);
+}
+
+// Limit the size of the frames, e.g. 1920x1080 on a 4K host with a slow network.
+// The aspect ratio is kept and the frames are never scaled up, `None` to capture in the native size.
+// The cached capturers are recreated, the portal session is kept.
+pub fn set_capture_resolution(max_size: Option<(usize, usize)>) {
+    if scrap::wayland::pipewire::get_capture_max_size() == max_size {
+        return;
+    }
+    log::info!("Set the wayland capture resolution to {:?}", max_size);
+    scrap::wayland::pipewire::set_capture_max_size(max_size);
+    if let Some(cap_display_info) = get_cap_display_info() {
+        let mut capturers = cap_display_info.capturers.lock().unwrap();
+        let indices: Vec<usize> = capturers.slots.keys().cloned().collect();
+        for idx in indices {
+            capturers.remove(idx);
+            reset_display_state(Some(idx));
+        }
+        capturers.session_slots.clear();
+    }
+    CAPTURE_SIZE_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+// Changed by `set_capture_resolution()`.
+#[inline]
+pub fn capture_size_generation() -> u64 {
+    CAPTURE_SIZE_GENERATION.load(Ordering::SeqCst)
+}
+
+#[inline]
+fn get_capture_size(width: usize, height: usize) -> (usize, usize) {
+    scrap::wayland::pipewire::fit_capture_size(
+        (width, height),
+        scrap::wayland::pipewire::get_capture_max_size(),
+    )
+}
+
+#[inline]
+fn get_capture_factor(display_width: usize, capture_width: usize) -> f64 {
+    if display_width == 0 {
+        1.0
+    } else {
+        capture_width as f64 / display_width as f64
+    }
+}
+
+// The ratio of the frame size to the display size, divide the frame coordinates by it to get
+// the display coordinates. 1.0 if the frames are not scaled.
+pub fn capture_scale_factor(idx: usize) -> Option<f64> {
+    let rect = get_cap_display_info()?.rect_for(idx)?;
+    Some(get_capture_factor(
+        rect.width,
+        get_capture_size(rect.width, rect.height).0,
+    ))
+}
+
+// The number of the buffers negotiated with pipewire, clamped to `MIN_BUFFER_COUNT..=MAX_BUFFER_COUNT`.
+// More buffers drop fewer frames, fewer buffers lower the latency. `None` to use the default of pipewire.
+// It takes effect on the capturers created afterwards, call `force_reinit()` to apply it to a display.
+// Returns the effective count, 0 for the default.
+pub fn set_buffer_count(count: Option<u32>) -> u32 {
+    let count = count
+        .map(|n| n.clamp(MIN_BUFFER_COUNT, MAX_BUFFER_COUNT))
+        .unwrap_or(0);
+    log::info!("Set the wayland buffer count to {}", count);
+    scrap::wayland::pipewire::set_buffer_count(count);
+    count
+}
+
+// The formats of the encoder in the order of preference, negotiated with pipewire by the capturers created afterwards.
+// The negotiated one is reported by `frame_format()`. Returns the effective order, only BGRA and RGBA are supported.
+pub fn set_format_preference(formats: &[scrap::Pixfmt]) -> Vec<scrap::Pixfmt> {
+    let order = scrap::wayland::pipewire::set_format_preference(formats);
+    log::info!("Set the wayland capture format preference to {:?}", order);
+    order
+}
+
+// Compute the changed regions of the frames, see `PixelBuffer::damage()`.
+// Off by default, the whole frame is dirty then.
+pub fn set_damage_tracking(enabled: bool) {
+    scrap::wayland::pipewire::set_damage_tracking(enabled);
+}
+
+#[inline]
+pub fn get_frame_timeout() -> Option<Duration> {
+    *FRAME_TIMEOUT.read().unwrap()
+}
+
+#[inline]
+pub fn displays_changed() -> bool {
+    DISPLAYS_CHANGED.load(Ordering::SeqCst)
+}
+
+pub fn set_allow_x11_fallback(allow: bool) {
+    ALLOW_X11_FALLBACK.store(allow, Ordering::SeqCst);
+}
+
+// Whether the capture has fallen back to X11, the UI may warn about the reduced fidelity.
+#[inline]
+pub fn is_x11_fallback() -> bool {
+    scrap::is_x11_fallback()
+}
+
+// The result of `probe_capture_capability()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureCapability {
+    pub portal_present: bool,
+    pub pipewire_present: bool,
+    // The portal implements the ScreenCast interface with at least one source type.
+    pub screencast_supported: bool,
+    pub backend: DisplayBackend,
+}
+
+// Checks the environment for the setup wizard, never starts a capture or shows the portal dialog.
+pub fn probe_capture_capability() -> CaptureCapability {
+    let portal_present = scrap::wayland::pipewire::is_portal_present().unwrap_or_else(|e| {
+        log::warn!("Failed to check the portal on the session bus, {}", e);
+        false
+    });
+    // Reading the property may activate the portal, but no request is made.
+    let screencast_supported = portal_present
+        && scrap::wayland::pipewire::get_available_source_types().map_or(false, |types| types != 0);
+    CaptureCapability {
+        portal_present,
+        pipewire_present: scrap::wayland::pipewire::is_pipewire_present(),
+        screencast_supported,
+        backend: detected_backend(),
+    }
+}
+
+// A stage of `run_capture_selftest()`.
+#[derive(Debug, Clone)]
+pub struct SelfTestStage {
+    pub name: &'static str,
+    pub elapsed: Duration,
+    // `None` if the stage passed.
+    pub error: Option<String>,
+}
+
+// The result of `run_capture_selftest()`, `to_string()` is the summary to paste in a support request.
+#[derive(Debug, Clone, Default)]
+pub struct SelfTestReport {
+    pub stages: Vec<SelfTestStage>,
+    pub capability: Option<CaptureCapability>,
+    pub frames: usize,
+    pub frame_format: Option<FrameFormat>,
+    pub uinput_ready: bool,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        !self.stages.is_empty() && self.stages.iter().all(|s| s.error.is_none())
+    }
+
+    fn stage<T>(&mut self, name: &'static str, start: Instant, res: ResultType<T>) -> Option<T> {
+        let elapsed = start.elapsed();
+        let (res, error) = match res {
+            Ok(v) => (Some(v), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        self.stages.push(SelfTestStage {
+            name,
+            elapsed,
+            error,
+        });
+        res
+    }
+}
+
+impl std::fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Wayland capture self-test: {}",
+            if self.passed() { "passed" } else { "failed" }
+        )?;
+        if let Some(capability) = &self.capability {
+            writeln!(f, "capability: {:?}", capability)?;
+        }
+        for stage in &self.stages {
+            match &stage.error {
+                Some(e) => writeln!(f, "[FAIL] {} ({:?}): {}", stage.name, stage.elapsed, e)?,
+                None => writeln!(f, "[ OK ] {} ({:?})", stage.name, stage.elapsed)?,
+            }
+        }
+        writeln!(
+            f,
+            "frames: {}, format: {:?}",
+            self.frames, self.frame_format
+        )?;
+        write!(f, "uinput ready: {}", self.uinput_ready)
+    }
+}
+
+// Whether all the sampled pixels are black, the BGRA/RGBA frames of a screen shared but not rendered.
+fn is_black_frame(data: &[u8]) -> bool {
+    const THRESHOLD: u8 = 16;
+    let pixels = data.len() / 4;
+    let step = (pixels / 4096).max(1);
+    (0..pixels)
+        .step_by(step)
+        .all(|i| data[i * 4..i * 4 + 3].iter().all(|v| *v < THRESHOLD))
+}
+
+// Grabs a few frames of the current display, the frames must not be black and must keep coming.
+fn selftest_frames(report: &mut SelfTestReport) -> ResultType<()> {
+    let mut capturer = get_capturer()?.capturer;
+    let start = Instant::now();
+    let mut non_black = false;
+    while report.frames < SELFTEST_FRAMES && start.elapsed() < SELFTEST_TIMEOUT {
+        match capturer.frame(Duration::from_millis(100)) {
+            Ok(Frame::PixelBuffer(f)) => {
+                report.frames += 1;
+                non_black |= !is_black_frame(f.data());
+                report.frame_format = Some(FrameFormat::of(&f));
+            }
+            Ok(Frame::Texture(_)) => bail!("Unexpected texture frame"),
+            // The identical frames are dropped by scrap.
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if report.frames == 0 {
+        bail!(
+            "No frame in {:?}, the stream may be stale",
+            SELFTEST_TIMEOUT
+        );
+    }
+    if !non_black {
+        bail!("All the {} frames are black", report.frames);
+    }
+    Ok(())
+}
+
+// Runs the whole capture pipeline once and reports each stage, for the diagnostics.
+// The portal dialog is shown if there's no session yet. The capture state initialized by the self-test
+// is cleared afterwards, but the state of a capture in progress is kept.
+pub async fn run_capture_selftest() -> SelfTestReport {
+    let mut report = SelfTestReport {
+        capability: Some(probe_capture_capability()),
+        ..Default::default()
+    };
+    if is_x11() {
+        report.stage::<()>(
+            "backend",
+            Instant::now(),
+            Err(WaylandError::WrongBackend.into()),
+        );
+        return report;
+    }
+    let was_inited = get_cap_display_info().is_some();
+
+    let start = Instant::now();
+    let res = check_init().await;
+    if report.stage("init", start, res).is_some() {
+        let start = Instant::now();
+        let fallback = report.clone();
+        let res = hbb_common::tokio::task::spawn_blocking(move || {
+            let mut report = report;
+            let res = selftest_frames(&mut report);
+            report.stage("frames", start, res);
+            report
+        })
+        .await;
+        report = match res {
+            Ok(report) => report,
+            Err(e) => {
+                let mut report = fallback;
+                report.stage::<()>("frames", start, Err(e.into()));
+                report
+            }
+        };
+
+        let start = Instant::now();
+        report.uinput_ready = is_uinput_ready();
+        let res = if !uinput_backend::wayland_use_uinput() || report.uinput_ready {
+            Ok(())
+        } else {
+            Err(hbb_common::anyhow::anyhow!(
+                "uinput is required but not ready"
+            ))
+        };
+        report.stage("uinput", start, res);
+    }
+
+    if !was_inited {
+        clear();
+    }
+    log::info!("{}", report);
+    report
+}
+
+// An owned screenshot, RGBA without the row padding, the stride is `width * 4`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl ImageBuffer {
+    // The wayland frames are BGRx/RGBx, the alpha is opaque.
+    fn of(view: &FrameView) -> ResultType<Self> {
+        let swap = match view.pixfmt {
+            scrap::Pixfmt::BGRA => true,
+            scrap::Pixfmt::RGBA => false,
+            pixfmt => bail!("Unsupported screenshot format {:?}", pixfmt),
+        };
+        let mut data = Vec::with_capacity(view.width * view.height * 4);
+        for y in 0..view.height {
+            for p in view.row(y).chunks_exact(4) {
+                if swap {
+                    data.extend_from_slice(&[p[2], p[1], p[0], 0xff]);
+                } else {
+                    data.extend_from_slice(&[p[0], p[1], p[2], 0xff]);
+                }
+            }
+        }
+        Ok(Self {
+            width: view.width,
+            height: view.height,
+            data,
+        })
+    }
+}
+
+// Grabs one frame of the display, e.g. for the host preview in the connection UI.
+// The portal dialog is shown if there's no session yet, `WaylandCaptureError::UserCancelled` is returned
+// if the user cancels it. The frame is grabbed by a new stream, so it works while the display is streamed,
+// and a static screen still gives a frame. The capture state initialized here is cleared afterwards,
+// the portal session is kept, so the next screenshot does not prompt again.
+pub async fn capture_single_frame(idx: usize) -> ResultType<ImageBuffer> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    let was_inited = get_cap_display_info().is_some();
+    check_init().await?;
+    let res = hbb_common::tokio::task::spawn_blocking(move || single_frame(idx)).await;
+    if !was_inited {
+        clear();
+    }
+    res?
+}
+
+fn single_frame(idx: usize) -> ResultType<ImageBuffer> {
+    let Some(cap_display_info) = get_cap_display_info() else {
+        bail!(WaylandError::NotInitialized);
+    };
+    check_display_idx(&cap_display_info, idx)?;
+    let display = cap_display_info
+        .capturers
+        .lock()
+        .unwrap()
+        .displays
+        .get(idx)
+        .and_then(|d| d.0.try_clone());
+    let Some(display) = display else {
+        bail!(WaylandError::IndexOutOfRange {
+            idx,
+            len: cap_display_info.num
+        });
+    };
+    drop(cap_display_info);
+    let mut capturer = Capturer::new(display).context(WaylandError::CapturerCreateFailed)?;
+    let start = Instant::now();
+    while start.elapsed() < SINGLE_FRAME_TIMEOUT {
+        match capturer.frame(Duration::from_millis(100)) {
+            Ok(Frame::PixelBuffer(f)) => return ImageBuffer::of(&FrameView::of(&f)?),
+            Ok(Frame::Texture(_)) => bail!("Unexpected texture frame"),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    bail!("No frame of display {} in {:?}", idx, SINGLE_FRAME_TIMEOUT)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayBackend {
+    X11,
+    WaylandWlroots,
+    WaylandGnome,
+    WaylandKde,
+    Unknown,
+}
+
+impl DisplayBackend {
+    fn detect() -> Self {
+        if hbb_common::platform::linux::get_display_server() != DISPLAY_SERVER_WAYLAND {
+            return Self::X11;
+        }
+        // The portal backend is more reliable than the desktop name, e.g. "ubuntu:GNOME".
+        let backends = scrap::wayland::pipewire::get_portal_backends().unwrap_or_default();
+        for backend in backends.iter().map(|b| b.to_lowercase()) {
+            match backend.as_str() {
+                "gnome" => return Self::WaylandGnome,
+                "kde" => return Self::WaylandKde,
+                "wlr" | "hyprland" => return Self::WaylandWlroots,
+                _ => {}
+            }
+        }
+        let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+        Self::from_desktop(&desktop)
+    }
+
+    fn from_desktop(desktop: &str) -> Self {
+        for name in desktop.split(':').map(|n| n.to_lowercase()) {
+            match name.as_str() {
+                "gnome" => return Self::WaylandGnome,
+                "kde" => return Self::WaylandKde,
+                "sway" | "hyprland" | "river" | "wayfire" | "labwc" | "wlroots" => {
+                    return Self::WaylandWlroots
+                }
+                _ => {}
+            }
+        }
+        Self::Unknown
+    }
+}
+
+// Detected once, for the diagnostics and the compositor specific workarounds.
+pub fn detected_backend() -> DisplayBackend {
+    *DETECTED_BACKEND
+}
+
+// The capture state in plain text, for the users to paste in the bug reports.
+// Read-only, safe to call at any time, the locks are held only to copy the values.
+pub fn debug_dump() -> String {
+    use std::fmt::Write;
+    let mut s = String::new();
+    let _ = writeln!(s, "backend: {:?}, x11: {}", detected_backend(), is_x11());
+    if is_x11() {
+        return s;
+    }
+    match get_cap_display_info() {
+        None => {
+            let _ = writeln!(s, "not initialized");
+        }
+        Some(info) => {
+            let _ = writeln!(
+                s,
+                "initialized, num: {}, primary: {}, current: {}, physical display: {}",
+                info.num,
+                info.primary,
+                info.current(),
+                info.has_physical_display
+            );
+            for (idx, d) in info.displays.iter().enumerate() {
+                let _ = writeln!(
+                    s,
+                    "display {}: {:?}, ({}, {}) {}x{}, scale: {}, capture scale: {:?}",
+                    idx,
+                    d.name,
+                    d.x,
+                    d.y,
+                    d.width,
+                    d.height,
+                    d.scale,
+                    capture_scale_factor(idx)
+                );
+            }
+        }
+    }
+    let _ = writeln!(s, "paused: {}", is_capture_paused());
+    let _ = writeln!(s, "capture stats: {:?}", capture_stats());
+    let _ = write!(s, "last error: {:?}", LAST_ERROR.lock().unwrap().clone());
+    s
+}
+
+pub(super) async fn check_init() -> ResultType<()> {
+    match check_init_().await {
+        // The user chose not to share the screen, do not capture with X11 instead.
+        Err(e)
+            if !is_x11()
+                && ALLOW_X11_FALLBACK.load(Ordering::SeqCst)
+                && !WaylandCaptureError::from_error(&e)
+                    .map_or(false, |e| e.is_user_cancelled()) =>
+        {
+            try_fallback_to_x11(e)
+        }
+        res => res,
+    }
+}
+
+// For the callers not in an async context, e.g. the FFI and plugin entries.
+// Shares `INIT_STATE` with `check_init()`, so it waits for the initialization in progress.
+// Must not be called in a tokio runtime, blocking a worker thread of it may deadlock.
+pub fn check_init_blocking() -> ResultType<()> {
+    if hbb_common::tokio::runtime::Handle::try_current().is_ok() {
+        bail!("Do not call check_init_blocking() in an async runtime, use check_init() instead");
+    }
+    hbb_common::tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(check_init())
+}
+
+fn try_fallback_to_x11(err: hbb_common::anyhow::Error) -> ResultType<()> {
+    log::warn!("Failed to init wayland capture, try X11, {}", err);
+    scrap::set_x11_fallback(true);
+    match Display::all() {
+        Ok(all) if !all.is_empty() => {
+            log::warn!("Fall back to X11 capture, {} displays", all.len());
+            super::display_service::check_update_displays(&all);
+            Ok(())
+        }
+        res => {
+            log::error!(
+                "Failed to fall back to X11 capture, {:?}",
+                res.map(|all| all.len())
+            );
+            scrap::set_x11_fallback(false);
+            Err(err)
+        }
+    }
+}
+
+async fn check_init_() -> ResultType<()> {
+    if !is_x11() {
+        // The tests check the displays and the frames directly, without the background threads.
+        #[cfg(not(test))]
+        {
+            start_displays_watcher();
+            start_frame_watchdog();
+        }
+        let use_uinput = uinput_backend::wayland_use_uinput();
+
+        if read_cap_display_info().is_none() {
+            // Only one caller initializes, the others wait for its result.
+            let attempts = INIT_ATTEMPTS.load(Ordering::SeqCst);
+            let mut state = INIT_STATE.clone().lock_owned().await;
+            if read_cap_display_info().is_none() {
+                if INIT_ATTEMPTS.load(Ordering::SeqCst) != attempts {
+                    // The initialization failed while waiting, do not prompt the user again.
+                    if let Some(e) = state.last_error.as_ref() {
+                        return Err(clone_init_error(e));
+                    }
+                }
+                if let Some(left) = reprompt_cooldown_left(state.cancelled_at) {
+                    if let Some(e) = state.last_error.as_ref() {
+                        log::debug!(
+                            "The user cancelled the screen sharing, do not prompt again in {:?}",
+                            left
+                        );
+                        return Err(clone_init_error(e));
+                    }
+                }
+                // The portal dialog may wait for the user for a long time.
+                // If this task is cancelled, e.g. the peer disconnects, the blocking task still
+                // publishes its result and releases `INIT_STATE`.
+                let res = hbb_common::tokio::task::spawn_blocking(move || {
+                    let res = new_cap_display_info_with_retry(use_uinput);
+                    INIT_ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+                    match res {
+                        Ok(cap_display_info) => {
+                            state.last_error = None;
+                            state.cancelled_at = None;
+                            *LAST_ERROR.lock().unwrap() = None;
+                            *write_cap_display_info() = Some(Arc::new(cap_display_info));
+                            run_lifecycle_hooks("on_init", |hook| hook.on_init());
+                            Ok(())
+                        }
+                        Err(e) => {
+                            if WaylandCaptureError::from_error(&e)
+                                .map_or(false, |e| e.is_user_cancelled())
+                            {
+                                log::info!("The user cancelled the screen sharing, {:#}", e);
+                                state.cancelled_at = Some(Instant::now());
+                            } else {
+                                state.cancelled_at = None;
+                                log::error!(
+                                    "Failed to init wayland capture, backend: {:?}, {:#}",
+                                    detected_backend(),
+                                    e
+                                );
+                            }
+                            run_lifecycle_error_hooks(&e);
+                            let err = clone_init_error(&e);
+                            state.last_error = Some(e);
+                            Err(err)
+                        }
+                    }
+                })
+                .await;
+                match res {
+                    Ok(res) => res?,
+                    Err(e) => bail!("Failed to init wayland capture, {}", e),
+                }
+            }
+        }
+
+        // Apply the resolution on every call, uinput may fail to be updated last time.
+        // Skipped for the view only sessions, `set_conn_input_enabled()` applies it later.
+        if use_uinput && is_input_enabled() {
+            if let Some((minx, maxx, miny, maxy)) = get_cached_uinput_resolution() {
+                log::info!(
+                    "update mouse resolution: ({}, {}), ({}, {})",
+                    minx,
+                    maxx,
+                    miny,
+                    maxy
+                );
+                set_uinput_result(
+                    &uinput_backend::update_mouse_resolution(minx, maxx, miny, maxy).await,
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+// Set how long `check_init()` returns the cancelled error instead of prompting the user again,
+// after the user cancelled the portal dialog. Zero prompts on every call.
+// `reset_and_reprompt()` always prompts.
+pub fn set_reprompt_cooldown(cooldown: Duration) {
+    *REPROMPT_COOLDOWN.write().unwrap() = cooldown;
+}
+
+fn reprompt_cooldown_left(cancelled_at: Option<Instant>) -> Option<Duration> {
+    let cooldown = *REPROMPT_COOLDOWN.read().unwrap();
+    cooldown
+        .checked_sub(cancelled_at?.elapsed())
+        .filter(|left| !left.is_zero())
+}
+
+// The selected display may be disconnected between enumerating the displays and creating its capturer,
+// e.g. by undocking, then the displays are enumerated again and another one is selected.
+// Not retried if the display is still there, its capturer failed for another reason.
+fn new_cap_display_info_with_retry(use_uinput: bool) -> ResultType<CapDisplayInfo> {
+    let mut failed = Vec::new();
+    let mut all = get_displays_or_virtual()?;
+    loop {
+        match new_cap_display_info(use_uinput, all, &mut failed) {
+            Ok(info) => {
+                if !failed.is_empty() {
+                    let current = info.current();
+                    log::info!(
+                        "Created the wayland capturer of display {} {:?} after failing on {:?}",
+                        current,
+                        info.displays.get(current).map(|d| d.name.clone()),
+                        failed
+                    );
+                }
+                return Ok(info);
+            }
+            Err(e)
+                if e.downcast_ref::<WaylandError>()
+                    == Some(&WaylandError::CapturerCreateFailed)
+                    && !failed.is_empty()
+                    && failed.len() <= MAX_VANISHED_DISPLAY_RETRIES =>
+            {
+                // The streams of the session are stale, a restored session has the present displays.
+                if scrap::wayland::pipewire::is_session_restorable() {
+                    scrap::wayland::pipewire::close_session();
+                }
+                all = get_displays_or_virtual()?;
+                let ids: Vec<Option<u64>> = all.iter().map(node_id_of).collect();
+                if failed.last().map_or(false, |id| ids.contains(&Some(*id))) {
+                    return Err(e);
+                }
+                log::warn!(
+                    "Failed to create the wayland capturer of node {:?}, it's disconnected, retry {}/{}, {:#}",
+                    failed.last(),
+                    failed.len(),
+                    MAX_VANISHED_DISPLAY_RETRIES,
+                    e
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn get_displays_or_virtual() -> ResultType<Vec<Display>> {
+    let all = get_all_displays_with_retry().context(WaylandError::DisplayEnumFailed)?;
+    if all.is_empty() {
+        return get_virtual_displays();
+    }
+    Ok(all)
+}
+
+// The pipewire node id, which is kept while the display is connected, unlike its index.
+fn node_id_of(display: &Display) -> Option<u64> {
+    match display {
+        Display::WAYLAND(d) => Some(d.node_id()),
+        _ => None,
+    }
+}
+
+// The primary display if it has not failed, otherwise the first one which has not failed.
+// `ids` are the node ids of the displays, see `node_id_of()`.
+fn select_not_failed(ids: &[Option<u64>], primary: usize, failed: &[u64]) -> Option<usize> {
+    let not_failed = |id: &Option<u64>| id.map_or(true, |id| !failed.contains(&id));
+    if ids.get(primary).map_or(false, not_failed) {
+        return Some(primary);
+    }
+    ids.iter().position(not_failed)
+}
+
+// The displays and capturer are created without holding `CAP_DISPLAY_INFO`,
+// `Display::all()` may wait for the user to answer the portal dialog.
+// `failed` are the node ids of the displays whose capturers failed to be created, they're skipped,
+// the node id of the selected display is appended if its capturer fails to be created.
+fn new_cap_display_info(
+    use_uinput: bool,
+    mut all: Vec<Display>,
+    failed: &mut Vec<u64>,
+) -> ResultType<CapDisplayInfo> {
+    let num = all.len();
+    let has_physical_display = all.iter().any(|d| !d.is_virtual());
+    // `primary` is the detected one, the preferred primary is applied by `get_primary_of()`.
+    let primary = super::display_service::get_primary_2(&all);
+    let names: Vec<String> = all.iter().map(|d| d.name()).collect();
+    let rects: Vec<((i32, i32), usize, usize)> = all
+        .iter()
+        .map(|d| (d.origin(), d.width(), d.height()))
+        .collect();
+    let mut current = if failed.is_empty() {
+        initial_display_under_cursor(&rects)
+            .or_else(|| restore_current_display(&names))
+            .unwrap_or_else(|| select_primary(primary, get_preferred_primary(), num))
+    } else {
+        let primary = select_primary(primary, get_preferred_primary(), num);
+        let ids: Vec<Option<u64>> = all.iter().map(node_id_of).collect();
+        match select_not_failed(&ids, primary, failed) {
+            Some(idx) => idx,
+            None => bail!(
+                "Failed to create the capturers of all the wayland displays, {:?}",
+                failed
+            ),
+        }
+    };
+    if current >= num {
+        log::warn!(
+            "Invalid current display {}, displays len: {}, use the first one",
+            current,
+            num
+        );
+        current = 0;
+    }
+    if all.iter().any(|d| match d {
+        Display::WAYLAND(d) => d.scale().is_none(),
+        _ => false,
+    }) {
+        static LOG_SCALE_ONCE: std::sync::Once = std::sync::Once::new();
+        LOG_SCALE_ONCE.call_once(|| {
+            log::warn!("Failed to get the wayland display scale, use 1.0");
+        });
+    }
+    super::display_service::check_update_displays(&all);
+    let mut displays = super::display_service::get_sync_displays();
+    for display in displays.iter_mut() {
+        display.cursor_embedded = cursor_embedded();
+    }
+    set_display_flags(&mut displays, primary, current);
+    set_physical_sizes(&mut displays, &get_drm_physical_sizes());
+    log::info!(
+        "wayland display refresh rates: {:?}",
+        displays.iter().map(|d| d.refresh_rate).collect::<Vec<_>>()
+    );
+
+    // The origins are the positions in the compositor's layout, they're negative for the displays
+    // on the left of or above the primary one.
+    log::info!("wayland display rects: {:?}", &rects);
+
+    let (origin, width, height) = (
+        all[current].origin(),
+        all[current].width(),
+        all[current].height(),
+    );
+    log::debug!(
+        "#displays={}, current={}, origin: {:?}, width={}, height={}, cpus={}/{}",
+        num,
+        current,
+        &origin,
+        width,
+        height,
+        num_cpus::get_physical(),
+        num_cpus::get(),
+    );
+
+    let uinput_resolution = if use_uinput {
+        get_uinput_resolution(&rects, get_max_desktop_resolution())
+    } else {
+        (0, 0, 0, 0)
+    };
+
+    let mut capturers = CapturerCache::default();
+    if let Some(displays) = all
+        .iter()
+        .map(|d| d.try_clone())
+        .collect::<Option<Vec<_>>>()
+    {
+        capturers.displays = displays.into_iter().map(SendDisplay).collect();
+    }
+    let id = node_id_of(&all[current]);
+    let capturer = Capturer::new(all.remove(current))
+        .map_err(|e| {
+            failed.extend(id);
+            e
+        })
+        .context(WaylandError::CapturerCreateFailed)?;
+    let bytes = estimated_capturer_bytes(capturer.width(), capturer.height());
+    capturers.insert(current, Box::new(capturer), bytes);
+    let mut cap_display_info = CapDisplayInfo {
+        rects,
+        displays,
+        num,
+        primary,
+        current: AtomicUsize::new(current),
+        capturers,
+        uinput_resolution,
+        has_physical_display,
+    };
+    if cap_display_info.validate().is_err() {
+        cap_display_info.rebuild_rects();
+    }
+    cap_display_info.validate()?;
+    Ok(cap_display_info)
+}
+
+// No monitor is attached on a headless host, try to share a virtual monitor, e.g. of the headless wlroots backend.
+fn get_virtual_displays() -> ResultType<Vec<Display>> {
+    let no_display = || {
+        *LAST_ERROR.lock().unwrap() = Some(WaylandCaptureError::NoDisplayAttached);
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            WaylandCaptureError::NoDisplayAttached,
+        )
+    };
+    let types = scrap::wayland::pipewire::get_source_types();
+    let available = scrap::wayland::pipewire::get_available_source_types().unwrap_or_default();
+    if types & SOURCE_TYPE_VIRTUAL != 0 || available & SOURCE_TYPE_VIRTUAL == 0 {
+        return Err(no_display().into());
+    }
+    log::info!("No wayland display is found, try the virtual monitor");
+    scrap::wayland::pipewire::set_source_types(types | SOURCE_TYPE_VIRTUAL);
+    scrap::wayland::pipewire::close_session();
+    let all = Display::all()?;
+    if all.is_empty() {
+        return Err(no_display().into());
+    }
+    Ok(all)
+}
+
+// `None` if the capture is not initialized.
+// The UI may offer to create a virtual display if no physical display is attached.
+pub fn has_physical_display() -> Option<bool> {
+    Some(get_cap_display_info()?.has_physical_display)
+}
+
+// `anyhow::Error` is not `Clone`, keep the `WaylandCaptureError` for the waiters.
+fn clone_init_error(e: &hbb_common::anyhow::Error) -> hbb_common::anyhow::Error {
+    match WaylandCaptureError::from_error(e) {
+        Some(kind) => io::Error::new(io::ErrorKind::Other, kind.clone()).into(),
+        None => hbb_common::anyhow::anyhow!("{:#}", e),
+    }
+}
+
+// Must be called in the server runtime, see `input_service::setup_uinput()`.
+pub(super) async fn setup_uinput(minx: i32, maxx: i32, miny: i32, maxy: i32) {
+    let res = uinput_backend::setup_uinput(minx, maxx, miny, maxy).await;
+    if res.is_ok() {
+        UINPUT_CREATED.store(true, Ordering::SeqCst);
+    }
+    set_uinput_result(&res);
+}
+
+#[inline]
+pub fn is_input_enabled() -> bool {
+    INPUT_ENABLED.load(Ordering::SeqCst)
+}
+
+// Called by the connections when the keyboard and mouse permission is granted or revoked.
+// Input is enabled while any connection controls the keyboard and mouse.
+pub(super) async fn set_conn_input_enabled(conn_id: i32, enabled: bool) {
+    let any = {
+        let mut conns = INPUT_CONNS.lock().unwrap();
+        if enabled {
+            conns.insert(conn_id);
+        } else {
+            conns.remove(&conn_id);
+        }
+        !conns.is_empty()
+    };
+    set_input_enabled(any).await;
+}
+
+// Disable input for the view only sessions, so `/dev/uinput` is not required.
+// The uinput setup is deferred until input is enabled.
+async fn set_input_enabled(enabled: bool) {
+    let was_enabled = INPUT_ENABLED.swap(enabled, Ordering::SeqCst);
+    if !enabled || was_enabled || !uinput_backend::wayland_use_uinput() {
+        return;
+    }
+    let (minx, maxx, miny, maxy) = get_cached_uinput_resolution().unwrap_or((0, 1920, 0, 1080));
+    log::info!(
+        "input enabled, set up uinput: ({}, {}), ({}, {})",
+        minx,
+        maxx,
+        miny,
+        maxy
+    );
+    if UINPUT_CREATED.load(Ordering::SeqCst) {
+        set_uinput_result(&uinput_backend::update_mouse_resolution(minx, maxx, miny, maxy).await);
+    } else {
+        setup_uinput(minx, maxx, miny, maxy).await;
+    }
+}
+
+// Record the result of setting up or updating uinput.
+pub(super) fn set_uinput_result(res: &ResultType<()>) {
+    match res {
+        Ok(()) => {
+            UINPUT_READY.store(true, Ordering::SeqCst);
+            *LAST_UINPUT_ERROR.lock().unwrap() = None;
+        }
+        Err(e) => {
+            log::error!("Failed to set up uinput, {}", e);
+            UINPUT_READY.store(false, Ordering::SeqCst);
+            *LAST_UINPUT_ERROR.lock().unwrap() = Some(format!("Failed to set up uinput, {}", e));
+        }
+    }
+}
+
+// False if the keyboard and mouse can not be controlled through uinput, though the screen is captured.
+// Always false if uinput is not used, e.g. the remote desktop portal is used for input.
+#[inline]
+pub fn is_uinput_ready() -> bool {
+    UINPUT_READY.load(Ordering::SeqCst)
+}
+
+// Whether the frames already contain the cursor, so the peer should not draw its own cursor.
+// It's known once the portal session is started, and updated if a new session negotiates another mode.
+#[inline]
+pub fn cursor_embedded() -> bool {
+    is_cursor_embedded()
+}
+
+pub(super) async fn get_displays() -> ResultType<Vec<DisplayInfo>> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    check_init().await?;
+    match get_cached_displays() {
+        Some(displays) => Ok(displays.as_ref().clone()),
+        None => bail!(WaylandError::NotInitialized),
+    }
+}
+
+// The displays of `get_displays()` without initializing the capture, `None` if it's not initialized.
+// Built once and shared until invalidated, so polling it is cheap.
+// The list is rebuilt after `clear()`, `force_reinit()`, `set_current_display()`, `set_preferred_primary()`
+// and the displays changes seen by the displays watcher. Other changes, e.g. the scale changed without
+// changing the size, are seen only after `invalidate_displays()`.
+pub fn get_cached_displays() -> Option<Arc<Vec<DisplayInfo>>> {
+    let cap_display_info = get_cap_display_info()?;
+    let mut cache = DISPLAYS_CACHE.lock().unwrap();
+    if let Some((info, displays)) = cache.as_ref() {
+        // Built from the current capture state, not the one before `clear()`.
+        if std::ptr::eq(info.as_ptr(), Arc::as_ptr(&cap_display_info)) {
+            return Some(displays.clone());
+        }
+    }
+    let displays = Arc::new(build_displays(&cap_display_info));
+    *cache = Some((Arc::downgrade(&cap_display_info), displays.clone()));
+    Some(displays)
+}
+
+// Force the next `get_cached_displays()` and `get_displays()` to rebuild the list, e.g. on hotplug.
+pub fn invalidate_displays() {
+    *DISPLAYS_CACHE.lock().unwrap() = None;
+}
+
+fn build_displays(cap_display_info: &CapDisplayInfo) -> Vec<DisplayInfo> {
+    if get_source_type() == SourceType::WholeDesktop {
+        return vec![desktop_display_info(cap_display_info)];
+    }
+    let mut displays = cap_display_info.displays.clone();
+    let primary = get_primary_of(cap_display_info);
+    set_display_flags(&mut displays, primary, cap_display_info.current());
+    let cursor_embedded = cursor_embedded();
+    for display in displays.iter_mut() {
+        display.cursor_embedded = cursor_embedded;
+    }
+    displays
+}
+
+// The hash of the display arrangement of `get_displays()`, `None` if the capture is not initialized.
+// The client can persist it to detect the layout changes, e.g. to reset the window placement per monitor.
+pub fn display_config_hash() -> Option<u64> {
+    get_cap_display_info().map(|info| config_hash_of(&info.displays))
+}
+
+// FNV-1a over the sorted (name, origin, size) of the displays. Not `DefaultHasher`,
+// which may change between Rust releases, the hash is compared across reconnects and upgrades.
+fn config_hash_of(displays: &[DisplayInfo]) -> u64 {
+    let mut layout: Vec<(&str, i32, i32, i32, i32)> = displays
+        .iter()
+        .map(|d| (d.name.as_str(), d.x, d.y, d.width, d.height))
+        .collect();
+    layout.sort();
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut write = |bytes: &[u8]| {
+        for b in bytes {
+            hash ^= *b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    for (name, x, y, width, height) in layout {
+        write(&(name.len() as u32).to_le_bytes());
+        write(name.as_bytes());
+        for v in [x, y, width, height] {
+            write(&v.to_le_bytes());
+        }
+    }
+    hash
+}
+
+// List the displays and the primary one without creating any capturer, e.g. for a monitor picker
+// before the user starts sharing. The capturers are created by `check_init()` when the capture starts.
+// The displays are read from the portal session, the portal dialog is still shown if there's no
+// session and no restore token yet.
+pub fn enumerate_displays() -> ResultType<(Vec<DisplayInfo>, usize)> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    if let Some(cap_display_info) = get_cap_display_info() {
+        let mut displays = cap_display_info.displays.clone();
+        let primary = get_primary_of(&cap_display_info);
+        set_display_flags(&mut displays, primary, cap_display_info.current());
+        return Ok((displays, primary));
+    }
+    let all = Display::all().context(WaylandError::DisplayEnumFailed)?;
+    let mut displays = super::display_service::get_displays_2(&all);
+    let primary = select_primary(
+        super::display_service::get_primary_2(&all),
+        get_preferred_primary(),
+        all.len(),
+    );
+    set_display_flags(&mut displays, primary, primary);
+    set_physical_sizes(&mut displays, &get_drm_physical_sizes());
+    Ok((displays, primary))
+}
+
+// Switch the display captured by `get_capturer()` and flagged as current by `get_displays()`,
+// it's persisted so the reconnections resume on the same display.
+pub fn set_current_display(idx: usize) -> ResultType<()> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    let Some(cap_display_info) = get_cap_display_info() else {
+        bail!(WaylandError::NotInitialized);
+    };
+    check_display_idx(&cap_display_info, idx)?;
+    let old = cap_display_info.current.swap(idx, Ordering::SeqCst);
+    if old != idx {
+        log::info!("Switch the current wayland display {} -> {}", old, idx);
+        invalidate_displays();
+    }
+    let name = cap_display_info
+        .displays
+        .get(idx)
+        .map(|d| d.name.clone())
+        .unwrap_or_default();
+    Config::set_option(OPTION_CURRENT_DISPLAY.to_owned(), idx.to_string());
+    Config::set_option(OPTION_CURRENT_DISPLAY_NAME.to_owned(), name);
+    Ok(())
+}
+
+// `None` if the capture is not initialized.
+pub fn get_current_display() -> Option<usize> {
+    get_cap_display_info().map(|info| info.current())
+}
+
+// The persisted current display, matched by the name first, then by the index.
+// `None` if it's not set or not in `names`.
+fn restore_current_display(names: &[String]) -> Option<usize> {
+    let name = Config::get_option(OPTION_CURRENT_DISPLAY_NAME);
+    if !name.is_empty() {
+        if let Some(idx) = names.iter().position(|n| *n == name) {
+            return Some(idx);
+        }
+    }
+    let idx = Config::get_option(OPTION_CURRENT_DISPLAY)
+        .parse::<usize>()
+        .ok()?;
+    if idx < names.len() {
+        Some(idx)
+    } else {
+        log::warn!(
+            "Ignore the saved current wayland display {}, displays len: {}",
+            idx,
+            names.len()
+        );
+        None
+    }
+}
+
+// Off by default. If on, `check_init()` captures the display under the cursor first,
+// instead of the last selected display or the primary one.
+pub fn set_initial_display_follows_cursor(follows: bool) {
+    INITIAL_DISPLAY_FOLLOWS_CURSOR.store(follows, Ordering::SeqCst);
+}
+
+// The cursor position is read through XWayland, it's `None` if XWayland is not running.
+// It may be stale if the cursor has not been over an XWayland window since it moved.
+fn initial_display_under_cursor(rects: &[((i32, i32), usize, usize)]) -> Option<usize> {
+    if !INITIAL_DISPLAY_FOLLOWS_CURSOR.load(Ordering::SeqCst) {
+        return None;
+    }
+    let res = display_under(rects, crate::platform::linux::get_cursor_pos());
+    if res.is_none() {
+        log::info!("The cursor is not on any wayland display, use the default one");
+    }
+    res
+}
+
+fn display_under(rects: &[((i32, i32), usize, usize)], pos: Option<(i32, i32)>) -> Option<usize> {
+    let (x, y) = pos?;
+    global_to_local(rects, x, y).map(|(idx, _, _)| idx)
+}
+
+// Overrides the primary display reported by the compositor, `None` to use the detected one.
+// The index is persisted, it's ignored if it's out of the displays.
+pub fn set_preferred_primary(idx: Option<usize>) {
+    Config::set_option(
+        OPTION_PREFERRED_PRIMARY.to_owned(),
+        idx.map(|i| i.to_string()).unwrap_or_default(),
+    );
+    invalidate_displays();
+}
+
+#[cfg(not(test))]
+pub fn get_preferred_primary() -> Option<usize> {
+    Config::get_option(OPTION_PREFERRED_PRIMARY).parse().ok()
+}
+
+// The tests do not read or write the config of the user.
+#[cfg(test)]
+pub fn get_preferred_primary() -> Option<usize> {
+    *tests::PREFERRED_PRIMARY.lock().unwrap()
+}
+
+fn select_primary(detected: usize, preferred: Option<usize>, num: usize) -> usize {
+    match preferred {
+        Some(idx) if idx < num => idx,
+        Some(idx) => {
+            log::warn!(
+                "Invalid preferred primary wayland display {}, displays len: {}, use {}",
+                idx,
+                num,
+                detected
+            );
+            detected
+        }
+        None => detected,
+    }
+}
+
+// Read on each call, the preferred primary can be changed without initializing again.
+fn get_primary_of(cap_display_info: &CapDisplayInfo) -> usize {
+    select_primary(
+        cap_display_info.primary,
+        get_preferred_primary(),
+        cap_display_info.num,
+    )
+}
+
+pub(super) fn get_primary() -> ResultType<usize> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    if let Some(cap_display_info) = get_cap_display_info() {
+        Ok(get_primary_of(&cap_display_info))
+    } else {
+        bail!(WaylandError::NotInitialized);
+    }
+}
+
+pub fn get_display_num() -> ResultType<usize> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    if let Some(cap_display_info) = get_cap_display_info() {
+        if get_source_type() == SourceType::WholeDesktop {
+            return Ok(1);
+        }
+        Ok(cap_display_info.num)
+    } else {
+        bail!(WaylandError::NotInitialized);
+    }
+}
+
+// Re-evaluates the session type, e.g. the user logs out of X11 into wayland without restarting the service,
+// and returns the new display server.
+// The wayland capture state is stale if the session type is changed, it's dropped.
+pub fn refresh_session_type() -> &'static str {
+    let changed = crate::platform::linux::refresh_is_x11();
+    let display_server = if is_x11() {
+        DISPLAY_SERVER_X11
+    } else {
+        DISPLAY_SERVER_WAYLAND
+    };
+    if changed {
+        log::info!("The session type is changed to {}", display_server);
+        on_session_type_changed();
+    }
+    display_server
+}
+
+// Forgets the frame state of the display, or of all the displays if `None`, e.g. its capturer is recreated.
+fn reset_display_state(idx: Option<usize>) {
+    fn reset<V>(map: &Mutex<HashMap<usize, V>>, idx: Option<usize>) {
+        let mut map = map.lock().unwrap();
+        match idx {
+            Some(idx) => {
+                map.remove(&idx);
+            }
+            None => map.clear(),
+        }
+    }
+    reset(&FRAME_FAILING_SINCE, idx);
+    reset(&FRAME_FORMATS, idx);
+    reset(&FRAME_TIMESTAMPS, idx);
+    reset(&FRAME_SEQS, idx);
+    reset(&FRAME_CHECKSUMS, idx);
+    reset(&FROZEN_STATE, idx);
+}
+
+fn on_session_type_changed() {
+    // Not `clear()`, which does nothing on X11.
+    let cap_display_info = write_cap_display_info().take();
+    DISPLAYS_CHANGED.store(false, Ordering::SeqCst);
+    *PENDING_DISPLAYS_CHANGE.lock().unwrap() = None;
+    *SEAT0_SESSION.lock().unwrap() = None;
+    *SCREEN_LOCK_CHECKED_AT.lock().unwrap() = None;
+    reset_display_state(None);
+    invalidate_displays();
+    CAPTURING.store(false, Ordering::SeqCst);
+    *LAST_ERROR.lock().unwrap() = None;
+    drop(cap_display_info);
+}
+
+// The (origin, width, height) of all the displays, in the order of the display indexes.
+pub fn get_display_rects() -> ResultType<Vec<((i32, i32), usize, usize)>> {
+    if let Some(cap_display_info) = get_cap_display_info() {
+        Ok(cap_display_info.rects.clone())
+    } else {
+        bail!(WaylandError::NotInitialized);
+    }
+}
+
+// Maps a point in the global layout to (display index, x, y) relative to the display.
+pub fn global_to_local(
+    rects: &[((i32, i32), usize, usize)],
+    x: i32,
+    y: i32,
+) -> Option<(usize, i32, i32)> {
+    rects
+        .iter()
+        .position(|((ox, oy), w, h)| {
+            x >= *ox && y >= *oy && x < *ox + *w as i32 && y < *oy + *h as i32
+        })
+        .map(|idx| {
+            let ((ox, oy), _, _) = rects[idx];
+            (idx, x - ox, y - oy)
+        })
+}
+
+// Maps (x, y) relative to the display `idx` to the global layout, the inverse of `global_to_local()`.
+// The point is clamped into the display, so it never lands on the neighbor display.
+pub fn local_to_global(
+    rects: &[((i32, i32), usize, usize)],
+    idx: usize,
+    x: i32,
+    y: i32,
+) -> Option<(i32, i32)> {
+    let ((ox, oy), w, h) = rects.get(idx).cloned()?;
+    if w == 0 || h == 0 {
+        return None;
+    }
+    Some((ox + x.clamp(0, w as i32 - 1), oy + y.clamp(0, h as i32 - 1)))
+}
+
+// The absolute uinput position of a point on a display, for the peers sending
+// (display index, x, y). The uinput bounds are the union of the displays, see `get_uinput_resolution()`,
+// so the global position is used as is.
+pub fn display_point_to_uinput(idx: usize, x: i32, y: i32) -> ResultType<(i32, i32)> {
+    let Some(cap_display_info) = get_cap_display_info() else {
+        bail!(WaylandError::NotInitialized);
+    };
+    check_display_idx(&cap_display_info, idx)?;
+    match local_to_global(&cap_display_info.rects, idx, x, y) {
+        Some(pos) => Ok(pos),
+        None => bail!("Display {} is empty", idx),
+    }
+}
+
+// Stops handing out the capturers and drops the references of this module.
+// A `CapturerInfo` handed out before keeps its capturer alive, the capturer is dropped when the
+// lease is dropped, e.g. by the video service. Its `frame()` returns errors meanwhile, so the caller
+// should drop it and get a new one.
+pub fn clear() {
+    if is_x11() {
+        return;
+    }
+    // Only one caller takes the info, the others get `None`, so it's safe to call from multiple threads.
+    // The capturers in use are dropped when their leases are dropped.
+    let cap_display_info = write_cap_display_info().take();
+    DISPLAYS_CHANGED.store(false, Ordering::SeqCst);
+    *PENDING_DISPLAYS_CHANGE.lock().unwrap() = None;
+    *SEAT0_SESSION.lock().unwrap() = None;
+    *SCREEN_LOCK_CHECKED_AT.lock().unwrap() = None;
+    reset_display_state(None);
+    invalidate_displays();
+    CAPTURE_PAUSED.store(false, Ordering::SeqCst);
+    CAPTURE_ARMED.store(false, Ordering::SeqCst);
+    CAPTURING.store(false, Ordering::SeqCst);
+    #[cfg(feature = "wayland-shm")]
+    super::wayland_shm::clear();
+    let cleared = cap_display_info.is_some();
+    // Drop the capturers after releasing the lock, stopping pipewire may take a while.
+    drop(cap_display_info);
+    if cleared {
+        run_lifecycle_hooks("on_clear", |hook| hook.on_clear());
+    }
+}
+
+// Stops pulling the frames, the portal session and the capturers are kept, so resuming does not prompt again.
+// `frame()` returns `WaylandCaptureError::Paused` meanwhile, see `is_paused_error()`.
+pub fn pause_capture() {
+    CAPTURING.store(false, Ordering::SeqCst);
+    if !CAPTURE_PAUSED.swap(true, Ordering::SeqCst) {
+        log::info!("Pause the wayland capture");
+        scrap::wayland::pipewire::set_stop_stream_on_pause(false);
+        set_idle_capturers_paused(true);
+    }
+}
+
+pub fn resume_capture() {
+    CAPTURE_ARMED.store(false, Ordering::SeqCst);
+    if CAPTURE_PAUSED.swap(false, Ordering::SeqCst) {
+        log::info!("Resume the wayland capture");
+        set_idle_capturers_paused(false);
+    }
+}
+
+// Armed but not streaming: like `pause_capture()`, but the pipewire streams are also stopped,
+// so the compositor's recording indicator is off while nobody is watching.
+// The portal session and the capturers are kept, `resume_capture()` restarts the streams without prompting.
+pub fn arm_capture() {
+    if get_cap_display_info().is_none() {
+        return;
+    }
+    log::info!("Arm the wayland capture, the streams are stopped until resumed");
+    CAPTURE_ARMED.store(true, Ordering::SeqCst);
+    CAPTURING.store(false, Ordering::SeqCst);
+    scrap::wayland::pipewire::set_stop_stream_on_pause(true);
+    // Also applied if the capture is already paused, to stop the streams.
+    CAPTURE_PAUSED.store(true, Ordering::SeqCst);
+    set_idle_capturers_paused(true);
+}
+
+#[inline]
+pub fn is_capture_armed() -> bool {
+    CAPTURE_ARMED.load(Ordering::SeqCst)
+}
+
+// Arm the capture instead of clearing it when the last peer disconnects, for the fast reconnects
+// of a daemon. Off by default, the portal session is closed then if it can be restored.
+pub fn set_keep_armed(keep: bool) {
+    KEEP_ARMED.store(keep, Ordering::SeqCst);
+}
+
+#[inline]
+pub fn is_keep_armed() -> bool {
+    KEEP_ARMED.load(Ordering::SeqCst)
+}
+
+#[inline]
+pub fn is_capture_paused() -> bool {
+    CAPTURE_PAUSED.load(Ordering::SeqCst)
+}
+
+// The states of the capture, each one implies the ones before:
+// - initialized, `is_initialized()`: the portal session and the displays are set up,
+//   from a successful `check_init()` to `clear()`. Nothing may be streamed.
+// - armed, `is_capture_armed()`: initialized with the streams stopped, from `arm_capture()` to
+//   `resume_capture()` or `clear()`. Exclusive with capturing.
+// - capturing, `is_capturing()`: a frame has been captured, until `pause_capture()`, `arm_capture()` or
+//   `clear()`. Resuming does not set it, the next captured frame does.
+// The tray shows the sharing indicator only while capturing.
+#[inline]
+pub fn is_initialized() -> bool {
+    get_cap_display_info().is_some()
+}
+
+#[inline]
+pub fn is_capturing() -> bool {
+    CAPTURING.load(Ordering::SeqCst)
+}
+
+// The leased capturers are paused or resumed by their leases on the next frame.
+fn set_idle_capturers_paused(paused: bool) {
+    let Some(cap_display_info) = get_cap_display_info() else {
+        return;
+    };
+    let slots: Vec<(usize, CapturerSlot)> = cap_display_info
+        .capturers
+        .lock()
+        .unwrap()
+        .slots
+        .iter()
+        .map(|(idx, slot)| (*idx, slot.clone()))
+        .collect();
+    for (idx, slot) in slots {
+        if let Some(capturer) = slot.lock().unwrap().as_mut() {
+            if let Err(e) = capturer.0.set_paused(paused) {
+                log::warn!(
+                    "Failed to set the wayland capturer of display {} paused: {}, {}",
+                    idx,
+                    paused,
+                    e
+                );
+            }
+        }
+    }
+}
+
+pub(super) fn get_capturer() -> ResultType<super::video_service::CapturerInfo> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    if let Some(cap_display_info) = get_cap_display_info() {
+        get_capturer_for(cap_display_info.current())
+    } else {
+        bail!(WaylandError::NotInitialized);
+    }
+}
+
+pub(super) fn get_capturer_for(idx: usize) -> ResultType<super::video_service::CapturerInfo> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    if let Some(cap_display_info) = get_cap_display_info() {
+        capturer_info_of(&cap_display_info, idx)
+    } else {
+        bail!(WaylandError::NotInitialized);
+    }
+}
+
+fn capturer_info_of(
+    cap_display_info: &CapDisplayInfo,
+    idx: usize,
+) -> ResultType<super::video_service::CapturerInfo> {
+    if get_source_type() == SourceType::WholeDesktop {
+        return new_desktop_capturer_info(cap_display_info);
+    }
+    check_display_idx(cap_display_info, idx)?;
+    let slot = cap_display_info
+        .capturers
+        .lock()
+        .unwrap()
+        .get_or_create(idx)?;
+    new_capturer_info(cap_display_info, idx, &slot)
+}
+
+// A viewer's own view of the capture, so the viewers can watch different displays.
+// The portal session and the displays are shared with the default session of the module functions.
+// The current display and the capturers are per session, each session has its own stream of a display,
+// so the viewers of the same display do not take the capturer from each other.
+// The whole desktop capturer is shared, see `SourceType::WholeDesktop`, one viewer holds it at a time.
+// The session is stale after `clear()` or a re-initialization, its methods return
+// `WaylandError::NotInitialized` then, create a new one.
+// Not used by the server yet, `Connection` and `video_service` still capture through the
+// default session, e.g. `set_current_display()` and `get_capturer_for()`.
+pub struct CaptureSession {
+    id: u64,
+    // Not kept alive by the session, the capturers are dropped by `clear()`.
+    info: std::sync::Weak<CapDisplayInfo>,
+    current: AtomicUsize,
+}
+
+impl CaptureSession {
+    // Initializes the capture if needed, the session starts on the current display of the default session.
+    pub async fn new() -> ResultType<Self> {
+        if is_x11() {
+            bail!(WaylandError::WrongBackend);
+        }
+        check_init().await?;
+        Self::of_current()
+    }
+
+    fn of_current() -> ResultType<Self> {
+        let Some(info) = get_cap_display_info() else {
+            bail!(WaylandError::NotInitialized);
+        };
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Ok(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::SeqCst),
+            info: Arc::downgrade(&info),
+            current: AtomicUsize::new(info.current()),
+        })
+    }
+
+    fn info(&self) -> ResultType<Arc<CapDisplayInfo>> {
+        match get_cap_display_info() {
+            // The weak reference keeps the allocation, so the address is not reused.
+            Some(info) if std::ptr::eq(Arc::as_ptr(&info), self.info.as_ptr()) => Ok(info),
+            _ => bail!(WaylandError::NotInitialized),
+        }
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.info().is_err()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    // Not persisted, unlike `set_current_display()`.
+    pub fn set_current(&self, idx: usize) -> ResultType<()> {
+        check_display_idx(&self.info()?, idx)?;
+        self.current.store(idx, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn display_num(&self) -> ResultType<usize> {
+        let info = self.info()?;
+        if get_source_type() == SourceType::WholeDesktop {
+            return Ok(1);
+        }
+        Ok(info.num)
+    }
+
+    pub fn primary(&self) -> ResultType<usize> {
+        Ok(get_primary_of(&self.info()?))
+    }
+
+    // The same as `get_displays()`, but flagged with the current display of this session.
+    pub fn displays(&self) -> ResultType<Vec<DisplayInfo>> {
+        let info = self.info()?;
+        let mut displays = build_displays(&info);
+        if get_source_type() == SourceType::PerOutput {
+            set_display_flags(&mut displays, get_primary_of(&info), self.current());
+        }
+        Ok(displays)
+    }
+
+    pub fn capturer(&self) -> ResultType<super::video_service::CapturerInfo> {
+        self.capturer_for(self.current())
+    }
+
+    // Fails if the capturer of the display is held by this session already.
+    pub fn capturer_for(&self, idx: usize) -> ResultType<super::video_service::CapturerInfo> {
+        let info = self.info()?;
+        if get_source_type() == SourceType::WholeDesktop {
+            return new_desktop_capturer_info(&info);
+        }
+        check_display_idx(&info, idx)?;
+        let Some(rect) = info.rect_for(idx) else {
+            bail!(WaylandError::IndexOutOfRange { idx, len: info.num });
+        };
+        let slot = info
+            .capturers
+            .lock()
+            .unwrap()
+            .get_or_create_for_session(self.id, idx, &rect)?;
+        new_capturer_info(&info, idx, &slot)
+    }
+}
+
+impl Drop for CaptureSession {
+    fn drop(&mut self) {
+        if let Some(info) = self.info.upgrade() {
+            info.capturers.lock().unwrap().remove_session(self.id);
+        }
+    }
+}
+
+// Recreate the capturer of a display, e.g. after pipewire renegotiated the stream.
+// The displays, uinput resolution and the portal session are kept, so the user is not prompted again.
+// The caller should drop the old `CapturerInfo` first, or it's dropped when the old lease is dropped.
+pub(super) fn force_reinit(idx: usize) -> ResultType<super::video_service::CapturerInfo> {
+    let res = force_reinit_(idx);
+    match &res {
+        Ok(_) => run_lifecycle_hooks("on_reinit", |hook| hook.on_reinit(idx)),
+        Err(e) => run_lifecycle_error_hooks(e),
+    }
+    res
+}
+
+fn force_reinit_(idx: usize) -> ResultType<super::video_service::CapturerInfo> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    let Some(cap_display_info) = get_cap_display_info() else {
+        bail!(WaylandError::NotInitialized);
+    };
+    check_display_idx(&cap_display_info, idx)?;
+    log::info!("Recreate the wayland capturer of display {}", idx);
+    let slot = {
+        let mut capturers = cap_display_info.capturers.lock().unwrap();
+        capturers.remove(idx);
+        reset_display_state(Some(idx));
+        capturers.get_or_create(idx)?
+    };
+    invalidate_displays();
+    new_capturer_info(&cap_display_info, idx, &slot)
+}
+
+// Stops the capture thread of `subscribe()` on drop.
+pub struct FrameSubscription {
+    stop: Arc<AtomicBool>,
+    wake: std::sync::mpsc::SyncSender<()>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl FrameSubscription {
+    // false if the capture thread exits, e.g. the capturer is recreated by `force_reinit()`.
+    pub fn is_active(&self) -> bool {
+        self.handle.as_ref().map_or(false, |h| !h.is_finished())
+    }
+}
+
+impl Drop for FrameSubscription {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let _ = self.wake.try_send(());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+// The push alternative of polling `frame()`, `callback` is called on a capture thread as pipewire
+// produces the frames, the thread sleeps while the screen is idle.
+// The capturer of the display is held by the subscription, `get_capturer_for(idx)` fails until it's dropped.
+pub fn subscribe<F>(idx: usize, mut callback: F) -> ResultType<FrameSubscription>
+where
+    F: FnMut(&PixelBuffer) + Send + 'static,
+{
+    let mut capturer = SendCapturer(get_capturer_for(idx)?.capturer);
+    // Only the latest wake up is kept, a frame is pulled for each of them.
+    let (wake, rx) = std::sync::mpsc::sync_channel::<()>(1);
+    let notify_wake = wake.clone();
+    let pushed = capturer.0.set_frame_notify(Some(Box::new(move || {
+        let _ = notify_wake.try_send(());
+    })))?;
+    if !pushed {
+        log::info!(
+            "The capturer of wayland display {} does not push the frames, poll every {:?}",
+            idx,
+            SUBSCRIBE_POLL_INTERVAL
+        );
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+    let handle = std::thread::Builder::new()
+        .name(format!("wayland-subscribe-{}", idx))
+        .spawn(move || {
+            // Moves the whole `SendCapturer` into the thread, not only its field.
+            let mut capturer = capturer;
+            loop {
+                let woken = if pushed {
+                    rx.recv().is_ok()
+                } else {
+                    !matches!(
+                        rx.recv_timeout(SUBSCRIBE_POLL_INTERVAL),
+                        Err(std::sync::mpsc::RecvTimeoutError::Disconnected)
+                    )
+                };
+                if !woken || thread_stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                match capturer.0.frame(Duration::ZERO) {
+                    Ok(Frame::PixelBuffer(f)) => callback(&f),
+                    Ok(Frame::Texture(_)) => {}
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock || is_paused_error(&e) => {}
+                    Err(e) => {
+                        log::error!("Stop the subscription of wayland display {}, {}", idx, e);
+                        break;
+                    }
+                }
+            }
+        })?;
+    Ok(FrameSubscription {
+        stop,
+        wake,
+        handle: Some(handle),
+    })
+}
+
+// Get the capturer of the current display only if it's ready.
+// Never initializes or creates a capturer, so the portal dialog is never shown.
+pub fn try_get_capturer() -> ResultType<Option<super::video_service::CapturerInfo>> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    let Some(cap_display_info) = get_cap_display_info() else {
+        return Ok(None);
+    };
+    let idx = cap_display_info.current();
+    check_display_idx(&cap_display_info, idx)?;
+    let Some(slot) = cap_display_info.capturers.lock().unwrap().get(idx) else {
+        return Ok(None);
+    };
+    new_capturer_info(&cap_display_info, idx, &slot).map(Some)
+}
+
+fn check_display_idx(cap_display_info: &CapDisplayInfo, idx: usize) -> ResultType<()> {
+    if idx >= cap_display_info.num || idx >= cap_display_info.rects.len() {
+        bail!(WaylandError::IndexOutOfRange {
+            idx,
+            len: cap_display_info.num
+        });
+    }
+    Ok(())
+}
+
+fn new_capturer_info(
+    cap_display_info: &CapDisplayInfo,
+    idx: usize,
+    slot: &CapturerSlot,
+) -> ResultType<super::video_service::CapturerInfo> {
+    let Some(rect) = cap_display_info.rect_for(idx) else {
+        bail!(WaylandError::IndexOutOfRange {
+            idx,
+            len: cap_display_info.num
+        });
+    };
+    let (width, height) = get_capture_size(rect.width, rect.height);
+    Ok(super::video_service::CapturerInfo {
+        origin: rect.origin,
+        width,
+        height,
+        ndisplay: cap_display_info.num,
+        current: idx,
+        scale: get_display_scale(cap_display_info, idx) * get_capture_factor(rect.width, width),
+        privacy_mode_id: 0,
+        _capturer_privacy_mode_id: 0,
+        capturer: Box::new(CapturerLease::take(slot, idx)?),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceType {
+    // A stream of each monitor, the peer switches between them.
+    PerOutput,
+    // The monitors are composed into one frame of the whole desktop.
+    WholeDesktop,
+}
+
+// `PerOutput` by default. `WholeDesktop` lets the user select all the monitors in the portal dialog,
+// and the capturers compose them. Takes effect on the next `check_init()`, the capture is cleared if changed.
+pub fn set_source_type(source_type: SourceType) {
+    let whole_desktop = source_type == SourceType::WholeDesktop;
+    if WHOLE_DESKTOP.swap(whole_desktop, Ordering::SeqCst) != whole_desktop {
+        log::info!("Set the wayland source type to {:?}", source_type);
+        scrap::wayland::pipewire::set_multiple_sources(whole_desktop);
+        clear();
+    }
+}
+
+pub fn get_source_type() -> SourceType {
+    if WHOLE_DESKTOP.load(Ordering::SeqCst) {
+        SourceType::WholeDesktop
+    } else {
+        SourceType::PerOutput
+    }
+}
+
+// The bounding rect of all the displays.
+fn desktop_rect(cap_display_info: &CapDisplayInfo) -> DisplayRect {
+    let (mut minx, mut miny, mut maxx, mut maxy) = (i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+    for ((x, y), w, h) in cap_display_info.rects.iter().cloned() {
+        minx = minx.min(x);
+        miny = miny.min(y);
+        maxx = maxx.max(x + w as i32);
+        maxy = maxy.max(y + h as i32);
+    }
+    if minx > maxx || miny > maxy {
+        return DisplayRect::from(((0, 0), 0, 0));
+    }
+    DisplayRect::from(((minx, miny), (maxx - minx) as usize, (maxy - miny) as usize))
+}
+
+fn desktop_display_info(cap_display_info: &CapDisplayInfo) -> DisplayInfo {
+    let rect = desktop_rect(cap_display_info);
+    DisplayInfo {
+        x: rect.origin.0,
+        y: rect.origin.1,
+        width: rect.width as _,
+        height: rect.height as _,
+        name: "Desktop".to_owned(),
+        online: true,
+        cursor_embedded: cursor_embedded(),
+        scale: 1.0,
+        ..Default::default()
+    }
+}
+
+fn new_desktop_capturer_info(
+    cap_display_info: &CapDisplayInfo,
+) -> ResultType<super::video_service::CapturerInfo> {
+    // Each display is scaled to fit the max size on its own, the frames can't be composed.
+    if let Some((w, h)) = scrap::wayland::pipewire::get_capture_max_size() {
+        bail!(
+            "The whole desktop can't be captured with the capture resolution {}x{}, call set_capture_resolution(None) first",
+            w,
+            h
+        );
+    }
+    let rect = desktop_rect(cap_display_info);
+    let mut leases = Vec::new();
+    for idx in 0..cap_display_info.num {
+        let Some(display_rect) = cap_display_info.rect_for(idx) else {
+            continue;
+        };
+        let slot = cap_display_info
+            .capturers
+            .lock()
+            .unwrap()
+            .get_or_create(idx)?;
+        leases.push((display_rect, CapturerLease::take(&slot, idx)?));
+    }
+    Ok(super::video_service::CapturerInfo {
+        origin: rect.origin,
+        width: rect.width,
+        height: rect.height,
+        ndisplay: 1,
+        current: 0,
+        scale: 1.0,
+        privacy_mode_id: 0,
+        _capturer_privacy_mode_id: 0,
+        capturer: Box::new(DesktopCapturer {
+            leases,
+            rect,
+            buffer: vec![0; rect.width * rect.height * 4],
+            pixfmt: None,
+        }),
+    })
+}
+
+// Composes the frames of all the displays into one frame of the whole desktop, see `set_source_type()`.
+// A display without a new frame keeps its last one, the areas not covered by any display are black.
+struct DesktopCapturer {
+    leases: Vec<(DisplayRect, CapturerLease)>,
+    rect: DisplayRect,
+    buffer: Vec<u8>,
+    // The format of the first frame, the frames of the other formats are skipped.
+    pixfmt: Option<scrap::Pixfmt>,
+}
+
+impl TraitCapturer for DesktopCapturer {
+    fn frame<'a>(&'a mut self, timeout: Duration) -> io::Result<Frame<'a>> {
+        let Self {
+            leases,
+            rect,
+            buffer,
+            pixfmt,
+        } = self;
+        let timeout = timeout / (leases.len() as u32).max(1);
+        let mut changed = false;
+        for (display, lease) in leases.iter_mut() {
+            let f = match lease.frame(timeout) {
+                Ok(Frame::PixelBuffer(f)) => f,
+                Ok(Frame::Texture(_)) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "Desktop capture does not support texture frames",
+                    ));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => return Err(e),
+            };
+            // The scaled frames, see `set_capture_resolution()`, can't be placed on the desktop.
+            if f.width() != display.width || f.height() != display.height {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!(
+                        "Desktop capture does not support the scaled frames, {}x{} of display {}x{}",
+                        f.width(),
+                        f.height(),
+                        display.width,
+                        display.height
+                    ),
+                ));
+            }
+            if *pixfmt.get_or_insert(f.pixfmt()) != f.pixfmt() {
+                continue;
+            }
+            let view = FrameView::of(&f)?;
+            let row = view.row_bytes();
+            let x = (display.origin.0 - rect.origin.0) as usize;
+            let y = (display.origin.1 - rect.origin.1) as usize;
+            for r in 0..view.height {
+                let dst = ((y + r) * rect.width + x) * 4;
+                buffer[dst..dst + row].copy_from_slice(view.row(r));
+            }
+            changed = true;
+        }
+        if !changed {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        Ok(Frame::PixelBuffer(PixelBuffer::new(
+            buffer,
+            pixfmt.unwrap_or(scrap::Pixfmt::BGRA),
+            rect.width,
+            rect.height,
+        )))
+    }
+}
+
+// A frame of `RoundRobinCapturer`, labeled with its display.
+pub struct LabeledFrame<'a> {
+    pub idx: usize,
+    pub name: String,
+    pub format: FrameFormat,
+    pub data: &'a [u8],
+}
+
+// Captures the displays in turn, see `capture_all_round_robin()`.
+pub struct RoundRobinCapturer {
+    interval: Duration,
+    next: usize,
+    last: Option<Instant>,
+    // The last frame of each display, returned again if the display has no new frame.
+    frames: HashMap<usize, (FrameFormat, Vec<u8>)>,
+}
+
+impl RoundRobinCapturer {
+    // Blocks until `interval` has passed since the last frame, then captures the next display.
+    // The displays whose capturers are in use or fail are skipped.
+    pub fn next_frame(&mut self, timeout: Duration) -> ResultType<LabeledFrame<'_>> {
+        if let Some(elapsed) = self.last.map(|last| last.elapsed()) {
+            if elapsed < self.interval {
+                std::thread::sleep(self.interval - elapsed);
+            }
+        }
+        self.last = Some(Instant::now());
+        let Some(cap_display_info) = get_cap_display_info() else {
+            bail!(WaylandError::NotInitialized);
+        };
+        let num = cap_display_info.num;
+        for _ in 0..num {
+            let idx = self.next % num;
+            self.next = (idx + 1) % num;
+            let mut info = match get_capturer_for(idx) {
+                Ok(info) => info,
+                Err(e) => {
+                    log::debug!("Skip wayland display {} in round robin, {}", idx, e);
+                    continue;
+                }
+            };
+            let (format, mut buf) = match self.frames.remove(&idx) {
+                Some((format, buf)) => (Some(format), buf),
+                None => (None, Vec::new()),
+            };
+            // `buf` is kept if there's no new frame.
+            let format = match info.capturer.as_mut().frame_into(&mut buf, timeout) {
+                Ok(format) => Some(format),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => format,
+                Err(e) => {
+                    log::debug!("Skip wayland display {} in round robin, {}", idx, e);
+                    None
+                }
+            };
+            drop(info);
+            if let Some(format) = format {
+                self.frames.insert(idx, (format, buf));
+                let name = cap_display_info
+                    .displays
+                    .get(idx)
+                    .map(|d| d.name.clone())
+                    .unwrap_or_default();
+                let (format, data) = &self.frames[&idx];
+                return Ok(LabeledFrame {
+                    idx,
+                    name,
+                    format: *format,
+                    data,
+                });
+            }
+        }
+        bail!("No wayland display has a frame");
+    }
+}
+
+// Cycles through the displays in one stream at a reduced rate, e.g. a dashboard of kiosks.
+// The cached capturers are shared with the video services, a display is leased only while it's captured.
+pub fn capture_all_round_robin(interval: Duration) -> ResultType<RoundRobinCapturer> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    if get_cap_display_info().is_none() {
+        bail!(WaylandError::NotInitialized);
+    }
+    Ok(RoundRobinCapturer {
+        interval,
+        next: 0,
+        last: None,
+        frames: HashMap::new(),
+    })
+}
+
+// Capture the display by its name in `DisplayInfo`, the index of a display may change after rebooting.
+// Fall back to the primary display if no display matches, see `set_preferred_primary()`.
+pub fn get_capturer_by_name(name: &str) -> ResultType<super::video_service::CapturerInfo> {
+    let Some(cap_display_info) = get_cap_display_info() else {
+        bail!(WaylandError::NotInitialized);
+    };
+    let idx = match cap_display_info
+        .displays
+        .iter()
+        .position(|d| !name.is_empty() && d.name == name)
+    {
+        Some(idx) => idx,
+        None => {
+            log::warn!(
+                "No wayland display named \"{}\", capture the primary display",
+                name
+            );
+            get_primary_of(&cap_display_info)
+        }
+    };
+    get_capturer_for(idx)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaylandWindow {
+    // The pipewire node id.
+    pub id: u64,
+    // The portal does not expose the window titles, this is the stream id.
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+}
+
+// The windows shared in the portal dialog.
+// The portal offers windows only if `SOURCE_TYPE_WINDOW` is requested by `set_source_types()`.
+// Empty if the portal does not support sharing windows, the caller should capture the displays.
+pub fn list_windows() -> Vec<WaylandWindow> {
+    let available = scrap::wayland::pipewire::get_available_source_types().unwrap_or_default();
+    if available & SOURCE_TYPE_WINDOW == 0 {
+        return vec![];
+    }
+    let Some(cap_display_info) = get_cap_display_info() else {
+        return vec![];
+    };
+    let capturers = cap_display_info.capturers.lock().unwrap();
+    capturers
+        .displays
+        .iter()
+        .filter_map(|d| match &d.0 {
+            Display::WAYLAND(w) if w.is_window() => Some(WaylandWindow {
+                id: w.node_id(),
+                name: w.name(),
+                width: w.width(),
+                height: w.height(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn get_window_capturer(id: u64) -> ResultType<super::video_service::CapturerInfo> {
+    let Some(cap_display_info) = get_cap_display_info() else {
+        bail!(WaylandError::NotInitialized);
+    };
+    let idx = cap_display_info
+        .capturers
+        .lock()
+        .unwrap()
+        .displays
+        .iter()
+        .position(|d| match &d.0 {
+            Display::WAYLAND(w) => w.is_window() && w.node_id() == id,
+            _ => false,
+        });
+    match idx {
+        Some(idx) => get_capturer_for(idx),
+        None => bail!("Failed to find the wayland window {}", id),
+    }
+}
+
+// Capture only a region of a display.
+// `x` and `y` are in the same coordinates as the display origins,
+// the region must be inside one display.
+pub fn get_capturer_region(
+    x: i32,
+    y: i32,
+    w: usize,
+    h: usize,
+) -> ResultType<super::video_service::CapturerInfo> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    if w == 0 || h == 0 {
+        bail!("Invalid capture region, ({}, {}), {}x{}", x, y, w, h);
+    }
+    let Some(cap_display_info) = get_cap_display_info() else {
+        bail!(WaylandError::NotInitialized);
+    };
+    let Some(idx) = cap_display_info
+        .rects
+        .iter()
+        .position(|(origin, width, height)| {
+            x >= origin.0
+                && y >= origin.1
+                && x as i64 + w as i64 <= origin.0 as i64 + *width as i64
+                && y as i64 + h as i64 <= origin.1 as i64 + *height as i64
+        })
+    else {
+        bail!(
+            "Capture region ({}, {}), {}x{} is out of the displays {:?}",
+            x,
+            y,
+            w,
+            h,
+            &cap_display_info.rects
+        );
+    };
+    let Some(DisplayRect {
+        origin,
+        width: display_width,
+        height: display_height,
+    }) = cap_display_info.rect_for(idx)
+    else {
+        bail!(WaylandError::IndexOutOfRange {
+            idx,
+            len: cap_display_info.num
+        });
+    };
+    let slot = cap_display_info
+        .capturers
+        .lock()
+        .unwrap()
+        .get_or_create(idx)?;
+    // The region is in the frames, which may be scaled down, see `set_capture_resolution()`.
+    let factor = get_capture_factor(
+        display_width,
+        get_capture_size(display_width, display_height).0,
+    );
+    let scaled = |v: usize| (v as f64 * factor) as usize;
+    let region = (
+        scaled((x - origin.0) as usize),
+        scaled((y - origin.1) as usize),
+        scaled(w).max(1),
+        scaled(h).max(1),
+    );
+    Ok(super::video_service::CapturerInfo {
+        origin: (x, y),
+        width: region.2,
+        height: region.3,
+        ndisplay: cap_display_info.num,
+        current: idx,
+        scale: get_display_scale(&cap_display_info, idx) * factor,
+        privacy_mode_id: 0,
+        _capturer_privacy_mode_id: 0,
+        capturer: Box::new(RegionCapturer {
+            inner: CapturerLease::take(&slot, idx)?,
+            region,
+            buffer: Vec::new(),
+        }),
+    })
+}
+
+// See `scrap::wayland::pipewire::CURSOR_MODE_*`.
+// The portal session is recreated, because the cursor mode is selected on starting the session.
+pub fn set_cursor_mode(mode: u32) {
+    if scrap::wayland::pipewire::get_cursor_mode() == mode {
+        return;
+    }
+    scrap::wayland::pipewire::set_cursor_mode(mode);
+    clear();
+    scrap::wayland::pipewire::close_session();
+}
+
+// The same as the X11 cursor API.
+// `pipewiresrc` does not expose the cursor metadata of the buffers,
+// so the cursor shape is read by XFixes through XWayland in the metadata mode.
+pub fn get_cursor() -> ResultType<Option<u64>> {
+    if scrap::wayland::pipewire::get_cursor_mode() != scrap::wayland::pipewire::CURSOR_MODE_METADATA
+    {
+        return Ok(None);
+    }
+    crate::platform::linux::get_cursor()
+}
+
+pub fn get_cursor_data(hcursor: u64) -> ResultType<CursorData> {
+    crate::platform::linux::get_cursor_data(hcursor)
+}
+
+// Force the portal to prompt the user on the next capture, e.g. to change the shared screen.
+pub fn clear_restore_token() {
+    scrap::wayland::pipewire::clear_restore_token();
+    clear();
+    scrap::wayland::pipewire::close_session();
+}
+
+// For the "Choose another screen" button.
+// Returns after the user selects the new source in the portal dialog.
+// The old capturers in use return errors on `frame()`, then the video service gets the new ones.
+pub async fn reset_and_reprompt() -> ResultType<()> {
+    if is_x11() {
+        bail!(WaylandError::WrongBackend);
+    }
+    clear_restore_token();
+    INIT_STATE.lock().await.cancelled_at = None;
+    check_init().await
+}
+
+// A translation key for the capture errors, it's translated by the UI.
+pub fn common_get_error() -> String {
+    if unmet_distro_requirement().is_some() {
+        return "".to_owned();
+    }
+    // The capture error is more important, the peer can see nothing without capture.
+    LAST_ERROR
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|e| e.to_string())
+        .or_else(|| LAST_UINPUT_ERROR.lock().unwrap().clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scrap::Pixfmt;
+
+    // The tests share `CAP_DISPLAY_INFO` and `LAST_ERROR`.
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    // See `get_preferred_primary()`.
+    pub(super) static PREFERRED_PRIMARY: Mutex<Option<usize>> = Mutex::new(None);
+
+    fn lock_test() -> std::sync::MutexGuard<'static, ()> {
+        TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(super) struct MockCapturer {
+        width: usize,
+        height: usize,
+        data: Vec<u8>,
+        // Times out like the stream of a static screen.
+        timeout: bool,
+        // The scrap error to return, mapped by `map_err_scrap()` like the pipewire capturer.
+        error: Option<String>,
+    }
+
+    impl MockCapturer {
+        pub(super) fn new(width: usize, height: usize) -> Self {
+            Self {
+                width,
+                height,
+                data: vec![0; width * height * 4],
+                timeout: false,
+                error: None,
+            }
+        }
+
+        fn with_error(err: &str) -> Self {
+            Self {
+                error: Some(err.to_owned()),
+                ..Self::new(1, 1)
+            }
+        }
+    }
+
+    impl TraitCapturer for MockCapturer {
+        fn frame<'a>(&'a mut self, _timeout: Duration) -> io::Result<Frame<'a>> {
+            if let Some(err) = &self.error {
+                return Err(map_err_scrap(err.clone()));
+            }
+            if self.timeout {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            Ok(Frame::PixelBuffer(PixelBuffer::new(
+                &self.data,
+                Pixfmt::BGRA,
+                self.width,
+                self.height,
+            )))
+        }
+    }
+
+    // Replace the displays of `check_init()` with mock displays, one mock capturer for each display.
+    fn set_mock_displays(
+        displays: Vec<(((i32, i32), usize, usize), MockCapturer)>,
+        primary: usize,
+    ) {
+        let num = displays.len();
+        let mut rects = Vec::new();
+        let mut capturers = CapturerCache::default();
+        for (idx, (rect, capturer)) in displays.into_iter().enumerate() {
+            rects.push(rect);
+            capturers.insert(
+                idx,
+                Box::new(capturer),
+                estimated_capturer_bytes(rect.1, rect.2),
+            );
+        }
+        *write_cap_display_info() = Some(Arc::new(CapDisplayInfo {
+            displays: vec![DisplayInfo::default(); num],
+            uinput_resolution: (0, 0, 0, 0),
+            has_physical_display: true,
+            rects,
+            num,
+            primary,
+            current: AtomicUsize::new(primary),
+            capturers: Mutex::new(capturers),
+        }));
+    }
+
+    pub(super) mod mock_uinput {
+        use super::*;
+
+        pub(in super::super) static USE_UINPUT: AtomicBool = AtomicBool::new(false);
+
+        lazy_static::lazy_static! {
+            pub(in super::super) static ref CALLS: Mutex<Vec<(&'static str, (i32, i32, i32, i32))>> =
+                Default::default();
+        }
+
+        fn record(call: &'static str, res: (i32, i32, i32, i32)) {
+            CALLS.lock().unwrap().push((call, res));
+        }
+
+        pub fn wayland_use_uinput() -> bool {
+            USE_UINPUT.load(Ordering::SeqCst)
+        }
+
+        pub async fn setup_uinput(minx: i32, maxx: i32, miny: i32, maxy: i32) -> ResultType<()> {
+            record("setup", (minx, maxx, miny, maxy));
+            Ok(())
+        }
+
+        pub async fn update_mouse_resolution(
+            minx: i32,
+            maxx: i32,
+            miny: i32,
+            maxy: i32,
+        ) -> ResultType<()> {
+            record("update", (minx, maxx, miny, maxy));
+            Ok(())
+        }
+    }
+
+    // Clears the capture state on drop, so a failed test does not leave its mock displays to the others.
+    struct MockDisplaysGuard;
+
+    impl Drop for MockDisplaysGuard {
+        fn drop(&mut self) {
+            clear();
+        }
+    }
+
+    // Runs `f` with one 1920x1080 mock display, which is cleared afterwards.
+    // The leases taken in `f` are dropped before clearing.
+    fn with_mock_display<T>(f: impl FnOnce() -> T) -> T {
+        set_mock_displays(
+            vec![(((0, 0), 1920, 1080), MockCapturer::new(1920, 1080))],
+            0,
+        );
+        let _guard = MockDisplaysGuard;
+        f()
+    }
+
+    fn expected_error(e: WaylandCaptureError) -> WaylandCaptureError {
+        match unmet_distro_requirement() {
+            Some(req) => WaylandCaptureError::DistroTooOld(req.message),
+            None => e,
+        }
+    }
+
+    #[test]
+    fn test_get_uinput_resolution() {
+        let single = [((0, 0), 1920, 1080)];
+        assert_eq!(
+            get_uinput_resolution(&single, Some("3840 x 1080,".to_owned())),
+            (0, 3840, 0, 1080)
+        );
+        // The xrandr result is smaller than the display.
+        assert_eq!(
+            get_uinput_resolution(&[((1920, 0), 1920, 1080)], Some("1920 x 1080,".to_owned())),
+            (0, 3840, 0, 1080)
+        );
+        assert_eq!(get_uinput_resolution(&single, None), (0, 1920, 0, 1080));
+        assert_eq!(
+            get_uinput_resolution(&single, Some("invalid".to_owned())),
+            (0, 1920, 0, 1080)
+        );
+        assert_eq!(
+            get_uinput_resolution(&single, Some("".to_owned())),
+            (0, 1920, 0, 1080)
+        );
+    }
+
+    #[test]
+    fn test_get_uinput_resolution_multi_monitor() {
+        // The secondary display is on the left of the primary one.
+        let rects = [((0, 0), 1920, 1080), ((-1920, 0), 1920, 1080)];
+        let (minx, maxx, miny, maxy) = get_uinput_resolution(&rects, None);
+        for ((x, y), w, h) in rects {
+            assert!(minx <= x && x + w as i32 <= maxx);
+            assert!(miny <= y && y + h as i32 <= maxy);
+        }
+        assert_eq!((minx, maxx, miny, maxy), (-1920, 1920, 0, 1080));
+        // The xrandr desktop size starts from the left-most display.
+        assert_eq!(
+            get_uinput_resolution(&rects, Some("3840 x 1080,".to_owned())),
+            (-1920, 1920, 0, 1080)
+        );
+        let rects = [((0, 0), 2560, 1440), ((2560, -360), 1920, 1080)];
+        assert_eq!(get_uinput_resolution(&rects, None), (0, 4480, -360, 1440));
+    }
+
+    #[test]
+    fn test_uinput_resolution_is_kept_after_init() {
+        let _lock = lock_test();
+        let rt = hbb_common::tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        mock_uinput::USE_UINPUT.store(true, Ordering::SeqCst);
+        mock_uinput::CALLS.lock().unwrap().clear();
+        UINPUT_CREATED.store(false, Ordering::SeqCst);
+        let res = (0, 1920, 0, 1080);
+        with_mock_display(|| {
+            let mut info = write_cap_display_info().take().unwrap();
+            Arc::get_mut(&mut info).unwrap().uinput_resolution = res;
+            *write_cap_display_info() = Some(info);
+            rt.block_on(set_conn_input_enabled(1, true));
+            // `check_init()` applies the cached resolution on every call.
+            rt.block_on(check_init()).unwrap();
+            rt.block_on(check_init()).unwrap();
+            rt.block_on(set_conn_input_enabled(1, false));
+        });
+        assert_eq!(
+            *mock_uinput::CALLS.lock().unwrap(),
+            vec![("setup", res), ("update", res), ("update", res)]
+        );
+        assert_eq!(get_cached_uinput_resolution(), None);
+        mock_uinput::USE_UINPUT.store(false, Ordering::SeqCst);
+        UINPUT_CREATED.store(false, Ordering::SeqCst);
+        UINPUT_READY.store(false, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_mock_displays() {
+        let _lock = lock_test();
+        set_mock_displays(
+            vec![
+                (((0, 0), 1920, 1080), MockCapturer::new(1920, 1080)),
+                (((1920, 0), 1280, 720), MockCapturer::new(1280, 720)),
+            ],
+            1,
+        );
+        assert_eq!(get_primary().unwrap(), 1);
+        assert_eq!(get_display_num().unwrap(), 2);
+
+        let mut info = get_capturer().unwrap();
+        assert_eq!(
+            (info.origin, info.width, info.height),
+            ((1920, 0), 1280, 720)
+        );
+        assert_eq!((info.ndisplay, info.current), (2, 1));
+        match info.capturer.frame(Duration::from_millis(10)).unwrap() {
+            Frame::PixelBuffer(f) => assert_eq!((f.width(), f.height()), (1280, 720)),
+            _ => panic!("unexpected frame"),
+        }
+        // The capturer is leased until `info` is dropped.
+        assert!(get_capturer().is_err());
+        assert!(get_capturer_for(0).is_ok());
+        assert!(get_capturer_for(2).is_err());
+
+        // The frames of the leased capturer are discarded after `clear()`.
+        clear();
+        assert!(get_primary().is_err());
+        assert!(get_display_num().is_err());
+        assert!(get_capturer().is_err());
+        assert!(info.capturer.frame(Duration::from_millis(10)).is_err());
+    }
+
+    #[test]
+    fn test_map_err_scrap() {
+        let _lock = lock_test();
+        for (err, expected) in [
+            (
+                "User cancelled the screen sharing request.",
+                WaylandCaptureError::UserCancelled,
+            ),
+            (
+                "no element \"pipewiresrc\"",
+                WaylandCaptureError::PipewireDown,
+            ),
+        ] {
+            set_mock_displays(vec![(((0, 0), 1, 1), MockCapturer::with_error(err))], 0);
+            let mut info = get_capturer().unwrap();
+            let e = info
+                .capturer
+                .frame(Duration::from_millis(10))
+                .err()
+                .unwrap();
+            let expected = expected_error(expected);
+            assert_eq!(WaylandCaptureError::from_io_error(&e), Some(&expected));
+            assert_eq!(LAST_ERROR.lock().unwrap().as_ref(), Some(&expected));
+            drop(info);
+            clear();
+        }
+    }
+
+    #[test]
+    fn test_no_user_session() {
+        assert!(is_dbus_error(
+            "org.freedesktop.DBus.Error.NoServer: Failed to connect to socket"
+        ));
+        assert!(!is_dbus_error("User cancelled the screen sharing request."));
+        assert!(!use_user_session_bus(Some(u32::MAX)));
+        assert_eq!(scrap::wayland::pipewire::get_session_bus_address(), None);
+        assert_eq!(
+            WaylandCaptureError::NoUserSession.to_string(),
+            SCRAP_NO_USER_SESSION
+        );
+        assert!(!WaylandCaptureError::NoUserSession.is_retryable());
+    }
+
+    #[test]
+    fn test_reprompt_cooldown() {
+        let _lock = lock_test();
+        assert_eq!(reprompt_cooldown_left(None), None);
+        let cancelled_at = Instant::now();
+        assert!(reprompt_cooldown_left(Some(cancelled_at)).is_some());
+        set_reprompt_cooldown(Duration::ZERO);
+        assert_eq!(reprompt_cooldown_left(Some(cancelled_at)), None);
+        set_reprompt_cooldown(Duration::from_secs(1));
+        assert_eq!(
+            reprompt_cooldown_left(Some(cancelled_at - Duration::from_secs(2))),
+            None
+        );
+        set_reprompt_cooldown(DEFAULT_REPROMPT_COOLDOWN);
+    }
+
+    #[test]
+    fn test_clone_init_error() {
+        let e: hbb_common::anyhow::Error =
+            io::Error::new(io::ErrorKind::Other, WaylandCaptureError::UserCancelled).into();
+        let cloned = clone_init_error(&e);
+        assert_eq!(
+            cloned
+                .downcast_ref::<io::Error>()
+                .and_then(WaylandCaptureError::from_io_error),
+            Some(&WaylandCaptureError::UserCancelled)
+        );
+        let e = hbb_common::anyhow::anyhow!("other").context("Failed to create capturer");
+        assert_eq!(
+            clone_init_error(&e).to_string(),
+            "Failed to create capturer: other"
+        );
+    }
+
+    #[test]
+    fn test_backend_from_desktop() {
+        assert_eq!(
+            DisplayBackend::from_desktop("ubuntu:GNOME"),
+            DisplayBackend::WaylandGnome
+        );
+        assert_eq!(
+            DisplayBackend::from_desktop("KDE"),
+            DisplayBackend::WaylandKde
+        );
+        assert_eq!(
+            DisplayBackend::from_desktop("sway"),
+            DisplayBackend::WaylandWlroots
+        );
+        assert_eq!(DisplayBackend::from_desktop(""), DisplayBackend::Unknown);
+    }
+
+    #[test]
+    fn test_mismatched_displays() {
+        let _lock = lock_test();
+        with_mock_display(|| {
+            let mut info = write_cap_display_info().take().unwrap();
+            let info_mut = Arc::get_mut(&mut info).unwrap();
+            // A display disappeared between getting the number and the rects.
+            info_mut.num = 2;
+            *info_mut.current.get_mut() = 1;
+            assert!(info.validate().is_err());
+            *write_cap_display_info() = Some(info);
+            assert!(get_capturer().is_err());
+            assert!(get_capturer_for(1).is_err());
+        });
+    }
+
+    #[test]
+    fn test_rebuild_rects() {
+        let _lock = lock_test();
+        // The primary display is the last one enumerated.
+        set_mock_displays(
+            vec![
+                (((0, 0), 1920, 1080), MockCapturer::new(1920, 1080)),
+                (((1920, 0), 1280, 720), MockCapturer::new(1280, 720)),
+                (((-2560, 0), 2560, 1440), MockCapturer::new(2560, 1440)),
+            ],
+            2,
+        );
+        let mut info = write_cap_display_info().take().unwrap();
+        let info_mut = Arc::get_mut(&mut info).unwrap();
+        for (d, rect) in info_mut.displays.iter_mut().zip(info_mut.rects.iter()) {
+            (d.x, d.y, d.width, d.height) = (rect.0 .0, rect.0 .1, rect.1 as _, rect.2 as _);
+        }
+        // The rects are built from another display set.
+        info_mut.rects.truncate(2);
+        assert!(info_mut.validate().is_err());
+        info_mut.rebuild_rects();
+        assert!(info_mut.validate().is_ok());
+        *write_cap_display_info() = Some(info);
+        let info = get_capturer().unwrap();
+        assert_eq!(
+            (info.origin, info.width, info.height, info.current),
+            ((-2560, 0), 2560, 1440, 2)
+        );
+        drop(info);
+
+        let mut info = write_cap_display_info().take().unwrap();
+        let info_mut = Arc::get_mut(&mut info).unwrap();
+        info_mut.displays.truncate(2);
+        info_mut.rebuild_rects();
+        assert_eq!(
+            (info_mut.num, info_mut.primary, info_mut.current()),
+            (2, 0, 0)
+        );
+        assert!(info_mut.validate().is_ok());
+        clear();
+    }
+
+    #[test]
+    fn test_get_capturer_by_name() {
+        let _lock = lock_test();
+        set_mock_displays(
+            vec![
+                (((0, 0), 1920, 1080), MockCapturer::new(1920, 1080)),
+                (((1920, 0), 1280, 720), MockCapturer::new(1280, 720)),
+            ],
+            0,
+        );
+        let mut info = write_cap_display_info().take().unwrap();
+        let info_mut = Arc::get_mut(&mut info).unwrap();
+        info_mut.displays[0].name = "0".to_owned();
+        info_mut.displays[1].name = "1".to_owned();
+        *write_cap_display_info() = Some(info);
+        assert_eq!(get_capturer_by_name("1").unwrap().current, 1);
+        // Fall back to the primary display.
+        assert_eq!(get_capturer_by_name("DP-1").unwrap().current, 0);
+        assert_eq!(get_capturer_by_name("").unwrap().current, 0);
+        struct PreferredPrimaryGuard;
+        impl Drop for PreferredPrimaryGuard {
+            fn drop(&mut self) {
+                *PREFERRED_PRIMARY.lock().unwrap() = None;
+            }
+        }
+        let _guard = PreferredPrimaryGuard;
+        *PREFERRED_PRIMARY.lock().unwrap() = Some(1);
+        assert_eq!(get_capturer_by_name("DP-1").unwrap().current, 1);
+        clear();
+    }
+
+    #[test]
+    fn test_try_get_capturer() {
+        let _lock = lock_test();
+        clear();
+        assert!(try_get_capturer().unwrap().is_none());
+        with_mock_display(|| {
+            let info = try_get_capturer().unwrap().unwrap();
+            assert_eq!((info.width, info.height), (1920, 1080));
+            // The capturer is leased.
+            assert!(try_get_capturer().is_err());
+            drop(info);
+            assert!(try_get_capturer().unwrap().is_some());
+        });
+    }
+
+    #[test]
+    fn test_concurrent_clear() {
+        let _lock = lock_test();
+        for _ in 0..20 {
+            set_mock_displays(
+                vec![
+                    (((0, 0), 64, 64), MockCapturer::new(64, 64)),
+                    (((64, 0), 64, 64), MockCapturer::new(64, 64)),
+                ],
+                0,
+            );
+            let threads: Vec<_> = (0..16)
+                .map(|i| {
+                    std::thread::spawn(move || {
+                        for _ in 0..50 {
+                            if i % 2 == 0 {
+                                clear();
+                            } else if let Ok(mut info) = get_capturer() {
+                                // Fails if cleared after getting the capturer.
+                                let _ = info.capturer.frame(Duration::from_millis(1));
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for t in threads {
+                t.join().unwrap();
+            }
+            clear();
+            clear();
+            assert!(get_cap_display_info().is_none());
+        }
+    }
+
+    #[test]
+    fn test_version_lt() {
+        assert!(version_lt("3", "21"));
+        assert!(version_lt("9", "21"));
+        assert!(version_lt("20.04", "21"));
+        assert!(!version_lt("21.04", "21"));
+        assert!(!version_lt("22.10", "21"));
+        assert!(!version_lt("100", "21"));
+        // Malformed versions are never too old.
+        assert!(!version_lt("", "21"));
+        assert!(!version_lt("rolling", "21"));
+        assert!(!version_lt("20.x", "21"));
+        assert!(!version_lt("20..04", "21"));
+        assert!(!version_lt("-1", "21"));
+    }
+
+    #[test]
+    fn test_distro_requirement() {
+        assert!(version_lt("15.3", "15.4"));
+        assert!(!version_lt("15.4", "15.4"));
+
+        let msg = |name: &str, version: &str| {
+            find_unmet_distro_requirement(name, version).map(|r| r.message)
+        };
+        assert_eq!(msg("Ubuntu", "20.04"), Some(SCRAP_UBUNTU_HIGHER_REQUIRED));
+        assert_eq!(msg("Ubuntu", "22.04"), None);
+        assert_eq!(
+            msg("Fedora Linux", "33"),
+            Some(SCRAP_FEDORA_HIGHER_REQUIRED)
+        );
+        assert_eq!(
+            msg("Debian GNU/Linux", "10"),
+            Some(SCRAP_DEBIAN_HIGHER_REQUIRED)
+        );
+        assert_eq!(
+            msg("openSUSE Leap", "15.3"),
+            Some(SCRAP_OPENSUSE_HIGHER_REQUIRED)
+        );
+        assert_eq!(msg("openSUSE Tumbleweed", "20240101"), None);
+        assert_eq!(msg("Arch Linux", ""), None);
+    }
+
+    #[test]
+    fn test_unknown_distro() {
+        // `/etc/os-release` is missing, e.g. in a minimal container.
+        assert!(find_unmet_distro_requirement("", "").is_none());
+        assert_eq!(
+            unclassified_error("", "unknown error"),
+            WaylandCaptureError::Unavailable
+        );
+        assert_eq!(
+            unclassified_error(" ", "unknown error").to_string(),
+            SCRAP_WAYLAND_UNAVAILABLE
+        );
+        assert_eq!(
+            unclassified_error("Ubuntu", "unknown error"),
+            WaylandCaptureError::Other("unknown error".to_owned())
+        );
+        assert_eq!(
+            unclassified_error("Arch Linux", "unknown error"),
+            WaylandCaptureError::X11Required
+        );
+    }
+
+    #[test]
+    fn test_removed_capturer_is_not_put_back() {
+        let _lock = lock_test();
+        with_mock_display(|| {
+            let info = get_capturer().unwrap();
+            let cap_display_info = get_cap_display_info().unwrap();
+            cap_display_info.capturers.lock().unwrap().remove(0);
+            drop(info);
+            assert!(cap_display_info.capturers.lock().unwrap().get(0).is_none());
+            assert!(try_get_capturer().unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn test_frame_format() {
+        let _lock = lock_test();
+        with_mock_display(|| {
+            assert_eq!(frame_format(0), None);
+            let mut info = get_capturer().unwrap();
+            info.capturer.frame(Duration::from_millis(10)).unwrap();
+            let format = frame_format(0).unwrap();
+            assert_eq!(format.pixfmt, Pixfmt::BGRA);
+            assert_eq!((format.width, format.height), (1920, 1080));
+            assert_eq!(format.stride, 1920 * 4);
+            assert_eq!(format.fourcc(), "AR24");
+            assert_eq!(format.color, scrap::ColorInfo::default());
+        });
+        assert_eq!(frame_format(0), None);
+    }
+
+    #[test]
+    fn test_parse_colorimetry() {
+        use scrap::{
+            wayland::pipewire::parse_colorimetry, ColorInfo, ColorRange::*, ColorSpace::*,
+        };
+        let color = |space, range| {
+            Some(ColorInfo {
+                space,
+                range,
+                ..Default::default()
+            })
+        };
+        assert_eq!(parse_colorimetry("bt709"), color(BT709, Limited));
+        assert_eq!(parse_colorimetry("bt601"), color(BT601, Limited));
+        assert_eq!(parse_colorimetry("sRGB"), color(BT709, Full));
+        assert_eq!(parse_colorimetry("1:4:0:0"), color(BT601, Full));
+        assert_eq!(parse_colorimetry("2:6:0:0"), color(BT2020, Limited));
+        assert_eq!(parse_colorimetry("0:0:0:0"), None);
+        assert_eq!(parse_colorimetry("unknown"), None);
+        assert!(!parse_colorimetry("bt709").unwrap().is_hdr());
+    }
+
+    #[test]
+    fn test_parse_hdr() {
+        use scrap::{
+            wayland::pipewire::{parse_colorimetry, parse_hdr_metadata},
+            ColorTransfer, HdrMetadata,
+        };
+        let pq = parse_colorimetry("bt2100-pq").unwrap();
+        assert_eq!(pq.transfer, ColorTransfer::Pq);
+        assert!(pq.is_hdr());
+        assert_eq!(
+            parse_colorimetry("2:6:15:7").unwrap().transfer,
+            ColorTransfer::Hlg
+        );
+        assert_eq!(
+            parse_colorimetry("2:6:14:7").unwrap().transfer,
+            ColorTransfer::Pq
+        );
+        assert_eq!(
+            parse_hdr_metadata(
+                Some("35400:14600:8500:39850:6550:2300:15635:16450:10000000:1"),
+                Some("1000:400")
+            ),
+            Some(HdrMetadata {
+                mastering_luminance: Some((1, 10000000)),
+                content_light_level: Some((1000, 400)),
+            })
+        );
+        assert_eq!(
+            parse_hdr_metadata(None, Some("1000:400"))
+                .unwrap()
+                .mastering_luminance,
+            None
+        );
+        assert_eq!(parse_hdr_metadata(Some("invalid"), None), None);
+        assert_eq!(parse_hdr_metadata(None, None), None);
+    }
+
+    #[test]
+    fn test_frame_timestamp() {
+        let _lock = lock_test();
+        with_mock_display(|| {
+            assert_eq!(frame_timestamp(0), None);
+            let mut info = get_capturer().unwrap();
+            let mut last = None;
+            for _ in 0..3 {
+                info.capturer.frame(Duration::from_millis(10)).unwrap();
+                let ts = frame_timestamp(0);
+                assert!(ts > last);
+                last = ts;
+            }
+            // The pipewire timestamps may restart, but never go back.
+            assert_eq!(update_frame_timestamp(0, Some(0)), last.unwrap() + 1);
+        });
+        assert_eq!(frame_timestamp(0), None);
+    }
+
+    #[test]
+    fn test_capturer_cache_budget() {
+        let _lock = lock_test();
+        set_mock_displays(
+            (0..3)
+                .map(|i| (((i * 100, 0), 100, 100), MockCapturer::new(100, 100)))
+                .collect(),
+            0,
+        );
+        let bytes = estimated_capturer_bytes(100, 100);
+        assert_eq!(capture_stats().capturer_cache_bytes, bytes * 3);
+        let info = get_capturer_for(0).unwrap();
+        // The leased capturer of the streaming display is kept even if it exceeds the budget.
+        set_capturer_cache_budget_bytes(1);
+        assert_eq!(capture_stats().capturer_cache_bytes, bytes);
+        let cap_display_info = get_cap_display_info().unwrap();
+        let slots: Vec<usize> = cap_display_info
+            .capturers
+            .lock()
+            .unwrap()
+            .slots
+            .keys()
+            .cloned()
+            .collect();
+        assert_eq!(slots, vec![0]);
+        drop(info);
+        set_capturer_cache_budget_bytes(0);
+        clear();
+        assert_eq!(capture_stats().capturer_cache_bytes, 0);
+    }
+
+    #[test]
+    fn test_capturer_cache_keeps_leased() {
+        let _lock = lock_test();
+        set_mock_displays(
+            (0..5)
+                .map(|i| (((i * 100, 0), 100, 100), MockCapturer::new(100, 100)))
+                .collect(),
+            1,
+        );
+        let cap_display_info = get_cap_display_info().unwrap();
+        let cached = || {
+            let mut slots: Vec<usize> = cap_display_info
+                .capturers
+                .lock()
+                .unwrap()
+                .slots
+                .keys()
+                .cloned()
+                .collect();
+            slots.sort();
+            slots
+        };
+        // Display 0 is the least recently used one.
+        assert_eq!(cached(), vec![1, 2, 3, 4]);
+        // Display 1 is streaming, and is the least recently used one now.
+        let mut info = get_capturer_for(1).unwrap();
+        for idx in 2..5 {
+            drop(get_capturer_for(idx).unwrap());
+        }
+        // Create the capturer of display 0 again, the idle display 2 is evicted instead.
+        cap_display_info.capturers.lock().unwrap().insert(
+            0,
+            Box::new(MockCapturer::new(100, 100)),
+            estimated_capturer_bytes(100, 100),
+        );
+        assert_eq!(cached(), vec![0, 1, 3, 4]);
+        assert!(info.capturer.frame(Duration::from_millis(10)).is_ok());
+        drop(info);
+        clear();
+    }
+
+    #[test]
+    fn test_display_config_hash() {
+        let display = |name: &str, x: i32, width: i32| DisplayInfo {
+            name: name.to_owned(),
+            x,
+            width,
+            height: 1080,
+            ..Default::default()
+        };
+        let layout = vec![display("DP-1", 0, 1920), display("HDMI-1", 1920, 1920)];
+        let hash = config_hash_of(&layout);
+        // The order of the portal streams does not matter.
+        let swapped = vec![layout[1].clone(), layout[0].clone()];
+        assert_eq!(config_hash_of(&swapped), hash);
+        let resized = vec![display("DP-1", 0, 2560), display("HDMI-1", 2560, 1920)];
+        assert_ne!(config_hash_of(&resized), hash);
+        let renamed = vec![display("DP-2", 0, 1920), display("HDMI-1", 1920, 1920)];
+        assert_ne!(config_hash_of(&renamed), hash);
+
+        let _lock = lock_test();
+        with_mock_display(|| {
+            assert_eq!(
+                display_config_hash(),
+                Some(config_hash_of(&[DisplayInfo::default()]))
+            );
+        });
+        assert_eq!(display_config_hash(), None);
+    }
+
+    #[test]
+    fn test_cached_displays() {
+        let _lock = lock_test();
+        let displays = || {
+            vec![
+                (((0, 0), 1920, 1080), MockCapturer::new(1920, 1080)),
+                (((1920, 0), 1280, 720), MockCapturer::new(1280, 720)),
+            ]
+        };
+        assert!(get_cached_displays().is_none());
+        set_mock_displays(displays(), 0);
+        let cached = get_cached_displays().unwrap();
+        assert_eq!(cached.len(), 2);
+        assert!(Arc::ptr_eq(&cached, &get_cached_displays().unwrap()));
+        invalidate_displays();
+        let rebuilt = get_cached_displays().unwrap();
+        assert!(!Arc::ptr_eq(&cached, &rebuilt));
+        assert_eq!(*cached, *rebuilt);
+        // Not the list of the capture state before `clear()`.
+        clear();
+        assert!(get_cached_displays().is_none());
+        set_mock_displays(displays(), 0);
+        assert!(!Arc::ptr_eq(&rebuilt, &get_cached_displays().unwrap()));
+        clear();
+    }
+
+    #[test]
+    fn test_initial_display_follows_cursor() {
+        let rects = vec![((0, 0), 1920, 1080), ((-1280, 0), 1280, 1024)];
+        assert_eq!(display_under(&rects, Some((100, 100))), Some(0));
+        assert_eq!(display_under(&rects, Some((-1, 1023))), Some(1));
+        assert_eq!(display_under(&rects, Some((-1, 1024))), None);
+        assert_eq!(display_under(&rects, None), None);
+        // Off by default.
+        assert_eq!(initial_display_under_cursor(&rects), None);
+    }
+
+    #[test]
+    fn test_frame_view() {
+        // 2x2 BGRA with 4 bytes of padding per row.
+        let data: Vec<u8> = (0..24).collect();
+        let f = PixelBuffer::new(&data, scrap::Pixfmt::BGRA, 2, 2).with_stride(Some(12));
+        let view = FrameView::of(&f).unwrap();
+        assert_eq!((view.stride, view.row_bytes()), (12, 8));
+        assert_eq!(view.row(1), &data[12..20]);
+        // Shorter than `stride * height`.
+        let f = PixelBuffer::new(&data[..20], scrap::Pixfmt::BGRA, 2, 2).with_stride(Some(12));
+        assert!(FrameView::of(&f).is_err());
+        // The stride is shorter than a row.
+        let f = PixelBuffer::new(&data, scrap::Pixfmt::BGRA, 2, 2).with_stride(Some(6));
+        assert!(FrameView::of(&f).is_err());
+    }
+
+    #[test]
+    fn test_capture_session() {
+        let _lock = lock_test();
+        let displays = || {
+            vec![
+                (((0, 0), 1920, 1080), MockCapturer::new(1920, 1080)),
+                (((1920, 0), 1280, 720), MockCapturer::new(1280, 720)),
+            ]
+        };
+        set_mock_displays(displays(), 0);
+        let a = CaptureSession::of_current().unwrap();
+        let b = CaptureSession::of_current().unwrap();
+        b.set_current(1).unwrap();
+        assert!(b.set_current(2).is_err());
+        // Switching a session does not switch the others.
+        assert_eq!((a.current(), b.current()), (0, 1));
+        assert_eq!(get_current_display(), Some(0));
+        assert!(b.displays().unwrap()[1].is_current);
+        let info_a = a.capturer().unwrap();
+        let info_b = b.capturer().unwrap();
+        assert_eq!((info_a.current, info_b.current), (0, 1));
+        assert_eq!(b.display_num().unwrap(), 2);
+        // Each session has its own stream of the display, so does the default session.
+        let mut info_b0 = b.capturer_for(0).unwrap();
+        assert!(info_b0.capturer.frame(Duration::from_millis(10)).is_ok());
+        let info_default = get_capturer_for(0).unwrap();
+        // The display is held by the session already.
+        assert!(b.capturer_for(0).is_err());
+        drop(info_b0);
+        assert!(b.capturer_for(0).is_ok());
+        drop(info_default);
+        let cap_display_info = get_cap_display_info().unwrap();
+        drop(b);
+        let session_slots = |info: &CapDisplayInfo| {
+            let capturers = info.capturers.lock().unwrap();
+            let mut keys: Vec<(u64, usize)> = capturers.session_slots.keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        // The capturers of a dropped session are dropped.
+        assert_eq!(session_slots(&cap_display_info), vec![(a.id, 0)]);
+        drop(info_a);
+        drop(info_b);
+        drop(cap_display_info);
+        clear();
+        assert!(a.is_stale());
+        assert!(a.capturer().is_err());
+        set_mock_displays(displays(), 0);
+        assert!(a.is_stale());
+        clear();
+    }
+
+    #[test]
+    fn test_capture_sessions_independent_frames() {
+        let _lock = lock_test();
+        set_mock_displays(
+            vec![
+                (((0, 0), 1920, 1080), MockCapturer::new(1920, 1080)),
+                (((1920, 0), 1280, 720), MockCapturer::new(1280, 720)),
+            ],
+            0,
+        );
+        let a = CaptureSession::of_current().unwrap();
+        let b = CaptureSession::of_current().unwrap();
+        b.set_current(1).unwrap();
+        // Both sessions hold their capturers at the same time.
+        let mut info_a = a.capturer().unwrap();
+        let mut info_b = b.capturer().unwrap();
+        assert_eq!((info_a.current, info_b.current), (0, 1));
+        let timeout = Duration::from_millis(10);
+        for _ in 0..2 {
+            match info_a.capturer.frame(timeout).unwrap() {
+                Frame::PixelBuffer(f) => assert_eq!((f.width(), f.height()), (1920, 1080)),
+                _ => panic!("unexpected frame"),
+            }
+            match info_b.capturer.frame(timeout).unwrap() {
+                Frame::PixelBuffer(f) => assert_eq!((f.width(), f.height()), (1280, 720)),
+                _ => panic!("unexpected frame"),
+            }
+        }
+        // The default session is not affected by the sessions.
+        assert_eq!(get_current_display(), Some(0));
+        let mut info_default = get_capturer_for(1).unwrap();
+        assert!(info_default.capturer.frame(timeout).is_ok());
+        drop(info_default);
+        drop(info_a);
+        drop(info_b);
+        drop(a);
+        drop(b);
+        clear();
+    }
+
+    #[test]
+    fn test_screenshot_rgba() {
+        // 2x1 BGRx with 4 bytes of padding.
+        let data = [1, 2, 3, 0, 4, 5, 6, 0, 9, 9, 9, 9];
+        let f = PixelBuffer::new(&data, scrap::Pixfmt::BGRA, 2, 1);
+        let image = ImageBuffer::of(&FrameView::of(&f).unwrap()).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.data, vec![3, 2, 1, 0xff, 6, 5, 4, 0xff]);
+        let f = PixelBuffer::new(&data[..8], scrap::Pixfmt::RGBA, 2, 1);
+        let image = ImageBuffer::of(&FrameView::of(&f).unwrap()).unwrap();
+        assert_eq!(image.data, vec![1, 2, 3, 0xff, 4, 5, 6, 0xff]);
+        let f = PixelBuffer::new(&data[..8], scrap::Pixfmt::I420, 2, 1);
+        assert!(ImageBuffer::of(&FrameView::of(&f).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_select_not_failed() {
+        // The names may be empty, the node ids are unique.
+        let ids = [Some(41), Some(42), Some(43)];
+        assert_eq!(select_not_failed(&ids, 2, &[41]), Some(2));
+        assert_eq!(select_not_failed(&ids, 2, &[43]), Some(0));
+        assert_eq!(select_not_failed(&ids, 0, &[41, 42]), Some(2));
+        assert_eq!(select_not_failed(&ids, 0, &[41, 42, 43]), None);
+        // Not a pipewire node, never failed.
+        assert_eq!(select_not_failed(&[None, Some(42)], 0, &[42]), Some(0));
+    }
+
+    #[cfg(feature = "wayland-shm")]
+    #[test]
+    fn test_shm_publish() {
+        use hbb_common::libc;
+        let _lock = lock_test();
+        super::super::wayland_shm::set_enabled(true);
+        set_mock_displays(vec![(((0, 0), 4, 2), MockCapturer::new(4, 2))], 0);
+        let mut info = get_capturer().unwrap();
+        info.capturer.frame(Duration::from_millis(10)).unwrap();
+        let name = super::super::wayland_shm::shm_name(0).unwrap();
+        let cname = std::ffi::CString::new(name).unwrap();
+        unsafe {
+            let fd = libc::shm_open(cname.as_ptr(), libc::O_RDONLY, 0);
+            assert!(fd >= 0);
+            let mut stat: libc::stat = std::mem::zeroed();
+            assert_eq!(libc::fstat(fd, &mut stat), 0);
+            let size = stat.st_size as usize;
+            let ptr = libc::mmap(
+                std::ptr::null_mut(),
+                size,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            ) as *const u8;
+            libc::close(fd);
+            let u32_at = |offset: usize| std::ptr::read_volatile(ptr.add(offset) as *const u32);
+            let u64_at = |offset: usize| std::ptr::read_volatile(ptr.add(offset) as *const u64);
+            assert_eq!(u32_at(0), 0x5357_4452);
+            let (slots, slot_size) = (u32_at(8) as usize, u32_at(12) as usize);
+            let latest = u64_at(24);
+            assert_eq!(latest, 1);
+            let slot = 64 + (latest as usize % slots) * slot_size;
+            // Not being written, the frame of `latest`.
+            assert_eq!(u64_at(slot) % 2, 0);
+            assert_eq!(u64_at(slot + 8), latest);
+            assert_eq!((u32_at(slot + 16), u32_at(slot + 20)), (4, 2));
+            assert_eq!((u32_at(slot + 24), u32_at(slot + 32)), (16, 32));
+            drop(info);
+            clear();
+            // Closed and removed.
+            assert_eq!(u32_at(16), 1);
+            libc::munmap(ptr as _, size);
+            assert!(libc::shm_open(cname.as_ptr(), libc::O_RDONLY, 0) < 0);
+        }
+        super::super::wayland_shm::set_enabled(false);
+    }
+
+    #[test]
+    fn test_debug_dump() {
+        let _lock = lock_test();
+        clear();
+        assert!(debug_dump().contains("not initialized"));
+        set_mock_displays(
+            vec![
+                (((0, 0), 1920, 1080), MockCapturer::new(1920, 1080)),
+                (((1920, 0), 1280, 720), MockCapturer::new(1280, 720)),
+            ],
+            1,
+        );
+        let dump = debug_dump();
+        assert!(dump.contains("num: 2, primary: 1, current: 1"));
+        assert!(dump.contains("display 1:"));
+        assert!(dump.contains("capture stats:"));
+        clear();
+    }
+
+    #[test]
+    fn test_lifecycle_hooks() {
+        struct Hook {
+            name: &'static str,
+            events: Arc<Mutex<Vec<String>>>,
+        }
+
+        impl CaptureLifecycle for Hook {
+            fn on_clear(&self) -> ResultType<()> {
+                self.events
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} clear", self.name));
+                bail!("hook failure")
+            }
+
+            fn on_error(&self, err: &WaylandError) -> ResultType<()> {
+                self.events
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} error {:?}", self.name, err));
+                Ok(())
+            }
+        }
+
+        let _lock = lock_test();
+        let events: Arc<Mutex<Vec<String>>> = Default::default();
+        for name in ["first", "second"] {
+            register_lifecycle_hook(Hook {
+                name,
+                events: events.clone(),
+            });
+        }
+        with_mock_display(|| {
+            assert!(force_reinit(1).is_err());
+        });
+        // The failed hooks do not stop the others or the capture.
+        assert!(get_cap_display_info().is_none());
+        // Nothing to clear.
+        clear();
+        let err = WaylandError::IndexOutOfRange { idx: 1, len: 1 };
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                format!("first error {:?}", err),
+                format!("second error {:?}", err),
+                "first clear".to_owned(),
+                "second clear".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_frame_checksum() {
+        assert_eq!(sampled_crc32(b"123456789", 0), 0xCBF43926);
+        assert_eq!(sampled_crc32(b"123456789", 64), 0xCBF43926);
+        let mut data = vec![0u8; 1920 * 1080 * 4];
+        let sampled = sampled_crc32(&data, DEFAULT_FRAME_CHECKSUM_CHUNKS);
+        let whole = sampled_crc32(&data, 0);
+        data[0] = 1;
+        assert_ne!(sampled_crc32(&data, DEFAULT_FRAME_CHECKSUM_CHUNKS), sampled);
+        assert_ne!(sampled_crc32(&data, 0), whole);
+
+        let _lock = lock_test();
+        set_mock_displays(vec![(((0, 0), 64, 64), MockCapturer::new(64, 64))], 0);
+        let mut info = get_capturer().unwrap();
+        info.capturer.frame(Duration::from_millis(10)).unwrap();
+        assert_eq!(frame_checksum(0), None);
+        set_frame_checksum(true);
+        info.capturer.frame(Duration::from_millis(10)).unwrap();
+        assert_eq!(
+            frame_checksum(0),
+            Some(sampled_crc32(&[0u8; 64 * 64 * 4], 0))
+        );
+        set_frame_checksum(false);
+        assert_eq!(frame_checksum(0), None);
+        drop(info);
+        clear();
+    }
+
+    #[test]
+    fn test_poisoned_cap_display_info() {
+        let _lock = lock_test();
+        with_mock_display(|| {
+            let res = std::thread::spawn(|| {
+                let _guard = write_cap_display_info();
+                panic!("poison the lock");
+            })
+            .join();
+            assert!(res.is_err());
+            assert!(CAP_DISPLAY_INFO.is_poisoned());
+            assert!(get_cap_display_info().is_some());
+            assert!(get_capturer().is_ok());
+        });
+        assert!(get_cap_display_info().is_none());
+    }
+
+    #[test]
+    fn test_whole_desktop() {
+        let _lock = lock_test();
+        let displays = || {
+            let mut left = MockCapturer::new(32, 16);
+            left.data = vec![2; 32 * 16 * 4];
+            let mut right = MockCapturer::new(64, 32);
+            right.data = vec![1; 64 * 32 * 4];
+            vec![(((0, 0), 64, 32), right), (((-32, 8), 32, 16), left)]
+        };
+        set_mock_displays(displays(), 0);
+        set_source_type(SourceType::WholeDesktop);
+        // Changing the source type clears the capture.
+        assert!(get_cap_display_info().is_none());
+        set_mock_displays(displays(), 0);
+        assert_eq!(get_display_num().unwrap(), 1);
+        let mut info = get_capturer_for(1).unwrap();
+        assert_eq!((info.origin, info.width, info.height), ((-32, 0), 96, 32));
+        match info.capturer.frame(Duration::from_millis(10)).unwrap() {
+            Frame::PixelBuffer(f) => {
+                assert_eq!((f.width(), f.height()), (96, 32));
+                assert_eq!(f.data().len(), 96 * 32 * 4);
+                let pixel = |x: usize, y: usize| f.data()[(y * 96 + x) * 4];
+                // Not covered by any display.
+                assert_eq!(pixel(0, 0), 0);
+                assert_eq!(pixel(0, 8), 2);
+                assert_eq!(pixel(31, 23), 2);
+                assert_eq!(pixel(0, 24), 0);
+                assert_eq!(pixel(32, 0), 1);
+                assert_eq!(pixel(95, 31), 1);
+            }
+            _ => panic!("Unexpected texture frame"),
+        }
+        // All the displays are leased by the desktop capturer.
+        assert!(get_capturer_for(0).is_err());
+        drop(info);
+        set_capture_resolution(Some((48, 16)));
+        assert!(get_capturer_for(1).is_err());
+        set_capture_resolution(None);
+        // A frame not in the size of its display, e.g. scaled by pipewire.
+        set_mock_displays(vec![(((0, 0), 64, 32), MockCapturer::new(32, 16))], 0);
+        let mut info = get_capturer_for(1).unwrap();
+        let err = info
+            .capturer
+            .frame(Duration::from_millis(10))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        drop(info);
+        set_source_type(SourceType::PerOutput);
+        assert_eq!(get_source_type(), SourceType::PerOutput);
+        set_mock_displays(displays(), 0);
+        assert_eq!(get_display_num().unwrap(), 2);
+        clear();
+    }
+
+    #[test]
+    fn test_frame_seq() {
+        let _lock = lock_test();
+        with_mock_display(|| {
+            assert_eq!(frame_seq(0), None);
+            let mut info = get_capturer().unwrap();
+            for seq in 1..=3 {
+                info.capturer.frame(Duration::from_millis(10)).unwrap();
+                assert_eq!(frame_seq(0), Some(seq));
+            }
+        });
+        assert_eq!(frame_seq(0), None);
+    }
+
+    #[test]
+    fn test_frame_into() {
+        let _lock = lock_test();
+        set_mock_displays(
+            vec![(((0, 0), 3840, 2160), MockCapturer::new(3840, 2160))],
+            0,
+        );
+        let mut info = get_capturer().unwrap();
+        let mut buf = Vec::new();
+        let format = info
+            .capturer
+            .frame_into(&mut buf, Duration::from_millis(10))
+            .unwrap();
+        assert_eq!((format.width, format.height), (3840, 2160));
+        assert_eq!(buf.len(), 3840 * 2160 * 4);
+        // The buffer is not re-allocated for the following frames of the same size.
+        let ptr = buf.as_ptr();
+        for _ in 0..10 {
+            info.capturer
+                .frame_into(&mut buf, Duration::from_millis(10))
+                .unwrap();
+            assert_eq!(buf.as_ptr(), ptr);
+        }
+        drop(info);
+        clear();
+    }
+
+    #[test]
+    fn test_pause_capture() {
+        let _lock = lock_test();
+        reset_capture_stats();
+        with_mock_display(|| {
+            let mut info = get_capturer().unwrap();
+            pause_capture();
+            assert!(is_capture_paused());
+            let err = info
+                .capturer
+                .frame(Duration::from_millis(10))
+                .err()
+                .unwrap();
+            assert!(is_paused_error(&err));
+            // Pausing is neither a frame nor an error.
+            let stats = capture_stats();
+            assert_eq!((stats.frames, stats.errors), (0, 0));
+            resume_capture();
+            assert!(info.capturer.frame(Duration::from_millis(10)).is_ok());
+            pause_capture();
+        });
+        assert!(!is_capture_paused());
+    }
+
+    #[test]
+    fn test_screen_lock_refresh_throttled() {
+        let _lock = lock_test();
+        // Refreshed just now, `loginctl` is not run again.
+        *SCREEN_LOCK_CHECKED_AT.lock().unwrap() = Some(Instant::now());
+        SCREEN_LOCKED.store(true, Ordering::SeqCst);
+        assert!(update_screen_locked());
+        SCREEN_LOCKED.store(false, Ordering::SeqCst);
+        assert!(!update_screen_locked());
+        with_mock_display(|| {});
+        assert!(SCREEN_LOCK_CHECKED_AT.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_screen_locked() {
+        let _lock = lock_test();
+        reset_capture_stats();
+        with_mock_display(|| {
+            let mut info = get_capturer().unwrap();
+            SCREEN_LOCKED.store(true, Ordering::SeqCst);
+            let err = info
+                .capturer
+                .frame(Duration::from_millis(10))
+                .err()
+                .unwrap();
+            assert_eq!(
+                WaylandCaptureError::from_io_error(&err),
+                Some(&WaylandCaptureError::ScreenLocked)
+            );
+            assert!(is_paused_error(&err));
+            assert_eq!(capture_stats().errors, 0);
+            set_capture_while_locked(true);
+            assert!(info.capturer.frame(Duration::from_millis(10)).is_ok());
+            set_capture_while_locked(false);
+            SCREEN_LOCKED.store(false, Ordering::SeqCst);
+            assert!(info.capturer.frame(Duration::from_millis(10)).is_ok());
+        });
+    }
+
+    #[test]
+    fn test_is_capturing() {
+        let _lock = lock_test();
+        clear();
+        assert!(!is_initialized() && !is_capturing());
+        with_mock_display(|| {
+            assert!(is_initialized() && !is_capturing());
+            let mut info = get_capturer().unwrap();
+            info.capturer.frame(Duration::from_millis(10)).unwrap();
+            assert!(is_capturing());
+            pause_capture();
+            assert!(!is_capturing());
+            resume_capture();
+            assert!(!is_capturing());
+            info.capturer.frame(Duration::from_millis(10)).unwrap();
+            assert!(is_capturing());
+            arm_capture();
+            assert!(is_capture_armed() && !is_capturing());
+            resume_capture();
+            info.capturer.frame(Duration::from_millis(10)).unwrap();
+        });
+        assert!(!is_initialized() && !is_capturing());
+    }
+
+    #[test]
+    fn test_adaptive_skip() {
+        let _lock = lock_test();
+        reset_capture_stats();
+        with_mock_display(|| {
+            let mut info = get_capturer().unwrap();
+            // Skips up to 2 frames in a row.
+            set_adaptive_skip(Some(Arc::new(|ctx: &SkipContext| {
+                ctx.idx == 0 && ctx.consecutive_skips < 2
+            })));
+            for _ in 0..2 {
+                let err = info
+                    .capturer
+                    .frame(Duration::from_millis(10))
+                    .err()
+                    .unwrap();
+                assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+            }
+            assert!(info.capturer.frame(Duration::from_millis(10)).is_ok());
+            let stats = capture_stats();
+            assert_eq!((stats.frames, stats.would_block, stats.skipped), (1, 0, 2));
+            set_adaptive_skip(None);
+            assert!(info.capturer.frame(Duration::from_millis(10)).is_ok());
+            assert_eq!(capture_stats().skipped, 2);
+        });
+        reset_capture_stats();
+    }
+
+    #[test]
+    fn test_arm_capture() {
+        let _lock = lock_test();
+        clear();
+        // Nothing to arm.
+        arm_capture();
+        assert!(!is_capture_armed());
+        with_mock_display(|| {
+            arm_capture();
+            assert!(is_capture_armed() && is_capture_paused());
+            let mut info = get_capturer().unwrap();
+            let err = info
+                .capturer
+                .frame(Duration::from_millis(10))
+                .err()
+                .unwrap();
+            assert!(is_paused_error(&err));
+            resume_capture();
+            assert!(!is_capture_armed() && !is_capture_paused());
+            assert!(info.capturer.frame(Duration::from_millis(10)).is_ok());
+            arm_capture();
+        });
+        assert!(!is_capture_armed() && !is_capture_paused());
+    }
+
+    #[test]
+    fn test_frame_watchdog() {
+        let _lock = lock_test();
+        reset_capture_stats();
+        set_mock_displays(
+            vec![
+                (((0, 0), 64, 64), MockCapturer::new(64, 64)),
+                (
+                    ((64, 0), 64, 64),
+                    MockCapturer::with_error("pipewire stream error"),
+                ),
+            ],
+            0,
+        );
+        assert_eq!(last_frame_instant(), None);
+        let mut info = get_capturer_for(0).unwrap();
+        info.capturer.frame(Duration::from_millis(10)).unwrap();
+        drop(info);
+        assert!(capture_stats().last_good_frame_age.is_some());
+        assert!(last_frame_instant().unwrap() <= Instant::now());
+        let mut info = get_capturer_for(1).unwrap();
+        assert!(info.capturer.frame(Duration::from_millis(10)).is_err());
+        drop(info);
+
+        assert!(take_failing_displays(Duration::from_secs(60)).is_empty());
+        assert_eq!(take_failing_displays(Duration::ZERO), vec![1]);
+        // Taken, so the capturer is recreated only once.
+        assert!(take_failing_displays(Duration::ZERO).is_empty());
+
+        update_frame_failing::<()>(1, &Err(io::ErrorKind::Other.into()));
+        update_frame_failing(1, &Ok(()));
+        assert!(take_failing_displays(Duration::ZERO).is_empty());
+        update_frame_failing::<()>(1, &Err(io::ErrorKind::WouldBlock.into()));
+        assert!(take_failing_displays(Duration::ZERO).is_empty());
+        clear();
+    }
+
+    #[test]
+    fn test_display_rects() {
+        let _lock = lock_test();
+        // The second display is on the left of the primary one.
+        set_mock_displays(
+            vec![
+                (((0, 0), 1920, 1080), MockCapturer::new(1920, 1080)),
+                (((-1280, 0), 1280, 1024), MockCapturer::new(1280, 1024)),
+            ],
+            0,
+        );
+        let rects = get_display_rects().unwrap();
+        assert_eq!(rects, vec![((0, 0), 1920, 1080), ((-1280, 0), 1280, 1024)]);
+        assert_eq!(global_to_local(&rects, 100, 200), Some((0, 100, 200)));
+        assert_eq!(global_to_local(&rects, -1, 0), Some((1, 1279, 0)));
+        assert_eq!(global_to_local(&rects, -1280, 1023), Some((1, 0, 1023)));
+        assert_eq!(global_to_local(&rects, -1, 1024), None);
+        assert_eq!(global_to_local(&rects, 1920, 0), None);
+        clear();
+        assert!(get_display_rects().is_err());
+    }
+
+    #[test]
+    fn test_local_to_global() {
+        let _lock = lock_test();
+        let rects = vec![
+            ((0, 0), 1920, 1080),
+            ((1920, 0), 1280, 1024),
+            ((-1280, 0), 1280, 1024),
+        ];
+        // A point on the secondary monitor on the right.
+        assert_eq!(local_to_global(&rects, 1, 100, 200), Some((2020, 200)));
+        assert_eq!(local_to_global(&rects, 2, 0, 1023), Some((-1280, 1023)));
+        // Clamped into the display.
+        assert_eq!(local_to_global(&rects, 1, 1280, -1), Some((3199, 0)));
+        assert_eq!(local_to_global(&rects, 3, 0, 0), None);
+        for (idx, x, y) in [(0, 5, 6), (1, 100, 200), (2, 1279, 0)] {
+            let (gx, gy) = local_to_global(&rects, idx, x, y).unwrap();
+            assert_eq!(global_to_local(&rects, gx, gy), Some((idx, x, y)));
+        }
+
+        set_mock_displays(
+            rects
+                .iter()
+                .map(|&(o, w, h)| ((o, w, h), MockCapturer::new(w, h)))
+                .collect(),
+            0,
+        );
+        let (gx, gy) = display_point_to_uinput(1, 100, 200).unwrap();
+        assert_eq!((gx, gy), (2020, 200));
+        // Inside the uinput bounds.
+        let (minx, maxx, miny, maxy) = get_uinput_resolution(&rects, None);
+        assert_eq!((minx, maxx, miny, maxy), (-1280, 3200, 0, 1080));
+        assert!(gx >= minx && gx < maxx && gy >= miny && gy < maxy);
+        assert!(display_point_to_uinput(3, 0, 0).is_err());
+        clear();
+        assert!(display_point_to_uinput(0, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_session_type_changed() {
+        let _lock = lock_test();
+        with_mock_display(|| {
+            let mut info = get_capturer().unwrap();
+            info.capturer.frame(Duration::from_millis(10)).unwrap();
+            *LAST_ERROR.lock().unwrap() = Some(WaylandCaptureError::PipewireDown);
+            on_session_type_changed();
+            assert!(get_cap_display_info().is_none());
+            assert!(LAST_ERROR.lock().unwrap().is_none());
+            assert_eq!(frame_format(0), None);
+            // The capturer in use is stale.
+            assert!(info.capturer.frame(Duration::from_millis(10)).is_err());
+        });
+    }
+
+    #[test]
+    fn test_with_map_err() {
+        let _lock = lock_test();
+        fn raw(err: String) -> io::Error {
+            io::Error::new(io::ErrorKind::Other, err)
+        }
+        fn current() -> usize {
+            get_map_err().map(|f| f as usize).unwrap_or_default()
+        }
+        init();
+        let res = with_map_err(raw, || {
+            assert_eq!(current(), raw as usize);
+            1
+        });
+        assert_eq!(res, 1);
+        assert_eq!(current(), map_err_scrap as usize);
+        // Restored on panic.
+        let res = std::panic::catch_unwind(|| {
+            with_map_err(raw, || panic!("diagnostics failed"));
+        });
+        assert!(res.is_err());
+        assert_eq!(current(), map_err_scrap as usize);
+
+        // The mapper of the embedder is kept by `init()`.
+        install_error_mapper(Some(raw));
+        init();
+        assert_eq!(current(), raw as usize);
+        install_error_mapper(None);
+        assert_eq!(current(), map_err_scrap as usize);
+    }
+
+    #[test]
+    fn test_set_display_flags() {
+        let mut displays = vec![DisplayInfo::default(); 3];
+        set_display_flags(&mut displays, 1, 2);
+        let flags: Vec<_> = displays
+            .iter()
+            .map(|d| (d.is_primary, d.is_current))
+            .collect();
+        assert_eq!(flags, vec![(false, false), (true, false), (false, true)]);
+    }
+
+    #[test]
+    fn test_wayland_error() {
+        let _lock = lock_test();
+        clear();
+        let e = get_primary().unwrap_err();
+        assert_eq!(
+            WaylandError::from_error(&e),
+            Some(&WaylandError::NotInitialized)
+        );
+        with_mock_display(|| {
+            let e = get_capturer_for(1).err().unwrap();
+            assert_eq!(
+                WaylandError::from_error(&e),
+                Some(&WaylandError::IndexOutOfRange { idx: 1, len: 1 })
+            );
+            assert_eq!(e.to_string(), "Failed to get display 1, displays len: 1");
+        });
+    }
+
+    #[test]
+    fn test_conn_input_enabled() {
+        let _lock = lock_test();
+        let rt = hbb_common::tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert!(!is_input_enabled());
+        rt.block_on(set_conn_input_enabled(1, true));
+        rt.block_on(set_conn_input_enabled(2, true));
+        assert!(is_input_enabled());
+        // The other connection still controls the keyboard and mouse.
+        rt.block_on(set_conn_input_enabled(1, false));
+        assert!(is_input_enabled());
+        rt.block_on(set_conn_input_enabled(2, false));
+        assert!(!is_input_enabled());
+    }
+
+    #[test]
+    fn test_check_init_blocking_in_runtime() {
+        let rt = hbb_common::tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert!(rt.block_on(async { check_init_blocking() }).is_err());
+    }
+
+    #[test]
+    fn test_selftest_report() {
+        let mut data = vec![0u8; 1920 * 1080 * 4];
+        assert!(is_black_frame(&data));
+        data[1920 * 540 * 4..].fill(255);
+        assert!(!is_black_frame(&data));
+
+        let mut report = SelfTestReport::default();
+        assert!(!report.passed());
+        assert_eq!(report.stage("init", Instant::now(), Ok(1)), Some(1));
+        assert!(report.passed());
+        let err = Err(WaylandError::NotInitialized.into());
+        assert_eq!(report.stage::<()>("frames", Instant::now(), err), None);
+        assert!(!report.passed());
+        let summary = report.to_string();
+        assert!(summary.contains("[ OK ] init"));
+        assert!(summary.contains("[FAIL] frames"));
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let _lock = lock_test();
+        with_mock_display(|| {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let subscription = subscribe(0, move |f| {
+                let _ = tx.send((f.width(), f.height()));
+            })
+            .unwrap();
+            assert!(subscription.is_active());
+            // The mock capturer does not push the frames, it's polled.
+            assert_eq!(
+                rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+                (1920, 1080)
+            );
+            assert!(get_capturer_for(0).is_err());
+            drop(subscription);
+            assert!(get_capturer_for(0).is_ok());
+        });
+    }
+
+    #[test]
+    fn test_enumerate_displays() {
+        let _lock = lock_test();
+        set_mock_displays(
+            vec![
+                (((0, 0), 1920, 1080), MockCapturer::new(1920, 1080)),
+                (((1920, 0), 1280, 720), MockCapturer::new(1280, 720)),
+            ],
+            1,
+        );
+        let (displays, primary) = enumerate_displays().unwrap();
+        assert_eq!(displays.len(), 2);
+        assert_eq!(primary, get_primary().unwrap());
+        assert!(displays[primary].is_primary);
+        assert!(!displays[0].is_primary);
+        clear();
+    }
+
+    #[test]
+    fn test_buffer_count() {
+        assert_eq!(set_buffer_count(Some(0)), MIN_BUFFER_COUNT);
+        assert_eq!(set_buffer_count(Some(100)), MAX_BUFFER_COUNT);
+        assert_eq!(set_buffer_count(Some(4)), 4);
+        assert_eq!(scrap::wayland::pipewire::get_buffer_count(), 4);
+        assert_eq!(set_buffer_count(None), 0);
+        assert_eq!(scrap::wayland::pipewire::get_buffer_count(), 0);
+    }
+
+    #[test]
+    fn test_format_preference() {
+        use scrap::Pixfmt::*;
+        assert_eq!(set_format_preference(&[RGBA]), vec![RGBA, BGRA]);
+        assert_eq!(set_format_preference(&[I420, RGBA, RGBA]), vec![RGBA, BGRA]);
+        assert_eq!(set_format_preference(&[I420]), vec![BGRA, RGBA]);
+        assert_eq!(set_format_preference(&[]), vec![BGRA, RGBA]);
+    }
+
+    #[test]
+    fn test_capture_resolution() {
+        let _lock = lock_test();
+        set_mock_displays(
+            vec![
+                (((0, 0), 3840, 2160), MockCapturer::new(3840, 2160)),
+                (((3840, 0), 1280, 720), MockCapturer::new(1280, 720)),
+            ],
+            0,
+        );
+        let generation = capture_size_generation();
+        update_frozen_state(0, None);
+        set_capture_resolution(Some((1920, 1080)));
+        assert_eq!(capture_size_generation(), generation + 1);
+        // The recreated capturers do not inherit the frame state.
+        assert!(FROZEN_STATE.lock().unwrap().is_empty());
+        // The cached capturers are dropped, so no capturer is ready.
+        assert!(try_get_capturer().unwrap().is_none());
+        assert_eq!(capture_scale_factor(0), Some(0.5));
+        assert_eq!(capture_scale_factor(1), Some(1.0));
+        set_capture_resolution(None);
+        assert_eq!(capture_scale_factor(0), Some(1.0));
+        clear();
+        assert_eq!(
+            scrap::wayland::pipewire::fit_capture_size((2560, 1600), Some((1920, 1080))),
+            (1728, 1080)
+        );
+    }
+
+    #[test]
+    fn test_capture_region() {
+        let _lock = lock_test();
+        // The first pixel of each display is white.
+        let mock = |width, height| {
+            let mut capturer = MockCapturer::new(width, height);
+            capturer.data[..4].fill(255);
+            capturer
+        };
+        set_mock_displays(
+            vec![
+                (((0, 0), 1920, 1080), mock(1920, 1080)),
+                (((1920, 0), 1280, 720), mock(1280, 720)),
+            ],
+            0,
+        );
+        for (region, current) in [((0, 0, 1920, 1080), 0), ((1920, 0, 640, 360), 1)] {
+            let mut info = get_capturer_region(region.0, region.1, region.2, region.3).unwrap();
+            assert_eq!(info.origin, (region.0, region.1));
+            assert_eq!(
+                (info.width, info.height, info.current),
+                (region.2, region.3, current)
+            );
+            match info.capturer.frame(Duration::from_millis(10)).unwrap() {
+                Frame::PixelBuffer(f) => {
+                    assert_eq!((f.width(), f.height()), (region.2, region.3));
+                    assert_eq!(&f.data()[..4], &[255; 4]);
+                }
+                _ => panic!("unexpected frame"),
+            }
+        }
+        // Not inside one display.
+        assert!(get_capturer_region(1280, 0, 1280, 720).is_err());
+        assert!(get_capturer_region(0, 0, 0, 720).is_err());
+        clear();
+    }
+
+    #[test]
+    fn test_capture_region_scaled() {
+        let _lock = lock_test();
+        set_capture_resolution(Some((1920, 1080)));
+        // The frames of the 4K display are scaled down by half.
+        set_mock_displays(
+            vec![(((0, 0), 3840, 2160), MockCapturer::new(1920, 1080))],
+            0,
+        );
+        for (region, expected) in [
+            ((0, 0, 3840, 2160), (0, 0, 1920, 1080)),
+            ((0, 0, 3840, 1), (0, 0, 1920, 1)),
+            ((1920, 1080, 1920, 1080), (960, 540, 960, 540)),
+        ] {
+            let mut info = get_capturer_region(region.0, region.1, region.2, region.3).unwrap();
+            assert_eq!((info.width, info.height), (expected.2, expected.3));
+            match info.capturer.frame(Duration::from_millis(10)).unwrap() {
+                Frame::PixelBuffer(f) => {
+                    assert_eq!((f.width(), f.height()), (expected.2, expected.3))
+                }
+                _ => panic!("unexpected frame"),
+            }
+        }
+        set_capture_resolution(None);
+        clear();
+    }
+
+    #[test]
+    fn test_frozen_detection() {
+        let _lock = lock_test();
+        with_mock_display(|| {
+            let mut info = get_capturer().unwrap();
+            info.capturer.frame(Duration::from_millis(10)).unwrap();
+            assert!(!capture_stats().possibly_frozen);
+            set_frozen_frame_threshold(3);
+            for _ in 0..3 {
+                assert!(!capture_stats().possibly_frozen);
+                info.capturer.frame(Duration::from_millis(10)).unwrap();
+            }
+            // The first frame is not identical to any.
+            info.capturer.frame(Duration::from_millis(10)).unwrap();
+            assert!(capture_stats().possibly_frozen);
+            assert_eq!(frozen_displays(true), vec![0]);
+            assert!(!capture_stats().possibly_frozen);
+            set_frozen_frame_threshold(0);
+        });
+
+        assert_ne!(sampled_hash(&[0; 4096]), sampled_hash(&[1; 4096]));
+        assert_ne!(sampled_hash(&[0; 10]), sampled_hash(&[0; 11]));
+    }
+
+    #[test]
+    fn test_frozen_detection_ignores_timeouts() {
+        let _lock = lock_test();
+        let mut capturer = MockCapturer::new(64, 64);
+        capturer.timeout = true;
+        set_mock_displays(vec![(((0, 0), 64, 64), capturer)], 0);
+        let mut info = get_capturer().unwrap();
+        set_frozen_frame_threshold(3);
+        // A static screen, no frame arrives.
+        for _ in 0..10 {
+            let err = info
+                .capturer
+                .frame(Duration::from_millis(10))
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        }
+        assert!(!capture_stats().possibly_frozen);
+        assert!(frozen_displays(true).is_empty());
+        set_frozen_frame_threshold(0);
+        drop(info);
+        clear();
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let _lock = lock_test();
+        set_mock_displays(
+            vec![
+                (((0, 0), 1920, 1080), MockCapturer::new(1920, 1080)),
+                (((1920, 0), 1280, 720), MockCapturer::new(1280, 720)),
+            ],
+            0,
+        );
+        let mut rr = capture_all_round_robin(Duration::ZERO).unwrap();
+        let timeout = Duration::from_millis(10);
+        let mut labels = Vec::new();
+        for _ in 0..3 {
+            let f = rr.next_frame(timeout).unwrap();
+            assert_eq!(f.data.len(), f.format.width * f.format.height * 4);
+            labels.push((f.idx, f.format.width));
+        }
+        assert_eq!(labels, vec![(0, 1920), (1, 1280), (0, 1920)]);
+        // The display in use is skipped.
+        let info = get_capturer_for(1).unwrap();
+        assert_eq!(rr.next_frame(timeout).unwrap().idx, 0);
+        drop(info);
+        clear();
+        assert!(rr.next_frame(timeout).is_err());
+    }
+
+    #[test]
+    fn test_rect_for() {
+        let _lock = lock_test();
+        set_mock_displays(
+            vec![(((-1280, 0), 1280, 1024), MockCapturer::new(1280, 1024))],
+            0,
+        );
+        let info = get_cap_display_info().unwrap();
+        assert_eq!(
+            info.rect_for(0),
+            Some(DisplayRect {
+                origin: (-1280, 0),
+                width: 1280,
+                height: 1024
+            })
+        );
+        assert_eq!(info.rect_for(1), None);
+        drop(info);
+        clear();
+    }
+
+    #[test]
+    fn test_current_display() {
+        let _lock = lock_test();
+        assert_eq!(get_current_display(), None);
+        set_mock_displays(
+            vec![
+                (((0, 0), 1920, 1080), MockCapturer::new(1920, 1080)),
+                (((1920, 0), 1280, 720), MockCapturer::new(1280, 720)),
+            ],
+            1,
+        );
+        assert_eq!(get_current_display(), Some(1));
+        let e = set_current_display(2).unwrap_err();
+        assert_eq!(
+            WaylandError::from_error(&e),
+            Some(&WaylandError::IndexOutOfRange { idx: 2, len: 2 })
+        );
+        assert_eq!(get_current_display(), Some(1));
+        clear();
+    }
+
+    #[test]
+    fn test_select_primary() {
+        assert_eq!(select_primary(0, None, 2), 0);
+        assert_eq!(select_primary(0, Some(1), 2), 1);
+        assert_eq!(select_primary(1, Some(2), 2), 1);
+    }
+
+    #[test]
+    fn test_physical_sizes() {
+        let mut edid = vec![0u8; 128];
+        edid[..8].copy_from_slice(&[0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]);
+        assert_eq!(parse_edid_physical_size(&edid), None);
+        // 2560x1440, 597x336 mm
+        edid[54] = 0x01;
+        edid[56] = (2560 & 0xff) as u8;
+        edid[58] = ((2560 >> 8) << 4) as u8;
+        edid[59] = (1440 & 0xff) as u8;
+        edid[61] = ((1440 >> 8) << 4) as u8;
+        edid[66] = (597 & 0xff) as u8;
+        edid[67] = (336 & 0xff) as u8;
+        edid[68] = (((597 >> 8) << 4) | (336 >> 8)) as u8;
+        let parsed = parse_edid_physical_size(&edid).unwrap();
+        assert_eq!(parsed, ((2560, 1440), (597, 336)));
+        assert_eq!(parse_edid_physical_size(&edid[..100]), None);
+
+        let mut displays = vec![DisplayInfo::default(); 3];
+        (displays[0].width, displays[0].height) = (2560, 1440);
+        (displays[1].width, displays[1].height) = (1440, 2560);
+        (displays[2].width, displays[2].height) = (1920, 1080);
+        set_physical_sizes(&mut displays, &[parsed]);
+        let sizes: Vec<_> = displays
+            .iter()
+            .map(|d| (d.physical_width_mm, d.physical_height_mm))
+            .collect();
+        assert_eq!(sizes, vec![(597, 336), (336, 597), (0, 0)]);
+
+        // Ambiguous
+        let mut displays = vec![DisplayInfo::default(); 1];
+        (displays[0].width, displays[0].height) = (2560, 1440);
+        set_physical_sizes(&mut displays, &[parsed, ((2560, 1440), (700, 390))]);
+        assert_eq!(displays[0].physical_width_mm, 0);
+    }
+
+    #[test]
+    fn test_displays_change_debounce() {
+        let _lock = lock_test();
+        clear();
+        let rects = vec![((0, 0), 1920, 1080)];
+        let burst = [vec![((0, 0), 1280, 720)], vec![((0, 0), 2560, 1440)]];
+
+        set_displays_change_debounce(Duration::from_secs(60));
+        for new_rects in burst.iter() {
+            update_displays_changed(&rects, new_rects.clone());
+        }
+        assert!(!displays_changed());
+        assert_eq!(
+            PENDING_DISPLAYS_CHANGE
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|(r, _)| r.clone()),
+            Some(burst[1].clone())
+        );
+        // Changed back before settling.
+        update_displays_changed(&rects, rects.clone());
+        assert!(PENDING_DISPLAYS_CHANGE.lock().unwrap().is_none());
+        assert!(!displays_changed());
+
+        set_displays_change_debounce(Duration::ZERO);
+        update_displays_changed(&rects, burst[1].clone());
+        assert!(displays_changed());
+        clear();
+        assert!(!displays_changed());
+        set_displays_change_debounce(DEFAULT_DISPLAYS_CHANGE_DEBOUNCE);
     }
-    return "".to_owned();
 }
@@ -0,0 +1,238 @@
+// Publishes the wayland frames in shared memory, so a local helper process can encode them
+// without copying them over a socket.
+//
+// A ring is created per display, named by `shm_name()`, opened by the helper with `shm_open()` and `mmap()`.
+// All the fields are native endian.
+//
+// Header, `HEADER_SIZE` bytes:
+//   magic: u32, `MAGIC`
+//   version: u32, `VERSION`
+//   slots: u32
+//   slot_size: u32, the bytes of a slot, including its header
+//   closed: u32, set before the ring is removed, the helper should open it again by the name
+//   _: u32
+//   latest: u64, the sequence number of the latest published frame, 0 before the first frame
+//
+// Slot `seq % slots`, at `HEADER_SIZE + (seq % slots) * slot_size`:
+//   lock: u64, odd while the slot is being written, the helper retries if it changes while reading
+//   seq: u64, the sequence number of the frame in the slot
+//   width: u32
+//   height: u32
+//   stride: u32
+//   pixfmt: u32, 0 for BGRA, 1 for RGBA
+//   len: u32, the bytes of the data
+//   _: u32
+//   timestamp_ns: u64, 0 if unknown
+//   data: `len` bytes, from `SLOT_HEADER_SIZE` of the slot
+use super::wayland::FrameView;
+use hbb_common::{bail, libc, log, ResultType};
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+const MAGIC: u32 = 0x5357_4452; // "RDWS"
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 64;
+const SLOT_HEADER_SIZE: usize = 64;
+// A few slots, so the helper reading a slot is not overwritten by the next frame.
+const DEFAULT_SLOTS: usize = 3;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref RINGS: Mutex<HashMap<usize, ShmRing>> = Default::default();
+}
+
+// Off by default. The rings are created on the next frame of each display, and removed if disabled.
+pub fn set_enabled(enabled: bool) {
+    let was_enabled = ENABLED.swap(enabled, Ordering::SeqCst);
+    if enabled && !was_enabled {
+        log::info!("Publish the wayland frames in shared memory");
+    } else if !enabled && was_enabled {
+        clear();
+    }
+}
+
+#[inline]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// The name of the ring of the display, for `shm_open()`, `None` before its first frame is published.
+pub fn shm_name(idx: usize) -> Option<String> {
+    RINGS.lock().unwrap().get(&idx).map(|r| r.name.clone())
+}
+
+// Removes the rings, e.g. the capture is cleared.
+pub fn clear() {
+    RINGS.lock().unwrap().clear();
+}
+
+pub(super) fn publish(idx: usize, view: &FrameView, timestamp_ns: Option<u64>) {
+    let pixfmt = match view.pixfmt {
+        scrap::Pixfmt::BGRA => 0,
+        scrap::Pixfmt::RGBA => 1,
+        _ => return,
+    };
+    let len = view.stride * view.height;
+    let mut rings = RINGS.lock().unwrap();
+    // Recreated if the frame is larger, the helper sees `closed` of the old one.
+    if rings
+        .get(&idx)
+        .map_or(true, |r| r.slot_size < SLOT_HEADER_SIZE + len)
+    {
+        rings.remove(&idx);
+        match ShmRing::create(&new_name(idx), DEFAULT_SLOTS, SLOT_HEADER_SIZE + len) {
+            Ok(ring) => {
+                rings.insert(idx, ring);
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to create the shared memory of display {}, {}",
+                    idx,
+                    e
+                );
+                return;
+            }
+        }
+    }
+    if let Some(ring) = rings.get_mut(&idx) {
+        ring.write(view, pixfmt, timestamp_ns.unwrap_or_default());
+    }
+}
+
+// Unique per ring, a helper never opens a removed ring by an old name.
+fn new_name(idx: usize) -> String {
+    static GENERATION: AtomicU32 = AtomicU32::new(0);
+    format!(
+        "/{}_wayland_{}_{}_{}",
+        hbb_common::config::APP_NAME.read().unwrap().to_lowercase(),
+        std::process::id(),
+        idx,
+        GENERATION.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+struct ShmRing {
+    name: String,
+    ptr: *mut u8,
+    size: usize,
+    slots: usize,
+    slot_size: usize,
+    seq: u64,
+}
+
+unsafe impl Send for ShmRing {}
+
+impl ShmRing {
+    fn create(name: &str, slots: usize, slot_size: usize) -> ResultType<Self> {
+        // Aligned for the atomics of the next slot.
+        let slot_size = (slot_size + 63) / 64 * 64;
+        let size = HEADER_SIZE + slots * slot_size;
+        let cname = CString::new(name)?;
+        unsafe {
+            let fd = libc::shm_open(
+                cname.as_ptr(),
+                libc::O_CREAT | libc::O_EXCL | libc::O_RDWR,
+                0o600,
+            );
+            if fd < 0 {
+                bail!("shm_open {}: {}", name, std::io::Error::last_os_error());
+            }
+            if libc::ftruncate(fd, size as _) != 0 {
+                let e = std::io::Error::last_os_error();
+                libc::close(fd);
+                libc::shm_unlink(cname.as_ptr());
+                bail!("ftruncate {}: {}", name, e);
+            }
+            let ptr = libc::mmap(
+                std::ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            );
+            libc::close(fd);
+            if ptr == libc::MAP_FAILED {
+                let e = std::io::Error::last_os_error();
+                libc::shm_unlink(cname.as_ptr());
+                bail!("mmap {}: {}", name, e);
+            }
+            let ring = Self {
+                name: name.to_owned(),
+                ptr: ptr as _,
+                size,
+                slots,
+                slot_size,
+                seq: 0,
+            };
+            // The memory of `ftruncate()` is zeroed.
+            ring.put_u32(0, MAGIC);
+            ring.put_u32(4, VERSION);
+            ring.put_u32(8, slots as _);
+            ring.put_u32(12, slot_size as _);
+            log::info!("Created the shared memory {}, size: {}", name, size);
+            Ok(ring)
+        }
+    }
+
+    fn write(&mut self, view: &FrameView, pixfmt: u32, timestamp_ns: u64) {
+        self.seq += 1;
+        let slot = HEADER_SIZE + (self.seq as usize % self.slots) * self.slot_size;
+        let len = view.stride * view.height;
+        let lock = self.atomic_u64(slot);
+        let locked = lock.load(Ordering::Relaxed) | 1;
+        lock.store(locked, Ordering::Relaxed);
+        std::sync::atomic::fence(Ordering::Release);
+        self.put_u64(slot + 8, self.seq);
+        self.put_u32(slot + 16, view.width as _);
+        self.put_u32(slot + 20, view.height as _);
+        self.put_u32(slot + 24, view.stride as _);
+        self.put_u32(slot + 28, pixfmt);
+        self.put_u32(slot + 32, len as _);
+        self.put_u64(slot + 40, timestamp_ns);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                view.data.as_ptr(),
+                self.ptr.add(slot + SLOT_HEADER_SIZE),
+                len,
+            );
+        }
+        lock.store(locked + 1, Ordering::Release);
+        self.atomic_u64(24).store(self.seq, Ordering::Release);
+    }
+
+    fn atomic_u64(&self, offset: usize) -> &AtomicU64 {
+        debug_assert!(offset % 8 == 0 && offset + 8 <= self.size);
+        unsafe { &*(self.ptr.add(offset) as *const AtomicU64) }
+    }
+
+    fn put_u32(&self, offset: usize, v: u32) {
+        debug_assert!(offset + 4 <= self.size);
+        unsafe { std::ptr::write_volatile(self.ptr.add(offset) as *mut u32, v) }
+    }
+
+    fn put_u64(&self, offset: usize, v: u64) {
+        debug_assert!(offset + 8 <= self.size);
+        unsafe { std::ptr::write_volatile(self.ptr.add(offset) as *mut u64, v) }
+    }
+}
+
+impl Drop for ShmRing {
+    fn drop(&mut self) {
+        self.put_u32(16, 1);
+        unsafe {
+            libc::munmap(self.ptr as _, self.size);
+            if let Ok(name) = CString::new(self.name.clone()) {
+                libc::shm_unlink(name.as_ptr());
+            }
+        }
+        log::info!("Removed the shared memory {}", self.name);
+    }
+}
@@ -372,6 +372,23 @@ impl<T: InvokeUiSession> Session<T> {
         }
     }
 
+    // Called when the window is minimized or loses the focus, the peer stops capturing meanwhile.
+    pub fn set_capture_paused(&self, paused: bool) {
+        let mut misc = Misc::new();
+        misc.set_option(OptionMessage {
+            pause_capture: if paused {
+                BoolOption::Yes
+            } else {
+                BoolOption::No
+            }
+            .into(),
+            ..Default::default()
+        });
+        let mut msg_out = Message::new();
+        msg_out.set_misc(misc);
+        self.send(Data::Message(msg_out));
+    }
+
     pub fn toggle_virtual_display(&self, index: i32, on: bool) {
         let mut misc = Misc::new();
         misc.set_toggle_virtual_display(ToggleVirtualDisplay {